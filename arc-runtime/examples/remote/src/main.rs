@@ -1,128 +1,77 @@
-#![feature(arbitrary_self_types)]
-#![allow(unused_mut)]
-#![allow(unreachable_code)]
-
-use arc_runtime::channels::local::concurrent::channel;
-use arc_runtime::channels::local::concurrent::Pullable;
-use arc_runtime::channels::local::concurrent::Pushable;
+use arc_runtime::cluster::Graphs;
+use arc_runtime::cluster::Master;
+use arc_runtime::cluster::Worker;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
 use arc_runtime::prelude::*;
 
-lazy_static::lazy_static! {
-    static ref EXECUTOR: Executor = Executor::new();
-}
-
-#[derive(Actor, ComponentDefinition)]
-struct Source<I: IntoIterator<Item = T> + Data, T: Data>
-where
-    <I as IntoIterator>::IntoIter: Data,
-{
-    ctx: ComponentContext<Self>,
-    iter: I,
-    pushable: Pushable<T>,
-}
-impl<I: IntoIterator<Item = T> + Data, T: Data> Source<I, T>
-where
-    <I as IntoIterator>::IntoIter: Data,
-{
-    fn new(iter: I, pushable: Pushable<T>) -> Self {
-        Self {
-            ctx: ComponentContext::uninitialised(),
-            iter,
-            pushable,
-        }
-    }
+use std::time::Duration;
 
-    async fn run(mut self: ComponentDefinitionAccess<Self>) -> Control<()> {
-        let i = self.iter.clone();
-        for x in i {
-            self.pushable.push(x).await?;
-        }
-        Control::Finished
+#[rewrite(nonpersistent)]
+async fn source(mut i: Vec<i32>, #[output] mut o: Pushable<i32>) {
+    for x in i.into_iter().cloned() {
+        push!(o, x);
     }
 }
 
-#[derive(Actor, ComponentDefinition)]
-struct Log<T: Data> {
-    ctx: ComponentContext<Self>,
-    pullable: Pullable<T>,
-}
-
-impl<T: Data> Log<T> {
-    fn new(pullable: Pullable<T>) -> Self {
-        Self {
-            ctx: ComponentContext::uninitialised(),
-            pullable,
-        }
-    }
-
-    async fn run(mut self: ComponentDefinitionAccess<Self>) -> Control<()> {
-        loop {
-            let data = self.pullable.pull().await?;
-//             info!(self.log(), "{:?}", data);
-        }
+#[rewrite(nonpersistent)]
+async fn log(mut i: Pullable<i32>) {
+    loop {
+        println!("{}", pull!(i));
     }
 }
 
-impl<I: IntoIterator<Item = T> + Data, T: Data> ComponentLifecycle for Source<I, T>
-where
-    <I as IntoIterator>::IntoIter: Data,
-{
-    fn on_start(&mut self) -> Handled {
-        self.spawn_local(move |async_self| async move {
-            async_self.run().await;
-            Handled::DieNow
-        });
-        Handled::Ok
-    }
+fn square(x: i32, _: Context) -> i32 {
+    x * x
 }
 
-impl<T: Data> ComponentLifecycle for Log<T> {
-    fn on_start(&mut self) -> Handled {
-        self.spawn_local(move |async_self| async move {
-            async_self.run().await;
-            Handled::DieNow
-        });
-        Handled::Ok
-    }
-}
-fn source<I: Data, T: Data>(i: I) -> Pullable<T>
-where
-    I: IntoIterator<Item = T>,
-    <I as IntoIterator>::IntoIter: Data,
-{
-    let (a0, a1) = channel(&EXECUTOR);
-    EXECUTOR.create_task(move || Source::new(i, a0));
-    a1
-}
-
-fn log<T: Data>(a: Pullable<T>) {
-    EXECUTOR.create_task(move || Log::new(a));
-}
-
-fn read_stream() -> Pullable<i32> {
-    todo!()
-}
-
-fn master() {
-    log(source(0..100));
-}
-
-fn worker() {
-    todo!()
+fn squares() -> DataflowBuilder {
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("source", |ctx| {
+        let mut v: Vec<i32> = Vec::new(ctx);
+        (0..100).for_each(|x| v.push(x, ctx));
+        source((v,), ctx)
+    });
+    builder.set_sendable(s);
+    let s = builder.add_map("square", s, square);
+    builder.set_sendable(s);
+    builder.add_sink("log", s, |s, ctx| log((s,), ctx));
+    builder
 }
 
+/// Usage:
+///
+/// ```text
+/// remote --master <addr> <workers>
+/// remote --worker <addr> <master addr>
+/// ```
 fn main() {
-    EXECUTOR.init({
-        let mut cfg = KompactConfig::default();
-        cfg.load_config_file("./application.conf");
-        cfg.system_components(DeadletterBox::new, NetworkConfig::default().build());
-        cfg.build().expect("KompactSystem")
-    });
-
-    match std::env::args().nth(1).as_ref().map(|x| x.as_str()) {
-        Some("--master") => master(),
-        Some("--worker") => worker(),
-        _ => panic!("Expected --master or --worker"),
+    let graphs = Graphs::new().with("squares", squares);
+    let args = std::env::args().skip(1).collect::<std::vec::Vec<_>>();
+    match args
+        .iter()
+        .map(|x| x.as_str())
+        .collect::<std::vec::Vec<_>>()[..]
+    {
+        ["--master", addr, workers] => {
+            let runtime = Runtime::with_network(addr.parse().expect("Invalid address"));
+            let master = Master::start(&runtime, graphs);
+            let workers = workers.parse().expect("Invalid number of workers");
+            if !master.await_workers(workers, Duration::from_secs(60)) {
+                panic!("Expected {} workers", workers);
+            }
+            let job = master.submit("squares").unwrap();
+            let status = job.await_completion();
+            println!("Job {} completed: {:?}", job.id(), status);
+            runtime.shutdown();
+            std::process::exit(status.exit_code());
+        }
+        ["--worker", addr, master] => {
+            let runtime = Runtime::with_network(addr.parse().expect("Invalid address"));
+            let master = master.parse().expect("Invalid master address");
+            Worker::start(&runtime, master, graphs).await_completed(1);
+            runtime.shutdown();
+        }
+        _ => panic!("Expected --master <addr> <workers> or --worker <addr> <master addr>"),
     }
-    EXECUTOR.await_termination();
 }
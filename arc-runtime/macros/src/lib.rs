@@ -12,6 +12,7 @@ mod proc_macro_attrs {
         pub mod functions;
         pub mod nonpersistent_tasks;
         pub mod persistent_tasks;
+        pub mod state_machines;
         pub mod structs;
     }
 }
@@ -82,12 +83,23 @@ pub fn call(input: TokenStream) -> TokenStream {
     proc_macros::call(syn::parse_macro_input!(input as syn::Expr))
 }
 
+/// Calls a function value or a closure. The environment of a closure is passed to its
+/// function after the arguments.
 #[proc_macro]
 pub fn call_indirect(input: TokenStream) -> TokenStream {
     proc_macros::call_indirect(syn::parse_macro_input!(input as syn::Expr))
 }
 
-/// Enwraps a value into an enum-variant.
+/// Calls a function value like `call_indirect!`, but catches panics. If the call keeps
+/// panicking after the retries of the task, the argument is routed to the dead letters of the
+/// task and `None` is returned.
+#[proc_macro]
+pub fn call_protected(input: TokenStream) -> TokenStream {
+    proc_macros::call_protected(syn::parse_macro_input!(input as syn::Expr))
+}
+
+/// Enwraps values into an enum-variant. Unit variants take no values, tuple variants take one
+/// value per field, and struct-like variants are constructed like structs.
 ///
 /// ```
 /// use arc_runtime::prelude::*;
@@ -96,10 +108,15 @@ pub fn call_indirect(input: TokenStream) -> TokenStream {
 ///     #[rewrite]
 ///     pub enum Bar {
 ///         Baz(i32),
-///         Qux(i32)
+///         Qux(i32, i32),
+///         Quux { x: i32 },
+///         Corge,
 ///     }
 /// }
 /// let x = enwrap!(foo::Bar::Baz, 5);
+/// let x = enwrap!(foo::Bar::Qux, 5, 6);
+/// let x = enwrap!(foo::Bar::Quux { x: 5 });
+/// let x = enwrap!(foo::Bar::Corge);
 /// ```
 #[proc_macro]
 pub fn enwrap(input: TokenStream) -> TokenStream {
@@ -127,7 +144,8 @@ pub fn is(input: TokenStream) -> TokenStream {
     proc_macros::is(input)
 }
 
-/// Unwraps a value out of an enum-variant.
+/// Unwraps a value out of a single-field enum-variant. The fields of other variants are unwrapped
+/// as a tuple by naming them in a pattern.
 ///
 /// ```
 /// use arc_runtime::prelude::*;
@@ -136,18 +154,57 @@ pub fn is(input: TokenStream) -> TokenStream {
 ///     #[rewrite]
 ///     pub enum Bar {
 ///         Baz(i32),
-///         Qux(i32)
+///         Qux(i32, i32),
+///         Quux { x: i32, y: i32 },
 ///     }
 /// }
 ///
 /// let x = enwrap!(foo::Bar::Baz, 5);
 /// let y = unwrap!(foo::Bar::Baz, x);
+/// let x = enwrap!(foo::Bar::Qux, 5, 6);
+/// let (y, z) = unwrap!(foo::Bar::Qux(_, _), x);
+/// let x = enwrap!(foo::Bar::Quux { x: 5, y: 6 });
+/// let (y, z) = unwrap!(foo::Bar::Quux { x, y }, x);
 /// ```
 #[proc_macro]
 pub fn unwrap(input: TokenStream) -> TokenStream {
     proc_macros::unwrap(input)
 }
 
+/// Matches an enum against patterns of its variants, which may be nested and guarded.
+///
+/// Nested variant patterns are tested by guards, since the fields of a variant are behind their
+/// own `Gc`s. The compiler therefore only checks exhaustiveness for the outermost variants: an
+/// arm with a nested pattern never counts as covering its variant, so every variant which is
+/// only matched through nested patterns needs an arm of its own or a wildcard arm.
+///
+/// ```
+/// use arc_runtime::prelude::*;
+/// mod foo {
+///     use arc_runtime::prelude::*;
+///     #[rewrite]
+///     pub enum Bar {
+///         Baz(i32),
+///         Qux(Bar, i32),
+///         Corge,
+///     }
+/// }
+/// use foo::*;
+///
+/// let x = enwrap!(Baz, 5);
+/// let x = enwrap!(Qux, x, 6);
+/// let y = match_enum!(x, {
+///     Qux(Baz(a), b) if *a > 0 => a + b,
+///     Qux(_, b) => *b,
+///     Baz(a) => *a,
+///     Corge => 0,
+/// });
+/// ```
+#[proc_macro]
+pub fn match_enum(input: TokenStream) -> TokenStream {
+    proc_macros::match_enum(input)
+}
+
 /// Constructs a struct.
 ///
 /// ```
@@ -177,6 +234,8 @@ pub fn erase(input: TokenStream) -> TokenStream {
     proc_macros::erase(input)
 }
 
+/// Unerases a value which was erased by `erase!`. Fails the task through `?` if the value was
+/// erased as another type.
 #[proc_macro]
 pub fn unerase(input: TokenStream) -> TokenStream {
     proc_macros::unerase(input)
@@ -235,6 +294,11 @@ pub fn rewrite(attr: TokenStream, input: TokenStream) -> TokenStream {
         syn::Item::Mod(item) if has_meta_key("persistent", &get_metas(&attr)) => {
             proc_macro_attrs::rewrite::persistent_tasks::rewrite(attr, item)
         }
+        syn::Item::Fn(item)
+            if item.sig.asyncness.is_some() && has_meta_key("persistent", &get_metas(&attr)) =>
+        {
+            proc_macro_attrs::rewrite::persistent_tasks::rewrite_fn(attr, item)
+        }
         syn::Item::Fn(item) if has_meta_key("main", &get_metas(&attr)) => {
             proc_macro_attrs::rewrite::driver::rewrite(attr, item)
         }
@@ -266,6 +330,120 @@ pub(crate) fn has_meta_key(name: &str, meta: &[syn::Meta]) -> bool {
         .any(|m| matches!(m, syn::Meta::Path(x) if x.is_ident(name)))
 }
 
+/// Returns the structural comparison traits which `#[rewrite(eq)]`, `#[rewrite(ord)]` and
+/// `#[rewrite(hash)]` opt into. Ordering and hashing imply equality.
+pub(crate) fn comparison_derives(attr: &[syn::NestedMeta]) -> Vec<syn::Path> {
+    let metas = get_metas(attr);
+    let ord = has_meta_key("ord", &metas);
+    let hash = has_meta_key("hash", &metas);
+    let eq = ord || hash || has_meta_key("eq", &metas);
+    let mut derives = Vec::new();
+    if eq {
+        derives.push(syn::parse_quote!(PartialEq));
+        derives.push(syn::parse_quote!(Eq));
+    }
+    if ord {
+        derives.push(syn::parse_quote!(PartialOrd));
+        derives.push(syn::parse_quote!(Ord));
+    }
+    if hash {
+        derives.push(syn::parse_quote!(Hash));
+    }
+    derives
+}
+
+/// Bounds the type parameters of a rewritten struct or enum by `Sharable`.
+pub(crate) fn sharable_generics(generics: &syn::Generics) -> syn::Generics {
+    let mut generics = generics.clone();
+    generics
+        .type_params_mut()
+        .for_each(|p| p.bounds.push(syn::parse_quote!(Sharable)));
+    generics
+}
+
+/// Bounds the type parameters of a rewritten struct or enum by `Sharable` and `Format`, for the
+/// implementation of `Format` of the struct or enum.
+pub(crate) fn format_generics(generics: &syn::Generics) -> syn::Generics {
+    let mut generics = sharable_generics(generics);
+    generics
+        .type_params_mut()
+        .for_each(|p| p.bounds.push(syn::parse_quote!(Format)));
+    generics
+}
+
+/// Bounds the type parameters of a rewritten struct or enum by `Sharable` and `Schema`, for the
+/// implementation of `Schema` of the struct or enum.
+pub(crate) fn schema_generics(generics: &syn::Generics) -> syn::Generics {
+    let mut generics = sharable_generics(generics);
+    generics
+        .type_params_mut()
+        .for_each(|p| p.bounds.push(syn::parse_quote!(Schema)));
+    generics
+}
+
+/// Describes the fields of a rewritten struct or enum variant by their names and types. The
+/// fields of tuple structs and variants are named by their index.
+pub(crate) fn describe_fields(fields: &syn::Fields) -> pm2::TokenStream {
+    let field = fields.iter().enumerate().map(|(i, f)| {
+        let name = f
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| i.to_string());
+        let ty = &f.ty;
+        quote::quote!(schema.field::<#ty>(#name);)
+    });
+    quote::quote!(#(#field)*)
+}
+
+/// Bounds the type parameters of the sendable form of a rewritten struct or enum, whose fields
+/// hold the sendable forms of the parameters. These must convert back to the parameters and
+/// implement the traits which the sendable form derives.
+pub(crate) fn sendable_generics(generics: &syn::Generics, derives: &[syn::Path]) -> syn::Generics {
+    let mut generics = sharable_generics(generics);
+    let params = generics
+        .type_params()
+        .map(|p| p.ident.clone())
+        .collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for p in params {
+        where_clause.predicates.push(syn::parse_quote!(
+            <#p as DynSharable>::T: Sendable<T = #p> + Debug #(+ #derives)*
+        ));
+    }
+    generics
+}
+
+/// Qualifies the type of a field of a rewritten struct or enum, which is moved into a submodule,
+/// unless the type is one of the type parameters.
+pub(crate) fn qualify_field_type(ty: &syn::Type, generics: &syn::Generics) -> syn::Type {
+    match ty {
+        syn::Type::Path(p)
+            if p.qself.is_none() && generics.type_params().any(|t| p.path.is_ident(&t.ident)) =>
+        {
+            ty.clone()
+        }
+        _ => syn::parse_quote!(super::#ty),
+    }
+}
+
+/// Returns the members of the fields of an enum variant, along with identifiers to bind them to.
+/// Patterns and constructors of the form `Variant { member: binding, .. }` work for unit, tuple
+/// and struct-like variants alike.
+pub(crate) fn variant_fields(fields: &syn::Fields) -> (Vec<syn::Member>, Vec<syn::Ident>) {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let member = match &f.ident {
+                Some(ident) => syn::Member::Named(ident.clone()),
+                None => syn::Member::Unnamed(syn::Index::from(i)),
+            };
+            (member, new_id(format!("x{}", i)))
+        })
+        .unzip()
+}
+
 pub(crate) fn has_meta_name_val(name: &str, meta: &[syn::Meta]) -> bool {
     meta.iter()
         .any(|m| matches!(m, syn::Meta::NameValue(x) if x.path.is_ident(name)))
@@ -7,48 +7,38 @@ pub(crate) fn rewrite(_attr: syn::AttributeArgs, item: syn::ItemFn) -> pm::Token
 
 #[cfg(not(feature = "legacy"))]
 pub(crate) fn rewrite(_attr: syn::AttributeArgs, item: syn::ItemFn) -> pm::TokenStream {
+    use crate::has_attr_key;
     use crate::new_id;
     let block = &item.block;
-    let id = item.sig.ident;
-    let component_id = new_id(format!("{}Component", id));
+    let attrs = &item.attrs;
+    let id = &item.sig.ident;
     let run_id = new_id(format!("{}_run", id));
+    // A test reports a failed job by panicking, since exiting would abort the other tests.
+    let check = if has_attr_key("test", attrs) {
+        quote::quote!(assert_eq!(status, JobStatus::Finished, "Job did not finish");)
+    } else {
+        quote::quote!(if status != JobStatus::Finished {
+            std::process::exit(status.exit_code());
+        })
+    };
 
     quote::quote! (
 
-        #[derive(ComponentDefinition, Actor)]
-        struct #component_id {
-            ctx: ComponentContext<Self>,
-        }
-
-        impl #component_id {
-            fn new() -> Self {
-                Self {
-                    ctx: ComponentContext::uninitialised()
-                }
-            }
-        }
-
         #[rewrite]
         fn #run_id() {
             #block
         }
 
-        impl ComponentLifecycle for #component_id {
-            fn on_start(&mut self) -> Handled {
-                let component = self.ctx().component();
-                let mutator = instantiate_immix(ImmixOptions::default());
-                let ctx = Context::new(component, mutator);
-                call!(#run_id());
-                self.ctx().system().shutdown_async();
-                Handled::DieNow
-            }
-        }
-
+        #(#attrs)*
         fn #id() {
-            let system = KompactConfig::default().build().unwrap();
-            let component = system.create(move || #component_id::new());
-            system.start(&component);
-            system.await_termination();
+            let runtime = Runtime::new();
+            let job = runtime.submit(move |ctx| call!(#run_id()));
+            let status = job.await_completion();
+            if let JobStatus::Failed(error) = &status {
+                error!(runtime.system.logger(), "Job failed: {}", error);
+            }
+            runtime.shutdown();
+            #check
         }
     )
     .into()
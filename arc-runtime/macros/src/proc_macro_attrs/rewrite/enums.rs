@@ -3,16 +3,50 @@
 use proc_macro as pm;
 use quote::quote;
 
+use crate::comparison_derives;
+use crate::describe_fields;
+use crate::format_generics;
 use crate::new_id;
+use crate::qualify_field_type;
+use crate::schema_generics;
+use crate::sendable_generics;
+use crate::sharable_generics;
+use crate::variant_fields;
 
 #[cfg(not(feature = "legacy"))]
 #[allow(unused)]
-pub(crate) fn rewrite(_: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm::TokenStream {
+pub(crate) fn rewrite(args: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm::TokenStream {
+    let derives = comparison_derives(&args);
     let abstract_id = enum_item.ident.clone();
     let concrete_id = new_id(format!("Concrete{}", abstract_id));
     let sharable_mod_id = new_id(format!("sharable_enum_{}", abstract_id));
     let sendable_mod_id = new_id(format!("sendable_enum_{}", abstract_id));
 
+    let sharable_generics = sharable_generics(&enum_item.generics);
+    let sendable_generics = sendable_generics(&enum_item.generics, &derives);
+    let (_, type_generics, sharable_where_clause) = sharable_generics.split_for_impl();
+    let (impl_generics, _, sendable_where_clause) = sendable_generics.split_for_impl();
+    let format_generics = format_generics(&enum_item.generics);
+    let (format_impl_generics, _, format_where_clause) = format_generics.split_for_impl();
+    let schema_generics = schema_generics(&enum_item.generics);
+    let (schema_impl_generics, _, schema_where_clause) = schema_generics.split_for_impl();
+
+    // Describe variants by their names and fields, in the order in which they are declared
+    let describe_variant = enum_item
+        .variants
+        .iter()
+        .map(|v| {
+            let id = &v.ident;
+            let describe_fields = describe_fields(&v.fields);
+            quote!(
+                schema.write(stringify!(#id));
+                schema.write("(");
+                #describe_fields
+                schema.write(")");
+            )
+        })
+        .collect::<Vec<_>>();
+
     let mut concrete_sharable_enum_item = enum_item.clone();
     let mut concrete_sendable_enum_item = enum_item;
 
@@ -20,14 +54,15 @@ pub(crate) fn rewrite(_: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm
 
     concrete_sharable_enum_item.ident = concrete_id.clone();
     concrete_sendable_enum_item.ident = concrete_id.clone();
+    concrete_sharable_enum_item.generics = sharable_generics.clone();
+    concrete_sendable_enum_item.generics = sendable_generics.clone();
 
     concrete_sharable_enum_item
         .variants
         .iter_mut()
         .for_each(|v| {
             v.fields.iter_mut().for_each(|f| {
-                let ty = f.ty.clone();
-                f.ty = syn::parse_quote!(super::#ty);
+                f.ty = qualify_field_type(&f.ty, &sharable_generics);
             })
         });
 
@@ -36,8 +71,8 @@ pub(crate) fn rewrite(_: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm
         .iter_mut()
         .for_each(|v| {
             v.fields.iter_mut().for_each(|f| {
-                let ty = f.ty.clone();
-                f.ty = syn::parse_quote!(<super::#ty as DynSharable>::T);
+                let ty = qualify_field_type(&f.ty, &sendable_generics);
+                f.ty = syn::parse_quote!(<#ty as DynSharable>::T);
             })
         });
 
@@ -47,52 +82,110 @@ pub(crate) fn rewrite(_: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm
         .map(|v| &v.ident)
         .collect::<Vec<_>>();
 
+    let (member, binding): (Vec<_>, Vec<_>) = concrete_sharable_enum_item
+        .variants
+        .iter()
+        .map(|v| variant_fields(&v.fields))
+        .unzip();
+
+    // Format variants like the arc-lang syntax for constructing them
+    let format_variant = concrete_sharable_enum_item
+        .variants
+        .iter()
+        .zip(&binding)
+        .map(|(v, binding)| {
+            let id = &v.ident;
+            match &v.fields {
+                syn::Fields::Unit => quote!(f.write_str(stringify!(#id))),
+                syn::Fields::Unnamed(_) => quote!(
+                    f.debug_tuple(stringify!(#id))
+                        #(.field((&Field::new(#binding)).as_debug()))*
+                        .finish()
+                ),
+                syn::Fields::Named(fields) => {
+                    let name = fields.named.iter().map(|f| &f.ident);
+                    quote!(
+                        f.debug_struct(stringify!(#id))
+                            #(.field(stringify!(#name), (&Field::new(#binding)).as_debug()))*
+                            .finish()
+                    )
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
     quote!(
 
         use arc_runtime::prelude::*;
         pub mod #sharable_mod_id {
             use arc_runtime::prelude::*;
 
-            #[derive(Clone, Debug, Abstract, Send, Sync, Unpin, Alloc, Collectable, Finalize, Trace)]
-            pub struct #abstract_id(pub Gc<#concrete_id>);
+            #[derive(Clone, Debug, Abstract, Send, Sync, Unpin, Alloc, Collectable, Finalize, Trace #(, #derives)*)]
+            pub struct #abstract_id #sharable_generics (pub Gc<#concrete_id #type_generics>) #sharable_where_clause;
 
-            #[derive(Clone, Debug, Collectable, Finalize, Trace)]
+            #[derive(Clone, Debug, Collectable, Finalize, Trace #(, #derives)*)]
             #concrete_sharable_enum_item
         }
 
         pub mod #sendable_mod_id {
             use arc_runtime::prelude::*;
   
-            #[derive(Clone, Debug, From, Abstract, Serialize, Deserialize)]
+            #[derive(Clone, Debug, From, Abstract, Serialize, Deserialize #(, #derives)*)]
+            #[serde(bound = "")]
             #[from(forward)]
-            pub struct #abstract_id(pub Box<#concrete_id>);
+            pub struct #abstract_id #sendable_generics (pub Box<#concrete_id #type_generics>) #sendable_where_clause;
  
-            #[derive(Clone, Debug, Serialize, Deserialize)]
+            #[derive(Clone, Debug, Serialize, Deserialize #(, #derives)*)]
+            #[serde(bound = "")]
             #concrete_sendable_enum_item
         }
 
         use #sharable_mod_id::#abstract_id;
         use #sharable_mod_id::#concrete_id::*;
 
-        impl DynSharable for #sharable_mod_id::#abstract_id {
-            type T = #sendable_mod_id::#abstract_id;
+        impl #impl_generics DynSharable for #sharable_mod_id::#abstract_id #type_generics #sendable_where_clause {
+            type T = #sendable_mod_id::#abstract_id #type_generics;
             fn into_sendable(&self, ctx: Context) -> Self::T {
                 match &*self.0 {
                     #(
-                        #sharable_mod_id::#concrete_id::#variant_id(x) =>
-                        #sendable_mod_id::#concrete_id::#variant_id(x.clone().into_sendable(ctx)).into()
+                        #sharable_mod_id::#concrete_id::#variant_id { #(#member: #binding),* } =>
+                        #sendable_mod_id::#concrete_id::#variant_id {
+                            #(#member: #binding.clone().into_sendable(ctx)),*
+                        }.into()
                     ),*
                 }
             }
         }
 
-        impl DynSendable for #sendable_mod_id::#abstract_id {
-            type T = #sharable_mod_id::#abstract_id;
+        impl #format_impl_generics Format for #sharable_mod_id::#abstract_id #type_generics #format_where_clause {
+            fn format(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                use arc_runtime::data::format::field::*;
+                match &*self.0 {
+                    #(
+                        #sharable_mod_id::#concrete_id::#variant_id { #(#member: #binding),* } =>
+                        #format_variant
+                    ),*
+                }
+            }
+        }
+
+        impl #schema_impl_generics Schema for #sharable_mod_id::#abstract_id #type_generics #schema_where_clause {
+            fn describe(schema: &mut SchemaWriter) {
+                schema.compound::<Self>(stringify!(#abstract_id), |schema| {
+                    #(#describe_variant)*
+                });
+            }
+        }
+
+        impl #impl_generics DynSendable for #sendable_mod_id::#abstract_id #type_generics #sendable_where_clause {
+            type T = #sharable_mod_id::#abstract_id #type_generics;
             fn into_sharable(&self, ctx: Context) -> Self::T {
                 match &*self.0 {
                     #(
-                        #sendable_mod_id::#concrete_id::#variant_id(x) =>
-                        #sharable_mod_id::#concrete_id::#variant_id(x.into_sharable(ctx)).alloc(ctx)
+                        #sendable_mod_id::#concrete_id::#variant_id { #(#member: #binding),* } =>
+                        #sharable_mod_id::#concrete_id::#variant_id {
+                            #(#member: #binding.into_sharable(ctx)),*
+                        }.alloc(ctx)
                     ),*
                 }
             }
@@ -104,20 +197,23 @@ pub(crate) fn rewrite(_: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm
 
 #[cfg(feature = "legacy")]
 #[allow(unused)]
-pub(crate) fn rewrite(_: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm::TokenStream {
+pub(crate) fn rewrite(args: syn::AttributeArgs, mut enum_item: syn::ItemEnum) -> pm::TokenStream {
+    let derives = comparison_derives(&args);
     let abstract_id = enum_item.ident.clone();
     let concrete_id = new_id(format!("Concrete{}", abstract_id));
     let mod_id = new_id(format!("send_{}", abstract_id));
 
     enum_item.ident = concrete_id.clone();
+    let generics = &enum_item.generics;
+    let (_, type_generics, where_clause) = generics.split_for_impl();
 
     quote!(
 
-        #[derive(Clone, Debug, From)]
+        #[derive(Clone, Debug, From #(, #derives)*)]
         #[from(forward)]
-        pub struct #abstract_id(pub std::rc::Rc<#concrete_id>);
+        pub struct #abstract_id #generics (pub std::rc::Rc<#concrete_id #type_generics>) #where_clause;
 
-        #[derive(Clone, Debug)]
+        #[derive(Clone, Debug #(, #derives)*)]
         #enum_item
 
         use #concrete_id::*;
@@ -72,25 +72,28 @@ pub(crate) fn rewrite(attr: syn::AttributeArgs, item: syn::ItemFn) -> pm::TokenS
             #[derive(Send)]
             struct Task {
                 pub ctx: ComponentContext<Self>,
+                pub state: Arc<TaskState>,
                 pub event_time: DateTime,
-                #(pub #iparam_name: #iparam_type,)*
+                #(pub #iparam_name: <#iparam_type as DynSharable>::T,)*
                 #(pub #oparam_name: #oparam_type,)*
             }
 
             #[allow(unused_parens)]
             pub fn #task_name((#(#iparam_name,)*): (#(#iparam_type,)*), ctx: Context) -> (#(#oparam_pull_type),*) {
+                // The inputs are kept in sendable form, and moved onto the heap of the task when
+                // it starts, since the context which launches the task may be destroyed first.
                 #(let #iparam_name = #iparam_name.into_sendable(ctx);)*
-                #(let #iparam_name = #iparam_name.into_sharable(ctx);)*
                 #(let (#oparam_name, #oparam_pull_name) = <#oparam_type as Channel>::channel(ctx);)*
-                ctx.launch(move || Task::new(#(#iparam_name,)* #(#oparam_name,)*));
+                ctx.supervise(move |state| Task::new(state, #(#iparam_name.clone(),)* #(#oparam_name.clone(),)*));
                 (#(#oparam_pull_name),*)
             }
 
             impl Task {
                 #[allow(deprecated)] // NOTE: DateTime::unix_epoch is deprecated
-                fn new(#(#iparam_name: #iparam_type,)* #(#oparam_name: #oparam_type,)*) -> Self {
+                fn new(state: Arc<TaskState>, #(#iparam_name: <#iparam_type as DynSharable>::T,)* #(#oparam_name: #oparam_type,)*) -> Self {
                     Self {
                         ctx: ComponentContext::uninitialised(),
+                        state,
                         event_time: DateTime::new(date!(1970-01-01), time!(0:0:0)),
                         #(#iparam_name,)*
                         #(#oparam_name,)*
@@ -128,12 +131,14 @@ pub(crate) fn rewrite(attr: syn::AttributeArgs, item: syn::ItemFn) -> pm::TokenS
             impl Actor for Task {
                 type Message = TaskMessage;
 
-                fn receive_local(&mut self, _: Self::Message) -> Handled {
+                fn receive_local(&mut self, msg: Self::Message) -> Handled {
+                    self.state.handle(msg);
                     Handled::Ok
                 }
 
                 fn receive_network(&mut self, _: NetMessage) -> Handled {
-                    todo!()
+                    warn!(self.log(), "Ignoring network message sent to a local task");
+                    Handled::Ok
                 }
             }
 
@@ -142,10 +147,11 @@ pub(crate) fn rewrite(attr: syn::AttributeArgs, item: syn::ItemFn) -> pm::TokenS
                     self.spawn_local(move |async_self| async move {
                         let component = async_self.ctx().component();
                         let mutator = instantiate_immix(ImmixOptions::default());
-                        let ctx = Context::new(component, mutator);
-                        #(let #iparam_name = async_self.#iparam_name.clone();)*
+                        let state = async_self.state.clone();
+                        let ctx = Context::with_state(component, mutator, state.clone());
+                        #(let #iparam_name = async_self.#iparam_name.into_sharable(ctx);)*
                         #(let #oparam_name = async_self.#oparam_name.clone();)*
-                        Task::run(#(#iparam_name,)* #(#oparam_name,)* ctx).await;
+                        state.control(Task::run(#(#iparam_name,)* #(#oparam_name,)* ctx)).await;
                         ctx.destroy();
                         Handled::DieNow
                     });
@@ -4,6 +4,8 @@ use crate::has_attr_key;
 use crate::new_id;
 use crate::split_name_type;
 
+use super::state_machines::StateMachine;
+
 use proc_macro as pm;
 use proc_macro2 as pm2;
 use quote::quote;
@@ -35,8 +37,6 @@ use syn::token::Comma;
 pub(crate) fn rewrite(attr: syn::AttributeArgs, item: syn::ItemMod) -> pm::TokenStream {
     let task_name = item.ident.clone();
 
-    let mod_name = new_id(format!("mod_{task_name}"));
-
     let items = item.content.expect("Expected module to contain items").1;
 
     let mut state = items
@@ -76,11 +76,95 @@ pub(crate) fn rewrite(attr: syn::AttributeArgs, item: syn::ItemMod) -> pm::Token
         })
         .expect(r#"Expected a function with name "task" in module"#);
 
-    let (iparams, oparams): (Vec<_>, Vec<_>) =
-        task.sig.inputs.clone().into_iter().partition(|p| match p {
+    let (iparams, oparams) = split_params(task.sig.inputs.clone());
+    let (iparam_name, _) = split_name_type(iparams);
+    let (oparam_name, _) = split_name_type(oparams);
+
+    let items = quote!(
+        #[derive(From)]
+        enum State {
+            #(#state_name(#state_name),)*
+        }
+
+        #(#[derive(New)] #state)*
+
+        #(#transition)*
+
+        fn transition(mut state: State, cx: &mut PollContext, ctx: Context) -> (Poll<()>, State) {
+            for _ in 0..arc_runtime::task::state::STEP_BUDGET {
+                let (poll, new_state) = match state {
+                    #(State::#state_name(state) => #transition_name(state, cx, ctx),)*
+                };
+                match &poll {
+                    Ready(()) if matches!(&new_state, State::#final_state_name(_)) => return (poll, new_state),
+                    Ready(()) => state = new_state,
+                    Pending => return (poll, new_state),
+                }
+            }
+            cx.waker().wake_by_ref();
+            (Pending, state)
+        }
+    );
+
+    let initial = quote!(#first_state_name::new(#(#iparam_name,)* #(#oparam_name,)*).into());
+
+    generate(task_name, task.sig.inputs.clone(), items, initial).into()
+}
+
+/// ```no_run
+/// #[rewrite(persistent)]
+/// async fn my_task(a: Pullable<i32>, #[output] b: Pushable<i32>) {
+///     loop {
+///         let x: i32 = pull!(a);
+///         push!(b, x);
+///     }
+/// }
+/// ```
+///
+/// Is compiled into the states and transitions of the module form above, see `state_machines`.
+pub(crate) fn rewrite_fn(attr: syn::AttributeArgs, item: syn::ItemFn) -> pm::TokenStream {
+    let task_name = item.sig.ident.clone();
+    let params = item
+        .sig
+        .inputs
+        .iter()
+        .map(|p| match p {
+            syn::FnArg::Typed(p) => match &*p.pat {
+                syn::Pat::Ident(i) => (i.ident.clone(), (*p.ty).clone()),
+                _ => panic!("Expected task parameters to be identifiers"),
+            },
             syn::FnArg::Receiver(_) => unreachable!(),
-            syn::FnArg::Typed(p) => !has_attr_key("output", &p.attrs),
-        });
+        })
+        .collect();
+    let generated = StateMachine::new(params, *item.block).generate();
+    generate(
+        task_name,
+        item.sig.inputs,
+        generated.items,
+        generated.initial,
+    )
+    .into()
+}
+
+/// Splits the parameters of a task into its inputs and its `#[output]`s.
+fn split_params(inputs: Punctuated<syn::FnArg, Comma>) -> (Vec<syn::FnArg>, Vec<syn::FnArg>) {
+    inputs.into_iter().partition(|p| match p {
+        syn::FnArg::Receiver(_) => unreachable!(),
+        syn::FnArg::Typed(p) => !has_attr_key("output", &p.attrs),
+    })
+}
+
+/// Generates the component of a task, given the items which define its `State` enum and
+/// `transition` function, and an expression for its initial state.
+fn generate(
+    task_name: syn::Ident,
+    inputs: Punctuated<syn::FnArg, Comma>,
+    items: pm2::TokenStream,
+    initial: pm2::TokenStream,
+) -> pm2::TokenStream {
+    let mod_name = new_id(format!("mod_{task_name}"));
+
+    let (iparams, oparams) = split_params(inputs);
 
     let (iparam_name, iparam_type): (Vec<_>, Vec<_>) = split_name_type(iparams);
     let (oparam_name, oparam_type): (Vec<_>, Vec<_>) = split_name_type(oparams);
@@ -96,137 +180,127 @@ pub(crate) fn rewrite(attr: syn::AttributeArgs, item: syn::ItemMod) -> pm::Token
         .collect::<Vec<_>>();
 
     quote!(
-            use #mod_name::#task_name;
-            #[allow(clippy::all)]
-            #[allow(non_snake_case)]
-            #[allow(unreachable_code)]
-            #[allow(unused)]
-            pub mod #mod_name {
-                use arc_runtime::prelude::*;
-                use arc_runtime::data::channels::local::multicast::Pushable;
-                use arc_runtime::data::channels::local::multicast::Pullable;
-                use super::*;
-
-                #[derive(Send)]
-                struct Task {
-                    pub ctx: ComponentContext<Self>,
-                    #(pub #iparam_name: #iparam_type,)*
-                    #(pub #oparam_name: #oparam_type,)*
-                }
+        use #mod_name::#task_name;
+        #[allow(clippy::all)]
+        #[allow(non_snake_case)]
+        #[allow(unreachable_code)]
+        #[allow(unused)]
+        pub mod #mod_name {
+            use arc_runtime::prelude::*;
+            use arc_runtime::data::channels::local::multicast::Pushable;
+            use arc_runtime::data::channels::local::multicast::Pullable;
+            use super::*;
+
+            #[derive(Send)]
+            struct Task {
+                pub ctx: ComponentContext<Self>,
+                pub state: Arc<TaskState>,
+                #(pub #iparam_name: <#iparam_type as DynSharable>::T,)*
+                #(pub #oparam_name: #oparam_type,)*
+            }
 
-                impl Task {
-                    fn new(#(#iparam_name: #iparam_type,)* #(#oparam_name: #oparam_type,)*) -> Self {
-                        Self {
-                            ctx: ComponentContext::uninitialised(),
-                            #(#iparam_name,)*
-                            #(#oparam_name,)*
-                        }
+            impl Task {
+                fn new(state: Arc<TaskState>, #(#iparam_name: <#iparam_type as DynSharable>::T,)* #(#oparam_name: #oparam_type,)*) -> Self {
+                    Self {
+                        ctx: ComponentContext::uninitialised(),
+                        state,
+                        #(#iparam_name,)*
+                        #(#oparam_name,)*
                     }
                 }
+            }
 
-                pub fn #task_name((#(#iparam_name,)*): (#(#iparam_type,)*), ctx: Context) -> (#(#oparam_pull_type),*) {
-                    #(let #iparam_name = #iparam_name.into_sendable(ctx);)*
-                    #(let #iparam_name = #iparam_name.into_sharable(ctx);)*
-                    #(let (#oparam_name, #oparam_pull_name) = <#oparam_type as Channel>::channel(ctx);)*
-                    ctx.launch(move || Task::new(#(#iparam_name,)* #(#oparam_name,)*));
-                    (#(#oparam_pull_name),*)
-                }
-
-                struct Pair(State, Context);
-
-                #[derive(From)]
-                enum State {
-                    #(#state_name(#state_name),)*
-                }
+            pub fn #task_name((#(#iparam_name,)*): (#(#iparam_type,)*), ctx: Context) -> (#(#oparam_pull_type),*) {
+                // The inputs are kept in sendable form, and moved onto the heap of the task when
+                // it starts, since the context which launches the task may be destroyed first.
+                #(let #iparam_name = #iparam_name.into_sendable(ctx);)*
+                #(let (#oparam_name, #oparam_pull_name) = <#oparam_type as Channel>::channel(ctx);)*
+                ctx.supervise(move |state| Task::new(state, #(#iparam_name.clone(),)* #(#oparam_name.clone(),)*));
+                (#(#oparam_pull_name),*)
+            }
 
-                #(#[derive(New)] #state)*
+            /// The state is taken out while the task runs, so that a task which panics drops it,
+            /// along with its channels.
+            struct Pair(Option<State>, Context);
 
-                #(#transition)*
+            #items
 
-                impl Future for Pair {
-                    type Output = ();
+            impl Future for Pair {
+                type Output = ();
 
-                    fn poll(self: Pin<&mut Self>, cx: &mut PollContext) -> Poll<Self::Output> {
-                        cx.waker().wake_by_ref();
-                        let Pair(state, ctx) = self.get_mut();
-                        replace_with_or_abort_and_return(state, |state| transition(state, cx, *ctx))
-                    }
+                // NOTE: The task is woken up by the futures which it is waiting on, or by itself
+                // when it yields after using up its step budget.
+                fn poll(self: Pin<&mut Self>, cx: &mut PollContext) -> Poll<Self::Output> {
+                    let Pair(state, ctx) = self.get_mut();
+                    let (poll, next) = transition(state.take().unwrap(), cx, *ctx);
+                    *state = Some(next);
+                    poll
                 }
+            }
 
-                fn transition(mut state: State, cx: &mut PollContext, ctx: Context) -> (Poll<()>, State) {
-                    loop {
-                        let (poll, new_state) = match state {
-                            #(State::#state_name(state) => #transition_name(state, cx, ctx),)*
-                        };
-                        match &poll {
-                            Ready(()) if matches!(&new_state, State::#final_state_name(_)) => return (poll, new_state),
-                            Ready(()) => state = new_state,
-                            Pending => return (poll, new_state),
-                        }
-                    }
+            impl ComponentDefinition for Task {
+                fn setup(&mut self, self_component: Arc<Component<Self>>) {
+                    self.ctx.initialise(self_component.clone());
                 }
 
-                impl ComponentDefinition for Task {
-                    fn setup(&mut self, self_component: Arc<Component<Self>>) {
-                        self.ctx.initialise(self_component.clone());
-                    }
-
-                    fn execute(&mut self, _max_events: usize, _skip: usize) -> ExecuteResult {
-                        ExecuteResult::new(false, 0, 0)
-                    }
+                fn execute(&mut self, _max_events: usize, _skip: usize) -> ExecuteResult {
+                    ExecuteResult::new(false, 0, 0)
+                }
 
-                    fn ctx_mut(&mut self) -> &mut ComponentContext<Self> {
-                        &mut self.ctx
-                    }
+                fn ctx_mut(&mut self) -> &mut ComponentContext<Self> {
+                    &mut self.ctx
+                }
 
-                    fn ctx(&self) -> &ComponentContext<Self> {
-                        &self.ctx
-                    }
+                fn ctx(&self) -> &ComponentContext<Self> {
+                    &self.ctx
+                }
 
-                    fn type_name() -> &'static str {
-                        stringify!(#task_name)
-                    }
+                fn type_name() -> &'static str {
+                    stringify!(#task_name)
                 }
+            }
 
-                impl Actor for Task {
-                    type Message = TaskMessage;
+            impl Actor for Task {
+                type Message = TaskMessage;
 
-                    fn receive_local(&mut self, _: Self::Message) -> Handled {
-                        Handled::Ok
-                    }
+                fn receive_local(&mut self, msg: Self::Message) -> Handled {
+                    self.state.handle(msg);
+                    Handled::Ok
+                }
 
-                    fn receive_network(&mut self, _: NetMessage) -> Handled {
-                        todo!()
-                    }
+                fn receive_network(&mut self, _: NetMessage) -> Handled {
+                    warn!(self.log(), "Ignoring network message sent to a local task");
+                    Handled::Ok
                 }
+            }
 
-                impl ComponentLifecycle for Task {
-                    fn on_start(&mut self) -> Handled {
-                        self.spawn_local(move |mut async_self| async move {
-                            let component = async_self.ctx().component();
-                            let mutator = instantiate_immix(ImmixOptions::default());
-                            let ctx = Context::new(component, mutator);
-                            #(let #iparam_name = async_self.#iparam_name.clone();)*
-                            #(let #oparam_name = async_self.#oparam_name.clone();)*
-                            let state = #first_state_name::new(#(#iparam_name,)* #(#oparam_name,)*).into();
-                            Pair(state, ctx).await;
-                            ctx.destroy();
-                            Handled::DieNow
-                        });
-                        Handled::Ok
-                    }
+            impl ComponentLifecycle for Task {
+                fn on_start(&mut self) -> Handled {
+                    self.spawn_local(move |mut async_self| async move {
+                        let component = async_self.ctx().component();
+                        let mutator = instantiate_immix(ImmixOptions::default());
+                        let state = async_self.state.clone();
+                        let ctx = Context::with_state(component, mutator, state.clone());
+                        #(let #iparam_name = async_self.#iparam_name.into_sharable(ctx);)*
+                        #(let #oparam_name = async_self.#oparam_name.clone();)*
+                        let pair = Pair(Some(#initial), ctx);
+                        state.control(pair).await;
+                        ctx.destroy();
+                        Handled::DieNow
+                    });
+                    Handled::Ok
                 }
+            }
 
-                impl DynamicPortAccess for Task {
-                    fn get_provided_port_as_any(&mut self, _: TypeId) -> Option<&mut dyn Any> {
-                        unreachable!();
-                    }
+            impl DynamicPortAccess for Task {
+                fn get_provided_port_as_any(&mut self, _: TypeId) -> Option<&mut dyn Any> {
+                    unreachable!();
+                }
 
-                    fn get_required_port_as_any(&mut self, _: TypeId) -> Option<&mut dyn Any> {
-                        unreachable!();
-                    }
+                fn get_required_port_as_any(&mut self, _: TypeId) -> Option<&mut dyn Any> {
+                    unreachable!();
                 }
             }
-        )
-    .into()
+        }
+    )
 }
@@ -0,0 +1,911 @@
+//! Compiles the straight-line body of a persistent task into a state machine.
+//!
+//! The body is split into basic blocks at every `pull!` and `push!`, and at every loop or
+//! conditional which contains one. Each block becomes a state. The fields of a state are the
+//! variables which are live when entering the block, as computed by a liveness analysis.
+//!
+//! Every `pull!` and `push!` gets two more states: one which is about to start the operation
+//! and one which is waiting for it to complete. A task can be checkpointed in any state. Waiting
+//! states are checkpointed as if their operation had not started (for `pull!`) or had completed
+//! (for `push!`, since pushing into a local channel never waits). A supervised task saves a
+//! checkpoint every time a `pull!` completes, and when it has to wait, at most once per
+//! checkpoint interval.
+//!
+//! Checkpoints do not contain channels. The channels of a task are wrapped in `HandBack`, so that
+//! a restarted task continues with the receivers of the failed task. Since every record which is
+//! taken out of a receiver is reflected in the latest checkpoint, a restarted task neither loses
+//! nor repeats records. It may however push again the values which it pushed after the latest
+//! checkpoint.
+//!
+//! Limitations:
+//! * `pull!` and `push!` must appear as statements, or as the initialiser of a `let`.
+//! * Variables which are live across a `pull!` or `push!` must have a type annotation.
+//! * Channels must be parameters of the task.
+//! * Loops and conditionals which contain a `pull!` or `push!` must be `loop`, `while` or `if`
+//!   without labels or `let`-conditions.
+
+use crate::new_id;
+
+use proc_macro2 as pm2;
+use quote::quote;
+use syn::visit::Visit;
+
+use std::collections::BTreeSet;
+
+type Vars = BTreeSet<usize>;
+
+struct Var {
+    id: syn::Ident,
+    ty: Option<syn::Type>,
+    param: bool,
+}
+
+struct Block {
+    stmts: Vec<syn::Stmt>,
+    term: Option<Term>,
+}
+
+enum Term {
+    Goto(usize),
+    Branch(syn::Expr, usize, usize),
+    Pull(usize),
+    Push(usize),
+    Return,
+}
+
+enum Op {
+    Pull { bind: Option<usize> },
+    Push { data: syn::Expr },
+}
+
+/// A `pull!` or `push!`.
+struct Suspension {
+    chan: usize,
+    op: Op,
+    next: usize,
+}
+
+/// The control-flow graph of a task body.
+pub(crate) struct StateMachine {
+    vars: Vec<Var>,
+    blocks: Vec<Block>,
+    suspensions: Vec<Suspension>,
+    loops: Vec<(usize, usize)>,
+    current: usize,
+}
+
+impl StateMachine {
+    /// Builds the state machine of a task with the given parameters and body.
+    pub(crate) fn new(params: Vec<(syn::Ident, syn::Type)>, body: syn::Block) -> Self {
+        let mut sm = Self {
+            vars: params
+                .into_iter()
+                .map(|(id, ty)| Var {
+                    id,
+                    ty: Some(ty),
+                    param: true,
+                })
+                .collect(),
+            blocks: Vec::new(),
+            suspensions: Vec::new(),
+            loops: Vec::new(),
+            current: 0,
+        };
+        sm.current = sm.new_block();
+        sm.lower_block(body.stmts);
+        sm.terminate(Term::Return);
+        sm
+    }
+
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(Block {
+            stmts: Vec::new(),
+            term: None,
+        });
+        self.blocks.len() - 1
+    }
+
+    /// Terminates the current block, unless it is already terminated.
+    fn terminate(&mut self, term: Term) {
+        let block = &mut self.blocks[self.current];
+        if block.term.is_none() {
+            block.term = Some(term);
+        }
+    }
+
+    fn var(&self, id: &syn::Ident) -> Option<usize> {
+        self.vars.iter().rposition(|v| &v.id == id)
+    }
+
+    fn declare(&mut self, id: syn::Ident, ty: Option<syn::Type>) -> usize {
+        if let Some(ty) = &ty {
+            if is_channel(ty) {
+                panic!("Channel `{}` must be a parameter of the task", id);
+            }
+        }
+        match self.var(&id) {
+            Some(v) if self.vars[v].param => panic!("Parameter `{}` cannot be shadowed", id),
+            Some(v) => {
+                if ty.is_some() {
+                    self.vars[v].ty = ty;
+                }
+                v
+            }
+            None => {
+                self.vars.push(Var {
+                    id,
+                    ty,
+                    param: false,
+                });
+                self.vars.len() - 1
+            }
+        }
+    }
+
+    fn channel(&self, tokens: pm2::TokenStream) -> usize {
+        let id: syn::Ident = syn::parse2(tokens).expect("Expected channel to be an identifier");
+        match self.var(&id) {
+            Some(v) if self.vars[v].param && self.vars[v].ty.as_ref().map_or(false, is_channel) => {
+                v
+            }
+            _ => panic!("Channel `{}` must be a parameter of the task", id),
+        }
+    }
+
+    fn suspend(&mut self, chan: usize, op: Op, term: fn(usize) -> Term) {
+        let next = self.new_block();
+        self.suspensions.push(Suspension { chan, op, next });
+        self.terminate(term(self.suspensions.len() - 1));
+        self.current = next;
+    }
+
+    fn lower_block(&mut self, stmts: Vec<syn::Stmt>) {
+        for stmt in stmts {
+            // Code after a `break`, `continue` or `return` is unreachable.
+            if self.blocks[self.current].term.is_some() {
+                self.current = self.new_block();
+            }
+            self.lower_stmt(stmt);
+        }
+    }
+
+    fn lower_stmt(&mut self, stmt: syn::Stmt) {
+        match stmt {
+            syn::Stmt::Local(local) => self.lower_local(local),
+            syn::Stmt::Item(syn::Item::Macro(item)) => {
+                let mac = item.mac.clone();
+                self.lower_macro(mac, syn::Stmt::Item(syn::Item::Macro(item)))
+            }
+            syn::Stmt::Expr(expr) | syn::Stmt::Semi(expr, _) => self.lower_expr(expr),
+            stmt => self.verbatim(stmt),
+        }
+    }
+
+    fn lower_local(&mut self, local: syn::Local) {
+        let (id, ty) = match &local.pat {
+            syn::Pat::Ident(p) => (Some(p.ident.clone()), None),
+            syn::Pat::Type(p) => match &*p.pat {
+                syn::Pat::Ident(i) => (Some(i.ident.clone()), Some((*p.ty).clone())),
+                _ => (None, Some((*p.ty).clone())),
+            },
+            _ => (None, None),
+        };
+        match local.init.as_ref().map(|(_, e)| &**e) {
+            Some(syn::Expr::Macro(m)) if m.mac.path.is_ident("pull") => {
+                let chan = self.channel(m.mac.tokens.clone());
+                let ty = ty.unwrap_or_else(|| item_type(self.vars[chan].ty.as_ref().unwrap()));
+                let bind = id.map(|id| self.declare(id, Some(ty)));
+                self.suspend(chan, Op::Pull { bind }, Term::Pull);
+            }
+            _ => {
+                if let Some(id) = id {
+                    self.declare(id, ty);
+                }
+                self.verbatim(syn::Stmt::Local(local));
+            }
+        }
+    }
+
+    fn lower_macro(&mut self, mac: syn::Macro, stmt: syn::Stmt) {
+        if mac.path.is_ident("pull") {
+            let chan = self.channel(mac.tokens);
+            self.suspend(chan, Op::Pull { bind: None }, Term::Pull);
+        } else if mac.path.is_ident("push") {
+            let parser = syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+            let args = syn::parse::Parser::parse2(parser, mac.tokens)
+                .expect("Expected push!(channel, data)");
+            let mut args = args.into_iter();
+            let chan = args.next().expect("Expected push!(channel, data)");
+            let chan = self.channel(quote!(#chan));
+            let data = args.next().expect("Expected push!(channel, data)");
+            self.suspend(chan, Op::Push { data }, Term::Push);
+        } else {
+            self.verbatim(stmt);
+        }
+    }
+
+    fn lower_expr(&mut self, expr: syn::Expr) {
+        if !suspends(&expr) && !escapes(&expr) {
+            return self.verbatim(syn::Stmt::Semi(expr, Default::default()));
+        }
+        match expr {
+            syn::Expr::Macro(m) => {
+                let stmt = syn::Stmt::Semi(syn::Expr::Macro(m.clone()), Default::default());
+                self.lower_macro(m.mac, stmt)
+            }
+            syn::Expr::Loop(e) if e.label.is_none() => {
+                let header = self.new_block();
+                let exit = self.new_block();
+                self.terminate(Term::Goto(header));
+                self.current = header;
+                self.loops.push((header, exit));
+                self.lower_block(e.body.stmts);
+                self.terminate(Term::Goto(header));
+                self.loops.pop();
+                self.current = exit;
+            }
+            syn::Expr::While(e) if e.label.is_none() && !matches!(*e.cond, syn::Expr::Let(_)) => {
+                let header = self.new_block();
+                let body = self.new_block();
+                let exit = self.new_block();
+                self.terminate(Term::Goto(header));
+                self.current = header;
+                self.terminate(Term::Branch(*e.cond, body, exit));
+                self.current = body;
+                self.loops.push((header, exit));
+                self.lower_block(e.body.stmts);
+                self.terminate(Term::Goto(header));
+                self.loops.pop();
+                self.current = exit;
+            }
+            syn::Expr::If(e) if !matches!(*e.cond, syn::Expr::Let(_)) => {
+                let then = self.new_block();
+                let otherwise = self.new_block();
+                let exit = self.new_block();
+                self.terminate(Term::Branch(*e.cond, then, otherwise));
+                self.current = then;
+                self.lower_block(e.then_branch.stmts);
+                self.terminate(Term::Goto(exit));
+                self.current = otherwise;
+                if let Some((_, e)) = e.else_branch {
+                    self.lower_expr(*e);
+                }
+                self.terminate(Term::Goto(exit));
+                self.current = exit;
+            }
+            syn::Expr::Block(e) if e.label.is_none() => self.lower_block(e.block.stmts),
+            syn::Expr::Break(e) if e.label.is_none() && e.expr.is_none() => {
+                let (_, exit) = *self.loops.last().expect("`break` outside of loop");
+                self.terminate(Term::Goto(exit));
+            }
+            syn::Expr::Continue(e) if e.label.is_none() => {
+                let (header, _) = *self.loops.last().expect("`continue` outside of loop");
+                self.terminate(Term::Goto(header));
+            }
+            syn::Expr::Return(e) if e.expr.is_none() => self.terminate(Term::Return),
+            _ => panic!("pull!, push!, break, continue and return must appear as statements"),
+        }
+    }
+
+    fn verbatim(&mut self, stmt: syn::Stmt) {
+        self.blocks[self.current].stmts.push(stmt);
+    }
+}
+
+/// Returns true if the expression contains a `pull!` or `push!`.
+fn suspends(expr: &syn::Expr) -> bool {
+    #[derive(Default)]
+    struct Visitor(bool);
+    impl<'a> Visit<'a> for Visitor {
+        fn visit_macro(&mut self, m: &'a syn::Macro) {
+            self.0 |= m.path.is_ident("pull")
+                || m.path.is_ident("push")
+                || suspends_tokens(m.tokens.clone());
+        }
+    }
+    let mut v = Visitor::default();
+    v.visit_expr(expr);
+    v.0
+}
+
+/// Returns true if the tokens of a macro contain a `pull!` or `push!`.
+fn suspends_tokens(tokens: pm2::TokenStream) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            pm2::TokenTree::Group(g) if suspends_tokens(g.stream()) => return true,
+            pm2::TokenTree::Ident(i) if i == "pull" || i == "push" => {
+                if matches!(tokens.peek(), Some(pm2::TokenTree::Punct(p)) if p.as_char() == '!') {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Returns true if the expression contains a `return`, or a `break` or `continue` which leaves
+/// the expression.
+fn escapes(expr: &syn::Expr) -> bool {
+    #[derive(Default)]
+    struct Visitor {
+        depth: usize,
+        escapes: bool,
+    }
+    impl<'a> Visit<'a> for Visitor {
+        fn visit_expr_loop(&mut self, e: &'a syn::ExprLoop) {
+            self.depth += 1;
+            syn::visit::visit_expr_loop(self, e);
+            self.depth -= 1;
+        }
+        fn visit_expr_while(&mut self, e: &'a syn::ExprWhile) {
+            self.depth += 1;
+            syn::visit::visit_expr_while(self, e);
+            self.depth -= 1;
+        }
+        fn visit_expr_for_loop(&mut self, e: &'a syn::ExprForLoop) {
+            self.depth += 1;
+            syn::visit::visit_expr_for_loop(self, e);
+            self.depth -= 1;
+        }
+        fn visit_expr_break(&mut self, e: &'a syn::ExprBreak) {
+            self.escapes |= self.depth == 0 || e.label.is_some();
+        }
+        fn visit_expr_continue(&mut self, e: &'a syn::ExprContinue) {
+            self.escapes |= self.depth == 0 || e.label.is_some();
+        }
+        fn visit_expr_return(&mut self, _: &'a syn::ExprReturn) {
+            self.escapes = true;
+        }
+        fn visit_expr_closure(&mut self, _: &'a syn::ExprClosure) {}
+        fn visit_expr_async(&mut self, _: &'a syn::ExprAsync) {}
+    }
+    let mut v = Visitor::default();
+    v.visit_expr(expr);
+    v.escapes
+}
+
+fn is_channel(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(p) => {
+            let id = &p.path.segments.last().unwrap().ident;
+            id == "Pullable" || id == "Pushable"
+        }
+        _ => false,
+    }
+}
+
+/// Returns `T` given `Pullable<T>` or `Pushable<T>`.
+fn item_type(ty: &syn::Type) -> syn::Type {
+    if let syn::Type::Path(p) = ty {
+        if let syn::PathArguments::AngleBracketed(args) = &p.path.segments.last().unwrap().arguments
+        {
+            if let Some(syn::GenericArgument::Type(ty)) = args.args.first() {
+                return ty.clone();
+            }
+        }
+    }
+    panic!("Expected channel type to be of the form `Pullable<T>` or `Pushable<T>`")
+}
+
+/// Collects the variables which are used by a piece of code, including inside macros.
+struct Uses<'a> {
+    sm: &'a StateMachine,
+    uses: Vars,
+}
+
+impl<'a> Uses<'a> {
+    fn tokens(&mut self, tokens: pm2::TokenStream) {
+        for token in tokens {
+            match token {
+                pm2::TokenTree::Group(g) => self.tokens(g.stream()),
+                pm2::TokenTree::Ident(i) => self.uses.extend(self.sm.var(&i)),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for Uses<'a> {
+    fn visit_expr_path(&mut self, e: &'ast syn::ExprPath) {
+        if let Some(id) = e.path.get_ident() {
+            self.uses.extend(self.sm.var(id));
+        }
+    }
+    fn visit_macro(&mut self, m: &'ast syn::Macro) {
+        self.tokens(m.tokens.clone());
+    }
+}
+
+impl StateMachine {
+    fn uses_stmt(&self, stmt: &syn::Stmt) -> Vars {
+        let mut v = Uses {
+            sm: self,
+            uses: Vars::new(),
+        };
+        v.visit_stmt(stmt);
+        v.uses
+    }
+
+    fn uses_expr(&self, expr: &syn::Expr) -> Vars {
+        let mut v = Uses {
+            sm: self,
+            uses: Vars::new(),
+        };
+        v.visit_expr(expr);
+        v.uses
+    }
+
+    fn def_stmt(&self, stmt: &syn::Stmt) -> Option<usize> {
+        match stmt {
+            syn::Stmt::Local(l) => match &l.pat {
+                syn::Pat::Ident(p) => self.var(&p.ident),
+                syn::Pat::Type(p) => match &*p.pat {
+                    syn::Pat::Ident(p) => self.var(&p.ident),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Variables which are live when a suspension is about to start.
+    fn live_suspension(&self, s: usize, live: &[Vars]) -> Vars {
+        let s = &self.suspensions[s];
+        let mut vars = live[s.next].clone();
+        if let Op::Pull { bind: Some(bind) } = s.op {
+            vars.remove(&bind);
+        }
+        vars.insert(s.chan);
+        vars
+    }
+
+    /// Computes the variables which are live when entering each block.
+    fn liveness(&self) -> Vec<Vars> {
+        let mut live = vec![Vars::new(); self.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (b, block) in self.blocks.iter().enumerate().rev() {
+                let mut vars = match block.term.as_ref().unwrap() {
+                    Term::Goto(next) => live[*next].clone(),
+                    Term::Branch(cond, then, otherwise) => {
+                        let mut vars = self.uses_expr(cond);
+                        vars.extend(&live[*then]);
+                        vars.extend(&live[*otherwise]);
+                        vars
+                    }
+                    Term::Pull(s) => self.live_suspension(*s, &live),
+                    Term::Push(s) => {
+                        let mut vars = self.live_suspension(*s, &live);
+                        if let Op::Push { data } = &self.suspensions[*s].op {
+                            vars.extend(self.uses_expr(data));
+                        }
+                        vars
+                    }
+                    Term::Return => Vars::new(),
+                };
+                for stmt in block.stmts.iter().rev() {
+                    if let Some(def) = self.def_stmt(stmt) {
+                        vars.remove(&def);
+                    }
+                    vars.extend(self.uses_stmt(stmt));
+                }
+                if vars != live[b] {
+                    live[b] = vars;
+                    changed = true;
+                }
+            }
+        }
+        live
+    }
+}
+
+/// The code generated for a state machine.
+pub(crate) struct Generated {
+    /// The `State` and `Checkpoint` enums, and the `transition` function.
+    pub(crate) items: pm2::TokenStream,
+    /// An expression for the initial state, in which the parameters and `ctx` are in scope.
+    pub(crate) initial: pm2::TokenStream,
+}
+
+impl StateMachine {
+    fn id(&self, v: usize) -> &syn::Ident {
+        &self.vars[v].id
+    }
+
+    fn ty(&self, v: usize) -> &syn::Type {
+        let var = &self.vars[v];
+        var.ty.as_ref().unwrap_or_else(|| {
+            panic!(
+                "Variable `{}` is live across a pull! or push! and needs a type annotation",
+                var.id
+            )
+        })
+    }
+
+    fn chan_ty(&self, s: usize) -> &syn::Type {
+        self.ty(self.suspensions[s].chan)
+    }
+
+    fn data(&self, vars: &Vars) -> Vars {
+        vars.iter()
+            .copied()
+            .filter(|v| !is_channel(self.ty(*v)))
+            .collect()
+    }
+
+    /// Returns the type of a variable when it is stored in a state.
+    fn state_ty(&self, v: usize) -> pm2::TokenStream {
+        let ty = self.ty(v);
+        if is_channel(ty) {
+            quote!(HandBack<#ty>)
+        } else {
+            quote!(#ty)
+        }
+    }
+
+    fn fields(&self, vars: &Vars) -> pm2::TokenStream {
+        let id = vars.iter().map(|v| self.id(*v));
+        let ty = vars.iter().map(|v| self.state_ty(*v));
+        quote!(#(#id: #ty,)*)
+    }
+
+    fn sendable_fields(&self, vars: &Vars) -> pm2::TokenStream {
+        let vars = self.data(vars);
+        let id = vars.iter().map(|v| self.id(*v));
+        let ty = vars.iter().map(|v| self.ty(*v));
+        quote!(#(#id: <#ty as DynSharable>::T,)*)
+    }
+
+    fn pattern(&self, vars: &Vars) -> pm2::TokenStream {
+        let id = vars.iter().map(|v| self.id(*v));
+        quote!(#(mut #id,)*)
+    }
+
+    fn names(&self, vars: &Vars) -> pm2::TokenStream {
+        let id = vars.iter().map(|v| self.id(*v));
+        quote!(#(#id,)*)
+    }
+
+    pub(crate) fn generate(&self) -> Generated {
+        let live = self.liveness();
+        if let Some(v) = live[0].iter().find(|v| !self.vars[**v].param) {
+            panic!(
+                "Variable `{}` may be used before it is defined",
+                self.id(*v)
+            );
+        }
+
+        let block_state = |b: usize| new_id(format!("B{}", b));
+        let start_state = |s: usize| new_id(format!("P{}", s));
+        let wait_state = |s: usize| new_id(format!("W{}", s));
+
+        // Variables which are kept while waiting on a suspension.
+        let waiting = |s: usize| {
+            let mut vars = self.live_suspension(s, &live);
+            vars.remove(&self.suspensions[s].chan);
+            vars
+        };
+
+        let mut variants = Vec::new();
+        let mut arms = Vec::new();
+        let mut checkpoint_variants = Vec::new();
+        let mut checkpoint_arms = Vec::new();
+        let mut restore_arms = Vec::new();
+
+        let goto = |b: usize| {
+            let state = block_state(b);
+            let names = self.names(&live[b]);
+            quote!(State::#state { #names })
+        };
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            let state = block_state(b);
+            let fields = self.fields(&live[b]);
+            let pattern = self.pattern(&live[b]);
+            let stmts = &block.stmts;
+            let term = match block.term.as_ref().unwrap() {
+                Term::Goto(next) => goto(*next),
+                Term::Branch(cond, then, otherwise) => {
+                    let then = goto(*then);
+                    let otherwise = goto(*otherwise);
+                    quote!(if #cond { #then } else { #otherwise })
+                }
+                Term::Pull(s) => {
+                    let start = start_state(*s);
+                    let names = self.names(&self.live_suspension(*s, &live));
+                    quote!(State::#start { #names })
+                }
+                Term::Push(s) => {
+                    let start = start_state(*s);
+                    let names = self.names(&self.live_suspension(*s, &live));
+                    let data = match &self.suspensions[*s].op {
+                        Op::Push { data } => data,
+                        Op::Pull { .. } => unreachable!(),
+                    };
+                    quote!({
+                        let __data = #data;
+                        State::#start { #names __data }
+                    })
+                }
+                Term::Return => quote!(return (Ready(()), State::Done)),
+            };
+            variants.push(quote!(#state { #fields }));
+            arms.push(quote!(
+                State::#state { #pattern } => {
+                    #(#stmts)*
+                    #term
+                }
+            ));
+            checkpoint_variants.push(self.checkpoint_variant(&state, &live[b]));
+            checkpoint_arms.push(self.checkpoint_arm(&state, &live[b], &state, &live[b]));
+            restore_arms.push(self.restore_arm(&state, &live[b], quote!()));
+        }
+
+        for (s, suspension) in self.suspensions.iter().enumerate() {
+            let start = start_state(s);
+            let wait = wait_state(s);
+            let chan = self.id(suspension.chan);
+            let item_ty = item_type(self.chan_ty(s));
+            let chan_ty = self.state_ty(suspension.chan);
+            let start_vars = self.live_suspension(s, &live);
+            let wait_vars = waiting(s);
+            let start_pattern = self.pattern(&start_vars);
+            let wait_fields = self.fields(&wait_vars);
+            let wait_pattern = self.pattern(&wait_vars);
+            let wait_names = self.names(&wait_vars);
+            let next = goto(suspension.next);
+            match &suspension.op {
+                Op::Pull { bind } => {
+                    let bind = bind.map(|v| {
+                        let id = self.id(v);
+                        let ty = self.ty(v);
+                        quote!(let #id: #ty = __data;)
+                    });
+                    variants.push(quote!(#start { #wait_fields #chan: #chan_ty }));
+                    variants.push(quote!(
+                        #wait {
+                            #wait_fields
+                            __future: BoxFuture<'static, (#chan_ty, Control<#item_ty>)>
+                        }
+                    ));
+                    arms.push(quote!(
+                        State::#start { #start_pattern } => {
+                            let __future = async move {
+                                let __data = #chan.pull(ctx).await;
+                                (#chan, __data)
+                            }.boxed();
+                            State::#wait { #wait_names __future }
+                        }
+                    ));
+                    arms.push(quote!(
+                        State::#wait { #wait_pattern mut __future } => match __future.as_mut().poll(cx) {
+                            Pending => return (Pending, State::#wait { #wait_names __future }),
+                            Ready((mut #chan, Control::Continue(__data))) => {
+                                #bind
+                                let __next = #next;
+                                // The record has been taken out of the receiver, which is handed
+                                // back as is, so the checkpoint must already reflect it.
+                                if ctx.state().is_checkpointing() {
+                                    ctx.state().save_checkpoint(__next.checkpoint(ctx));
+                                }
+                                __next
+                            }
+                            Ready((_, Control::Failed(error))) => {
+                                ctx.state().fail(error);
+                                return (Ready(()), State::Done);
+                            }
+                            Ready((_, Control::Finished)) => return (Ready(()), State::Done),
+                        }
+                    ));
+                    checkpoint_variants.push(self.checkpoint_variant(&start, &start_vars));
+                    checkpoint_arms.push(self.checkpoint_arm(
+                        &start,
+                        &start_vars,
+                        &start,
+                        &start_vars,
+                    ));
+                    checkpoint_arms.push(self.checkpoint_arm(
+                        &wait,
+                        &wait_vars,
+                        &start,
+                        &start_vars,
+                    ));
+                    restore_arms.push(self.restore_arm(&start, &start_vars, quote!()));
+                }
+                Op::Push { .. } => {
+                    variants
+                        .push(quote!(#start { #wait_fields #chan: #chan_ty, __data: #item_ty }));
+                    variants.push(quote!(
+                        #wait {
+                            #wait_fields
+                            __future: BoxFuture<'static, (#chan_ty, Control<()>)>
+                        }
+                    ));
+                    arms.push(quote!(
+                        State::#start { #start_pattern __data } => {
+                            let __future = async move {
+                                let __data = #chan.push(__data, ctx).await;
+                                (#chan, __data)
+                            }.boxed();
+                            State::#wait { #wait_names __future }
+                        }
+                    ));
+                    arms.push(quote!(
+                        State::#wait { #wait_pattern mut __future } => match __future.as_mut().poll(cx) {
+                            Pending => return (Pending, State::#wait { #wait_names __future }),
+                            Ready((mut #chan, Control::Continue(()))) => #next,
+                            Ready((_, Control::Failed(error))) => {
+                                ctx.state().fail(error);
+                                return (Ready(()), State::Done);
+                            }
+                            Ready((_, Control::Finished)) => return (Ready(()), State::Done),
+                        }
+                    ));
+                    let sendable_fields = self.sendable_fields(&start_vars);
+                    checkpoint_variants.push(quote!(
+                        #start { #sendable_fields __data: <#item_ty as DynSharable>::T }
+                    ));
+                    let data = self.data(&start_vars);
+                    let names = self.names(&data);
+                    let convert = data.iter().map(|v| self.id(*v));
+                    checkpoint_arms.push(quote!(
+                        State::#start { #names __data, .. } => Checkpoint::#start {
+                            #(#convert: #convert.into_sendable(ctx),)*
+                            __data: __data.into_sendable(ctx)
+                        }
+                    ));
+                    // The push has completed, since pushing into a local channel never waits.
+                    let next_state = block_state(suspension.next);
+                    checkpoint_arms.push(self.checkpoint_arm(
+                        &wait,
+                        &wait_vars,
+                        &next_state,
+                        &live[suspension.next],
+                    ));
+                    restore_arms.push(self.restore_arm(
+                        &start,
+                        &start_vars,
+                        quote!(__data: __data.into_sharable(ctx)),
+                    ));
+                }
+            }
+        }
+
+        let params = self
+            .vars
+            .iter()
+            .filter(|v| v.param)
+            .map(|v| &v.id)
+            .collect::<Vec<_>>();
+        let param_ty = (0..self.vars.len())
+            .filter(|v| self.vars[*v].param)
+            .map(|v| self.state_ty(v))
+            .collect::<Vec<_>>();
+        let chans = self
+            .vars
+            .iter()
+            .filter(|v| v.param && is_channel(v.ty.as_ref().unwrap()))
+            .map(|v| &v.id)
+            .collect::<Vec<_>>();
+        let start = goto(0);
+        // A restarted task continues from its latest checkpoint, with the channels which the
+        // failed task handed back.
+        let initial = quote!({
+            #(let #chans = HandBack::new(stringify!(#chans), #chans, ctx.state());)*
+            match ctx.state().take_checkpoint::<Checkpoint>() {
+                Some(checkpoint) => State::restore(checkpoint, #(#params,)* ctx),
+                None => #start,
+            }
+        });
+
+        let items = quote!(
+            enum State {
+                #(#variants,)*
+                Done,
+            }
+
+            /// A snapshot of the state of the task, without its channels.
+            #[derive(Clone, Serialize, Deserialize)]
+            pub enum Checkpoint {
+                #(#checkpoint_variants,)*
+                Done,
+            }
+
+            /// Runs the task until it has to wait, and saves a checkpoint if it is supervised by a
+            /// supervisor which can restart it, and its latest checkpoint is old enough. Waiting
+            /// states are checkpointed before their operation, so no record is lost.
+            fn transition(state: State, cx: &mut PollContext, ctx: Context) -> (Poll<()>, State) {
+                let (poll, state) = step(state, cx, ctx);
+                if poll.is_pending() && ctx.state().is_checkpoint_due() {
+                    ctx.state().save_checkpoint(state.checkpoint(ctx));
+                }
+                (poll, state)
+            }
+
+            /// Runs the task until it has to wait, or until it has used up its step budget, in
+            /// which case it yields and is polled again.
+            fn step(mut state: State, cx: &mut PollContext, ctx: Context) -> (Poll<()>, State) {
+                for _ in 0..arc_runtime::task::state::STEP_BUDGET {
+                    state = match state {
+                        #(#arms)*
+                        State::Done => return (Ready(()), State::Done),
+                    };
+                }
+                cx.waker().wake_by_ref();
+                (Pending, state)
+            }
+
+            impl State {
+                fn checkpoint(&self, ctx: Context) -> Checkpoint {
+                    match self {
+                        #(#checkpoint_arms,)*
+                        State::Done => Checkpoint::Done,
+                    }
+                }
+
+                fn restore(checkpoint: Checkpoint, #(#params: #param_ty,)* ctx: Context) -> State {
+                    match checkpoint {
+                        #(#restore_arms,)*
+                        Checkpoint::Done => State::Done,
+                    }
+                }
+            }
+        );
+        Generated { items, initial }
+    }
+
+    fn checkpoint_variant(&self, state: &syn::Ident, vars: &Vars) -> pm2::TokenStream {
+        let fields = self.sendable_fields(vars);
+        quote!(#state { #fields })
+    }
+
+    /// Checkpoints the state `from`, whose variables are `from_vars`, as the state `to`.
+    fn checkpoint_arm(
+        &self,
+        from: &syn::Ident,
+        from_vars: &Vars,
+        to: &syn::Ident,
+        to_vars: &Vars,
+    ) -> pm2::TokenStream {
+        let data = self.data(to_vars);
+        debug_assert!(data.is_subset(from_vars));
+        let names = self.names(&data);
+        let convert = data.iter().map(|v| self.id(*v));
+        quote!(
+            State::#from { #names .. } => Checkpoint::#to {
+                #(#convert: #convert.into_sendable(ctx)),*
+            }
+        )
+    }
+
+    /// Restores a state from its checkpoint. Channels are taken from the parameters.
+    fn restore_arm(
+        &self,
+        state: &syn::Ident,
+        vars: &Vars,
+        extra: pm2::TokenStream,
+    ) -> pm2::TokenStream {
+        let data = self.data(vars);
+        let names = self.names(&data);
+        let fields = vars.iter().map(|v| {
+            let id = self.id(*v);
+            if data.contains(v) {
+                quote!(#id: #id.into_sharable(ctx))
+            } else {
+                quote!(#id)
+            }
+        });
+        let extra_pattern = if extra.is_empty() {
+            quote!()
+        } else {
+            quote!(__data)
+        };
+        quote!(
+            Checkpoint::#state { #names #extra_pattern } => State::#state { #(#fields,)* #extra }
+        )
+    }
+}
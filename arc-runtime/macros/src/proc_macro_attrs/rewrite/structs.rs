@@ -1,38 +1,55 @@
 use proc_macro as pm;
 use quote::quote;
 
+use crate::comparison_derives;
+use crate::describe_fields;
+use crate::format_generics;
 use crate::new_id;
+use crate::qualify_field_type;
+use crate::schema_generics;
+use crate::sendable_generics;
+use crate::sharable_generics;
 
 #[cfg(not(feature = "legacy"))]
 #[allow(unused)]
 pub(crate) fn rewrite(args: syn::AttributeArgs, struct_item: syn::ItemStruct) -> pm::TokenStream {
+    let derives = comparison_derives(&args);
     let abstract_id = struct_item.ident.clone();
     let concrete_id = new_id(format!("Concrete{}", struct_item.ident));
     let sharable_mod_id = new_id(format!("sharable_struct_{}", struct_item.ident));
     let sendable_mod_id = new_id(format!("sendable_struct_{}", struct_item.ident));
 
+    let sharable_generics = sharable_generics(&struct_item.generics);
+    let sendable_generics = sendable_generics(&struct_item.generics, &derives);
+    let (_, type_generics, sharable_where_clause) = sharable_generics.split_for_impl();
+    let (impl_generics, _, sendable_where_clause) = sendable_generics.split_for_impl();
+    let format_generics = format_generics(&struct_item.generics);
+    let (format_impl_generics, _, format_where_clause) = format_generics.split_for_impl();
+    let schema_generics = schema_generics(&struct_item.generics);
+    let (schema_impl_generics, _, schema_where_clause) = schema_generics.split_for_impl();
+    let describe_fields = describe_fields(&struct_item.fields);
+
     let mut concrete_sharable_struct_item = struct_item.clone();
     let mut concrete_sendable_struct_item = struct_item;
 
     concrete_sharable_struct_item.ident = concrete_id.clone();
     concrete_sendable_struct_item.ident = concrete_id.clone();
+    concrete_sharable_struct_item.generics = sharable_generics.clone();
+    concrete_sendable_struct_item.generics = sendable_generics.clone();
 
-    // Generate the sendable struct
+    // Generate the sharable struct
     concrete_sharable_struct_item
         .fields
         .iter_mut()
-        .for_each(|f| {
-            let ty = f.ty.clone();
-            f.ty = syn::parse_quote!(super::#ty);
-        });
+        .for_each(|f| f.ty = qualify_field_type(&f.ty, &sharable_generics));
 
     // Generate the sendable struct
     concrete_sendable_struct_item
         .fields
         .iter_mut()
         .for_each(|f| {
-            let ty = f.ty.clone();
-            f.ty = syn::parse_quote!(<super::#ty as DynSharable>::T);
+            let ty = qualify_field_type(&f.ty, &sendable_generics);
+            f.ty = syn::parse_quote!(<#ty as DynSharable>::T);
         });
 
     let field_id = concrete_sendable_struct_item
@@ -47,29 +64,31 @@ pub(crate) fn rewrite(args: syn::AttributeArgs, struct_item: syn::ItemStruct) ->
         pub mod #sharable_mod_id {
             use arc_runtime::prelude::*;
 
-            #[derive(Clone, Debug, Send, Sync, Alloc, Unpin, From, Deref, Abstract, Collectable, Finalize, Trace)]
-            pub struct #abstract_id(pub Gc<#concrete_id>);
+            #[derive(Clone, Debug, Send, Sync, Alloc, Unpin, From, Deref, Abstract, Collectable, Finalize, Trace #(, #derives)*)]
+            pub struct #abstract_id #sharable_generics (pub Gc<#concrete_id #type_generics>) #sharable_where_clause;
 
-            #[derive(Clone, Debug, Collectable, Finalize, Trace)]
+            #[derive(Clone, Debug, Collectable, Finalize, Trace #(, #derives)*)]
             #concrete_sharable_struct_item
         }
 
         mod #sendable_mod_id {
             use arc_runtime::prelude::*;
 
-            #[derive(Clone, Debug, Deref, From, Abstract, Deserialize, Serialize)]
+            #[derive(Clone, Debug, Deref, From, Abstract, Deserialize, Serialize #(, #derives)*)]
+            #[serde(bound = "")]
             #[from(forward)]
-            pub struct #abstract_id(pub Box<#concrete_id>);
+            pub struct #abstract_id #sendable_generics (pub Box<#concrete_id #type_generics>) #sendable_where_clause;
 
-            #[derive(Clone, Debug, Deserialize, Serialize)]
+            #[derive(Clone, Debug, Deserialize, Serialize #(, #derives)*)]
+            #[serde(bound = "")]
             #concrete_sendable_struct_item
         }
 
         use #sharable_mod_id::#abstract_id;
         use #sharable_mod_id::#concrete_id;
 
-        impl DynSharable for #sharable_mod_id::#abstract_id {
-            type T = #sendable_mod_id::#abstract_id;
+        impl #impl_generics DynSharable for #sharable_mod_id::#abstract_id #type_generics #sendable_where_clause {
+            type T = #sendable_mod_id::#abstract_id #type_generics;
             fn into_sendable(&self, ctx: Context) -> Self::T {
                 #sendable_mod_id::#concrete_id {
                     #(#field_id: self.0.#field_id.clone().into_sendable(ctx)),*
@@ -77,8 +96,25 @@ pub(crate) fn rewrite(args: syn::AttributeArgs, struct_item: syn::ItemStruct) ->
             }
         }
 
-        impl DynSendable for #sendable_mod_id::#abstract_id {
-            type T = #sharable_mod_id::#abstract_id;
+        impl #format_impl_generics Format for #sharable_mod_id::#abstract_id #type_generics #format_where_clause {
+            fn format(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                use arc_runtime::data::format::field::*;
+                f.debug_struct(stringify!(#abstract_id))
+                    #(.field(stringify!(#field_id), (&Field::new(&self.0.#field_id)).as_debug()))*
+                    .finish()
+            }
+        }
+
+        impl #schema_impl_generics Schema for #sharable_mod_id::#abstract_id #type_generics #schema_where_clause {
+            fn describe(schema: &mut SchemaWriter) {
+                schema.compound::<Self>(stringify!(#abstract_id), |schema| {
+                    #describe_fields
+                });
+            }
+        }
+
+        impl #impl_generics DynSendable for #sendable_mod_id::#abstract_id #type_generics #sendable_where_clause {
+            type T = #sharable_mod_id::#abstract_id #type_generics;
             fn into_sharable(&self, ctx: Context) -> Self::T {
                 #sharable_mod_id::#concrete_id {
                     #(#field_id: self.0.#field_id.into_sharable(ctx)),*
@@ -92,21 +128,27 @@ pub(crate) fn rewrite(args: syn::AttributeArgs, struct_item: syn::ItemStruct) ->
 
 #[cfg(feature = "legacy")]
 #[allow(unused)]
-pub(crate) fn rewrite(_: syn::AttributeArgs, mut struct_item: syn::ItemStruct) -> pm::TokenStream {
+pub(crate) fn rewrite(
+    args: syn::AttributeArgs,
+    mut struct_item: syn::ItemStruct,
+) -> pm::TokenStream {
+    let derives = comparison_derives(&args);
     let abstract_id = struct_item.ident.clone();
     let concrete_id = new_id(format!("Concrete{}", struct_item.ident));
     let mod_id = new_id(format!("send_{}", struct_item.ident));
 
     struct_item.ident = concrete_id.clone();
+    let generics = &struct_item.generics;
+    let (_, type_generics, where_clause) = generics.split_for_impl();
 
     quote!(
 
         use arc_runtime::prelude::*;
-        #[derive(Clone, Debug, From, Deref)]
+        #[derive(Clone, Debug, From, Deref #(, #derives)*)]
         #[from(forward)]
-        pub struct #abstract_id(pub std::rc::Rc<#concrete_id>);
+        pub struct #abstract_id #generics (pub std::rc::Rc<#concrete_id #type_generics>) #where_clause;
 
-        #[derive(Clone, Debug)]
+        #[derive(Clone, Debug #(, #derives)*)]
         #struct_item
     )
     .into()
@@ -1,4 +1,5 @@
 use crate::new_id;
+use crate::variant_fields;
 use proc_macro::TokenStream;
 
 pub fn derive_abstract(input: syn::DeriveInput) -> TokenStream {
@@ -66,11 +67,16 @@ pub fn derive_trace(input: syn::DeriveInput) -> TokenStream {
         }
         syn::Data::Enum(data) => {
             let variant = data.variants.iter().map(|v| &v.ident);
+            let (member, binding): (Vec<_>, Vec<_>) = data
+                .variants
+                .iter()
+                .map(|v| variant_fields(&v.fields))
+                .unzip();
             quote::quote!(
                 unsafe impl #impl_generics Trace for #name #type_generics #where_clause {
                     fn trace(&mut self, vis: &mut dyn Visitor) {
                         match self {
-                            #(Self::#variant(data) => data.trace(vis),)*
+                            #(Self::#variant { #(#member: #binding),* } => { #(#binding.trace(vis);)* })*
                         }
                     }
                 }
@@ -108,9 +114,9 @@ pub fn derive_alloc(input: syn::DeriveInput) -> TokenStream {
     let abstract_id = &input.ident;
     let concrete_id = new_id(format!("Concrete{}", abstract_id));
     quote::quote!(
-        impl #impl_generics Alloc<#abstract_id> for #concrete_id #type_generics #where_clause {
+        impl #impl_generics Alloc<#abstract_id #type_generics> for #concrete_id #type_generics #where_clause {
             fn alloc(self, ctx: Context) -> #abstract_id #type_generics {
-                #abstract_id(ctx.mutator().allocate(self, AllocationSpace::New).into())
+                #abstract_id(ctx.allocate(self).into())
             }
         }
     )
@@ -37,9 +37,30 @@ pub fn call_indirect(input: syn::Expr) -> TokenStream {
             let func = e.func;
             let args = e.args;
             if args.len() == 1 && !args.trailing_punct() {
-                quote::quote!((#func.ptr)((#args,), ctx)).into()
+                quote::quote!(#func.call((#args,), ctx)).into()
             } else {
-                quote::quote!((#func.ptr)((#args), ctx)).into()
+                quote::quote!(#func.call((#args), ctx)).into()
+            }
+        }
+        _ => panic!("Expected function call expression"),
+    }
+}
+
+#[cfg(feature = "legacy")]
+pub fn call_protected(input: syn::Expr) -> TokenStream {
+    quote::quote!(Some(#input)).into()
+}
+
+#[cfg(not(feature = "legacy"))]
+pub fn call_protected(input: syn::Expr) -> TokenStream {
+    match input {
+        syn::Expr::Call(e) => {
+            let func = e.func;
+            let args = e.args;
+            if args.len() == 1 && !args.trailing_punct() {
+                quote::quote!(protect(#func.ptr, (#args,), ctx)).into()
+            } else {
+                quote::quote!(protect(#func.ptr, (#args), ctx)).into()
             }
         }
         _ => panic!("Expected function call expression"),
@@ -49,19 +70,36 @@ pub fn call_indirect(input: syn::Expr) -> TokenStream {
 #[cfg(feature = "legacy")]
 pub fn enwrap(input: TokenStream) -> TokenStream {
     let mut iter = input.into_iter();
-    let mut path: syn::Path = parse(&mut iter);
-    concrete_enum_path(&mut path);
-    let data: syn::Expr = parse(&mut iter);
-    quote::quote!(#path(#data).into()).into()
+    let variant = enwrap_variant(&mut iter);
+    quote::quote!(#variant.into()).into()
 }
 
 #[cfg(not(feature = "legacy"))]
 pub fn enwrap(input: TokenStream) -> TokenStream {
     let mut iter = input.into_iter();
-    let mut path: syn::Path = parse(&mut iter);
-    concrete_enum_path(&mut path);
-    let data: syn::Expr = parse(&mut iter);
-    quote::quote!(#path(#data).alloc(ctx)).into()
+    let variant = enwrap_variant(&mut iter);
+    quote::quote!(#variant.alloc(ctx)).into()
+}
+
+/// Constructs a variant from either `Variant { field: expr, .. }`, or `Variant` followed by the
+/// expressions of its fields.
+fn enwrap_variant(iter: &mut impl Iterator<Item = pm::TokenTree>) -> pm2::TokenStream {
+    match parse(iter) {
+        syn::Expr::Struct(mut data) => {
+            concrete_enum_path(&mut data.path);
+            quote::quote!(#data)
+        }
+        syn::Expr::Path(mut path) => {
+            concrete_enum_path(&mut path.path);
+            let data: Vec<syn::Expr> = parse_all(iter);
+            if data.is_empty() {
+                quote::quote!(#path)
+            } else {
+                quote::quote!(#path(#(#data),*))
+            }
+        }
+        _ => panic!("Expected an enum variant"),
+    }
 }
 
 pub fn is(input: TokenStream) -> TokenStream {
@@ -69,15 +107,202 @@ pub fn is(input: TokenStream) -> TokenStream {
     let mut path: syn::Path = parse(&mut iter);
     concrete_enum_path(&mut path);
     let data: syn::Expr = parse(&mut iter);
-    quote::quote!(if let #path(_) = #data.0.as_ref() { true } else { false }).into()
+    quote::quote!(if let #path { .. } = #data.0.as_ref() { true } else { false }).into()
 }
 
+/// Unwraps the field of a single-field variant `Variant`, or the fields which are bound by a
+/// pattern `Variant(_, ..)` or `Variant { field, .. }` as a tuple.
 pub fn unwrap(input: TokenStream) -> TokenStream {
     let mut iter = input.into_iter();
-    let mut path: syn::Path = parse(&mut iter);
-    concrete_enum_path(&mut path);
+    let (pat, binding) = match parse(&mut iter) {
+        syn::Pat::TupleStruct(mut pat) => {
+            concrete_enum_path(&mut pat.path);
+            let binding = (0..pat.pat.elems.len())
+                .map(|i| new_id(format!("x{}", i)))
+                .collect::<Vec<_>>();
+            let path = &pat.path;
+            (quote::quote!(#path(#(#binding),*)), binding)
+        }
+        syn::Pat::Struct(mut pat) => {
+            concrete_enum_path(&mut pat.path);
+            let binding = pat
+                .fields
+                .iter()
+                .map(|f| match &f.member {
+                    syn::Member::Named(ident) => ident.clone(),
+                    syn::Member::Unnamed(index) => new_id(format!("x{}", index.index)),
+                })
+                .collect::<Vec<_>>();
+            let member = pat.fields.iter().map(|f| &f.member);
+            let path = &pat.path;
+            (quote::quote!(#path { #(#member: #binding,)* .. }), binding)
+        }
+        syn::Pat::Ident(pat) => {
+            let mut path = syn::Path::from(pat.ident);
+            concrete_enum_path(&mut path);
+            (quote::quote!(#path(x0)), vec![new_id("x0")])
+        }
+        syn::Pat::Path(mut pat) => {
+            concrete_enum_path(&mut pat.path);
+            let path = &pat.path;
+            (quote::quote!(#path(x0)), vec![new_id("x0")])
+        }
+        _ => panic!("Expected an enum variant"),
+    };
+    let expr: syn::Expr = parse(&mut iter);
+    quote::quote!(
+        if let #pat = &*#expr.0 { (#(#binding.clone()),*) } else { unreachable!() }
+    )
+    .into()
+}
+
+/// Matches a rewritten enum against arms whose patterns are variants of the enum. The value is
+/// borrowed through its `Gc` once, so bindings are references to its fields.
+///
+/// Variant patterns may be nested inside the fields of other variants. A nested pattern is
+/// lowered into a guard which tests it, and a match in the body of the arm which binds it, so
+/// arms with nested patterns do not count towards exhaustiveness.
+pub fn match_enum(input: TokenStream) -> TokenStream {
+    let mut iter = input.into_iter();
     let expr: syn::Expr = parse(&mut iter);
-    quote::quote!(if let #path(v) = &*#expr.0 { v.clone() } else { unreachable!() }).into()
+    let arms: pm2::TokenStream = iter.collect::<pm::TokenStream>().into();
+    let data: syn::ExprMatch = syn::parse2(quote::quote!(match x #arms)).unwrap();
+    let mut count = 0;
+    let arms = data.arms.into_iter().map(|arm| {
+        let Lowered { pat, nested } = lower_variant(arm.pat, &mut count);
+        let guard = arm.guard.map(|(_, guard)| *guard);
+        let body = &arm.body;
+        if nested.is_empty() {
+            match guard {
+                Some(guard) => quote::quote!(#pat if #guard => #body),
+                None => quote::quote!(#pat => #body),
+            }
+        } else {
+            let check = check_nested(&nested, guard.as_ref());
+            let bind = bind_nested(&nested, quote::quote!(#body));
+            quote::quote!(#pat if { #[allow(unused_variables)] let matched = #check; matched } => #bind)
+        }
+    });
+    quote::quote!(match &*(#expr).0 { #(#arms,)* }).into()
+}
+
+/// A pattern of a variant, whose nested variant patterns have been replaced by bindings.
+#[derive(Clone)]
+struct Lowered {
+    pat: syn::Pat,
+    nested: Vec<(syn::Ident, Lowered)>,
+}
+
+fn lower_variant(pat: syn::Pat, count: &mut usize) -> Lowered {
+    let mut nested = Vec::new();
+    let pat = match pat {
+        syn::Pat::TupleStruct(mut pat) => {
+            concrete_enum_path(&mut pat.path);
+            pat.pat.elems = std::mem::take(&mut pat.pat.elems)
+                .into_iter()
+                .map(|p| lower_field(p, &mut nested, count))
+                .collect();
+            syn::Pat::TupleStruct(pat)
+        }
+        syn::Pat::Struct(mut pat) => {
+            concrete_enum_path(&mut pat.path);
+            pat.fields.iter_mut().for_each(|f| {
+                let p = std::mem::replace(&mut *f.pat, syn::parse_quote!(_));
+                *f.pat = lower_field(p, &mut nested, count);
+                f.colon_token.get_or_insert_with(Default::default);
+            });
+            syn::Pat::Struct(pat)
+        }
+        syn::Pat::Path(mut pat) => {
+            concrete_enum_path(&mut pat.path);
+            syn::Pat::Path(pat)
+        }
+        syn::Pat::Or(mut pat) => {
+            pat.cases = std::mem::take(&mut pat.cases)
+                .into_iter()
+                .map(|p| {
+                    let lowered = lower_variant(p, count);
+                    if !lowered.nested.is_empty() {
+                        panic!("Nested variant patterns are not supported in or-patterns");
+                    }
+                    lowered.pat
+                })
+                .collect();
+            syn::Pat::Or(pat)
+        }
+        pat => pat,
+    };
+    Lowered { pat, nested }
+}
+
+/// Lowers the pattern of a field, which is nested if it is a variant pattern. Identifiers which
+/// start with an uppercase letter are taken to be unit variants.
+fn lower_field(
+    pat: syn::Pat,
+    nested: &mut Vec<(syn::Ident, Lowered)>,
+    count: &mut usize,
+) -> syn::Pat {
+    match pat {
+        syn::Pat::TupleStruct(_) | syn::Pat::Struct(_) | syn::Pat::Path(_) => {
+            let id = new_id(format!("nested{}", count));
+            *count += 1;
+            nested.push((id.clone(), lower_variant(pat, count)));
+            syn::parse_quote!(#id)
+        }
+        syn::Pat::Ident(mut pat) if pat.subpat.is_some() => {
+            let (_, subpat) = pat.subpat.take().unwrap();
+            nested.push((pat.ident.clone(), lower_variant(*subpat, count)));
+            syn::Pat::Ident(pat)
+        }
+        syn::Pat::Ident(pat) if pat.ident.to_string().starts_with(char::is_uppercase) => {
+            let path = syn::Path::from(pat.ident);
+            lower_field(syn::parse_quote!(#path), nested, count)
+        }
+        syn::Pat::Tuple(mut pat) => {
+            pat.elems = std::mem::take(&mut pat.elems)
+                .into_iter()
+                .map(|p| lower_field(p, nested, count))
+                .collect();
+            syn::Pat::Tuple(pat)
+        }
+        pat => pat,
+    }
+}
+
+/// Tests if the nested patterns and the guard match.
+fn check_nested(nested: &[(syn::Ident, Lowered)], guard: Option<&syn::Expr>) -> pm2::TokenStream {
+    match nested.split_first() {
+        Some(((id, lowered), rest)) => {
+            let pat = &lowered.pat;
+            let rest = lowered
+                .nested
+                .iter()
+                .chain(rest)
+                .cloned()
+                .collect::<Vec<_>>();
+            let check = check_nested(&rest, guard);
+            quote::quote!(match &*#id.0 { #pat if #check => true, _ => false })
+        }
+        None => guard.map_or_else(|| quote::quote!(true), |guard| quote::quote!(#guard)),
+    }
+}
+
+/// Binds the nested patterns around the body, after they have been checked.
+fn bind_nested(nested: &[(syn::Ident, Lowered)], body: pm2::TokenStream) -> pm2::TokenStream {
+    match nested.split_first() {
+        Some(((id, lowered), rest)) => {
+            let pat = &lowered.pat;
+            let rest = lowered
+                .nested
+                .iter()
+                .chain(rest)
+                .cloned()
+                .collect::<Vec<_>>();
+            let body = bind_nested(&rest, body);
+            quote::quote!(match &*#id.0 { #pat => #body, _ => unreachable!() })
+        }
+        None => body,
+    }
 }
 
 #[cfg(feature = "legacy")]
@@ -108,10 +333,11 @@ pub fn erase(input: TokenStream) -> TokenStream {
     let ident: syn::Ident = parse(&mut iter);
     let (wrapper_impl, wrapper_cons) = generate_wrapper(&ident);
     let wrapper = wrapper_cons(expr);
+    let vtable = generate_vtable(&ident);
     quote::quote!(
         {
             #wrapper_impl
-            Erased::erase(#wrapper, ctx)
+            Erased::erase(#wrapper, ErasedType::of::<#ident>(), #vtable, ctx)
         }
     )
     .into()
@@ -121,12 +347,8 @@ pub fn unerase(input: TokenStream) -> TokenStream {
     let mut iter = input.into_iter();
     let expr: syn::Expr = parse(&mut iter);
     let id: syn::Ident = parse(&mut iter);
-    let (wrapper_impl, _) = generate_wrapper(&id);
     quote::quote!(
-        {
-            #wrapper_impl
-            Erased::unerase::<#id>(#expr, ctx)
-        }
+        Erased::unerase::<#id>(#expr, ctx)?
     )
     .into()
 }
@@ -177,11 +399,12 @@ pub fn push_transition(input: TokenStream) -> TokenStream {
     .into()
 }
 
-// /// Transition to a new state.
+// /// Transition to a new state. The new state is entered immediately so that any future it
+// /// waits on gets polled, and thereby registers a waker, before the task is suspended.
 pub fn transition(input: TokenStream) -> TokenStream {
     let mut iter = input.into_iter();
     let state: syn::Expr = parse(&mut iter);
-    quote::quote!(return (Pending, #state.into())).into()
+    quote::quote!(return (Ready(()), #state.into())).into()
 }
 
 // /// Terminate the state machine.
@@ -191,29 +414,48 @@ pub fn terminate(input: TokenStream) -> TokenStream {
     quote::quote!(return (Ready(()), #state.into())).into()
 }
 
-// /// Wait until a future completes.
+// /// Wait until a future completes. While the future is pending, the task is suspended in the
+// /// `pending` state until the future wakes it up.
 pub fn wait(input: TokenStream) -> TokenStream {
     let mut iter = input.into_iter();
     let arg: syn::Expr = parse(&mut iter);
     let cx: syn::Expr = parse(&mut iter);
-    let finished: syn::Expr = parse(&mut iter);
     let pending: syn::Expr = parse(&mut iter);
+    let finished: syn::Expr = parse(&mut iter);
     quote::quote!(
         match #arg.as_mut().poll(#cx) {
             Ready(Finished) => terminate!(#finished),
+            Ready(Failed(error)) => {
+                ctx.state().fail(error);
+                terminate!(#finished)
+            }
             Ready(Continue(x)) => x,
-            Pending => transition!(#pending),
+            Pending => return (Pending, #pending.into()),
         }
     )
     .into()
 }
 
+/// Captures the implementations of `Debug`, `PartialEq`, `Eq` and `Hash` of `id`, if it has them.
+fn generate_vtable(id: &syn::Ident) -> pm2::TokenStream {
+    quote::quote!(
+        {
+            use arc_runtime::data::erased::probe::*;
+            ErasedVtable {
+                debug: (&Probe::<#id>::new()).debug_fn(),
+                format: (&Probe::<#id>::new()).format_fn(),
+                eq: (&Probe::<#id>::new()).eq_fn(),
+                hash: (&Probe::<#id>::new()).hash_fn(),
+                total_eq: (&Probe::<#id>::new()).is_total_eq(),
+            }
+        }
+    )
+}
+
 fn generate_wrapper(id: &syn::Ident) -> (pm2::TokenStream, impl Fn(syn::Expr) -> pm2::TokenStream) {
-    let span = id.span().unwrap().start();
-    let line = span.line;
-    let column = span.column;
-    let abstract_id: syn::Ident = new_id(format!("Wrapper_{}_{}", line, column));
-    let concrete_id: syn::Ident = new_id(format!("ConcreteWrapper_{}_{}", line, column));
+    let vtable = generate_vtable(id);
+    let abstract_id: syn::Ident = new_id(format!("Wrapper_{}", id));
+    let concrete_id: syn::Ident = new_id(format!("ConcreteWrapper_{}", id));
     let sharable_wrapper_mod_id = new_id(format!("sharable_{}", abstract_id));
     let sendable_wrapper_mod_id = new_id(format!("sendable_{}", abstract_id));
     let wrapper_impl = quote::quote!(
@@ -229,25 +471,34 @@ fn generate_wrapper(id: &syn::Ident) -> (pm2::TokenStream, impl Fn(syn::Expr) ->
 
         mod #sendable_wrapper_mod_id {
             use arc_runtime::prelude::*;
-            #[derive(Clone, Debug, Deref, From, Abstract, Serialize, Deserialize)]
+            #[derive(Clone, Deref, From, Abstract, Serialize, Deserialize)]
             #[repr(transparent)]
             pub struct #abstract_id(pub #concrete_id);
-            #[derive(Clone, Debug, Serialize, Deserialize)]
+            #[derive(Clone, Serialize, Deserialize)]
             #[repr(transparent)]
             pub struct #concrete_id(pub <super::#id as DynSharable>::T);
+
+            // Formats as the wrapped value, so that erased values can be debugged after being sent.
+            impl std::fmt::Debug for #abstract_id {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    std::fmt::Debug::fmt(&self.0.0, f)
+                }
+            }
         }
 
         impl DynSharable for #sharable_wrapper_mod_id::#abstract_id {
             type T = <Erased as DynSharable>::T;
             fn into_sendable(&self, ctx: Context) -> Self::T {
-                Self::T::erase(#sendable_wrapper_mod_id::#abstract_id(#sendable_wrapper_mod_id::#concrete_id(self.0.0.into_sendable(ctx))), ctx)
+                let ty = ErasedType::of::<#id>();
+                Self::T::erase(#sendable_wrapper_mod_id::#abstract_id(#sendable_wrapper_mod_id::#concrete_id(self.0.0.into_sendable(ctx))), ty, ctx)
             }
         }
 
         impl DynSendable for #sendable_wrapper_mod_id::#abstract_id {
             type T = Erased;
             fn into_sharable(&self, ctx: Context) -> Self::T {
-                Self::T::erase(#sharable_wrapper_mod_id::#abstract_id(#sharable_wrapper_mod_id::#concrete_id(self.0.0.into_sharable(ctx))), ctx)
+                let ty = ErasedType::of::<#id>();
+                Self::T::erase(#sharable_wrapper_mod_id::#abstract_id(#sharable_wrapper_mod_id::#concrete_id(self.0.0.into_sharable(ctx))), ty, #vtable, ctx)
             }
         }
     );
@@ -297,5 +548,8 @@ fn parse_all<T: syn::parse::Parse>(input: &mut impl Iterator<Item = pm::TokenTre
             _ => stream.extend([token]),
         }
     }
+    if !stream.is_empty() {
+        nodes.push(syn::parse::<T>(stream).unwrap());
+    }
     nodes
 }
@@ -0,0 +1,155 @@
+//! Remote channels, which carry the streams between nodes on different workers.
+
+use crate::cluster::message::ClusterMessage;
+use crate::cluster::message::Edge;
+use crate::data::channels::local::multicast::channel;
+use crate::data::channels::local::multicast::Pullable;
+use crate::data::channels::local::multicast::Pushable;
+use crate::prelude::*;
+
+use kompact::prelude::*;
+
+/// A type-erased `Pullable<T>`.
+type AnyPullable = Box<dyn Any + Send>;
+
+/// Where the values of an exported stream are sent.
+#[derive(Clone)]
+pub(crate) struct Link {
+    pub(crate) target: ActorPath,
+    pub(crate) job: u64,
+    pub(crate) edge: Edge,
+}
+
+/// The receiving end of a remote channel, which pushes deserialised values into a local
+/// channel. Dropping it closes the local channel.
+pub(crate) struct Import {
+    push: Box<dyn FnMut(&[u8]) -> Result<(), bincode::Error> + Send>,
+    /// The context in which values are pushed, which outlives the context of the job.
+    ctx: Context,
+}
+
+impl Import {
+    pub(crate) fn push(&mut self, value: &[u8]) -> Result<(), bincode::Error> {
+        (self.push)(value)
+    }
+}
+
+impl Drop for Import {
+    fn drop(&mut self) {
+        self.ctx.destroy();
+    }
+}
+
+/// Launches a task which sends the values of a local stream over `link`.
+pub(crate) fn export<T>(input: AnyPullable, link: Link, ctx: Context)
+where
+    T: Sharable + 'static,
+    T::T: Sendable<T = T>,
+{
+    let input = *input.downcast::<Pullable<T>>().unwrap();
+    ctx.supervise(move |state| Export::new(state, input.clone(), link.clone()));
+}
+
+/// Creates a local stream whose values are received through the returned import.
+pub(crate) fn import<T>(ctx: Context) -> (Import, AnyPullable)
+where
+    T: Sharable + 'static,
+    T::T: Sendable<T = T>,
+{
+    let (o, i): (Pushable<T>, Pullable<T>) = channel(ctx);
+    let mutator = instantiate_immix(ImmixOptions::default());
+    let ctx = Context::new(ctx.component().clone(), mutator);
+    let push = Box::new(move |value: &[u8]| {
+        let value: T::T = bincode::deserialize(value)?;
+        o.try_push(value.into_sharable(ctx), ctx);
+        Ok(())
+    });
+    (Import { push, ctx }, Box::new(i))
+}
+
+/// The task which sends the values of a stream to another worker.
+#[derive(ComponentDefinition)]
+struct Export<T: Sharable + 'static>
+where
+    T::T: Sendable<T = T>,
+{
+    ctx: ComponentContext<Self>,
+    state: Arc<TaskState>,
+    input: Option<Pullable<T>>,
+    link: Link,
+}
+
+impl<T: Sharable + 'static> Export<T>
+where
+    T::T: Sendable<T = T>,
+{
+    fn new(state: Arc<TaskState>, input: Pullable<T>, link: Link) -> Self {
+        Self {
+            ctx: ComponentContext::uninitialised(),
+            state,
+            input: Some(input),
+            link,
+        }
+    }
+
+    async fn run(
+        mut input: Pullable<T>,
+        link: Link,
+        system: KompactSystem,
+        ctx: Context,
+    ) -> Control<()> {
+        loop {
+            let value = match input.pull(ctx).await {
+                Control::Continue(value) => value,
+                Control::Finished => break,
+                Control::Failed(error) => return Control::Failed(error),
+            };
+            let value = bincode::serialize(&value.into_sendable(ctx))?;
+            let (job, edge) = (link.job, link.edge);
+            link.target
+                .tell(ClusterMessage::Data { job, edge, value }, &system);
+        }
+        let (job, edge) = (link.job, link.edge);
+        link.target
+            .tell(ClusterMessage::Close { job, edge }, &system);
+        Control::Finished
+    }
+}
+
+impl<T: Sharable + 'static> Actor for Export<T>
+where
+    T::T: Sendable<T = T>,
+{
+    type Message = TaskMessage;
+
+    fn receive_local(&mut self, msg: Self::Message) -> Handled {
+        self.state.handle(msg);
+        Handled::Ok
+    }
+
+    fn receive_network(&mut self, _: NetMessage) -> Handled {
+        warn!(self.log(), "Ignoring network message sent to a local task");
+        Handled::Ok
+    }
+}
+
+impl<T: Sharable + 'static> ComponentLifecycle for Export<T>
+where
+    T::T: Sendable<T = T>,
+{
+    fn on_start(&mut self) -> Handled {
+        let input = self.input.take().unwrap();
+        let link = self.link.clone();
+        let state = self.state.clone();
+        self.spawn_local(move |async_self| async move {
+            let component = async_self.ctx().component();
+            let system = async_self.ctx().system();
+            let mutator = instantiate_immix(ImmixOptions::default());
+            let ctx = Context::with_state(component, mutator, state.clone());
+            state.control(Self::run(input, link, system, ctx)).await;
+            ctx.destroy();
+            Handled::DieNow
+        });
+        Handled::Ok
+    }
+}
@@ -0,0 +1,220 @@
+//! The master of a cluster, which places the nodes of submitted graphs on its workers.
+
+use crate::cluster::message::ClusterMessage;
+use crate::cluster::Graphs;
+use crate::control::TaskError;
+use crate::dataflow::DataflowError;
+use crate::prelude::*;
+
+use kompact::prelude::*;
+
+use std::collections::HashMap;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The alias under which the master is registered in its runtime.
+pub(crate) const ALIAS: &str = "master";
+
+/// The state which is shared between the master and its coordinator.
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    workers: std::vec::Vec<ActorPath>,
+    next_job: u64,
+    jobs: HashMap<u64, Progress>,
+}
+
+/// The progress of a job across all workers.
+struct Progress {
+    /// The number of workers which have not completed their part of the job yet.
+    remaining: usize,
+    errors: std::vec::Vec<std::string::String>,
+}
+
+impl Shared {
+    /// Blocks until `done` holds for the state, or until `timeout` has passed.
+    fn wait_until(&self, timeout: Option<Duration>, mut done: impl FnMut(&State) -> bool) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state.lock().unwrap();
+        while !done(&state) {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+                }
+                None => state = self.changed.wait(state).unwrap(),
+            }
+        }
+        true
+    }
+}
+
+/// Receives registrations and completions from workers, and acknowledges the completions.
+#[derive(ComponentDefinition)]
+struct Coordinator {
+    ctx: ComponentContext<Self>,
+    shared: Arc<Shared>,
+}
+
+impl Coordinator {
+    fn new(shared: Arc<Shared>) -> Self {
+        Self {
+            ctx: ComponentContext::uninitialised(),
+            shared,
+        }
+    }
+}
+
+impl ComponentLifecycle for Coordinator {}
+
+impl Actor for Coordinator {
+    type Message = Never;
+
+    fn receive_local(&mut self, msg: Self::Message) -> Handled {
+        match msg {}
+    }
+
+    fn receive_network(&mut self, msg: NetMessage) -> Handled {
+        let sender = msg.sender.clone();
+        let mut state = self.shared.state.lock().unwrap();
+        match msg.try_deserialise::<ClusterMessage, ClusterMessage>() {
+            Ok(ClusterMessage::Register) => {
+                info!(self.log(), "Worker {} registered", sender);
+                state.workers.push(sender);
+            }
+            Ok(ClusterMessage::Completed { job, outcome }) => {
+                if let Some(progress) = state.jobs.get_mut(&job) {
+                    progress.remaining = progress.remaining.saturating_sub(1);
+                    progress.errors.extend(outcome.err());
+                }
+                sender.tell(ClusterMessage::Acknowledged { job }, self);
+            }
+            Ok(msg) => warn!(self.log(), "Ignoring unexpected message {:?}", msg),
+            Err(error) => warn!(self.log(), "Ignoring undecodable message: {:?}", error),
+        }
+        self.shared.changed.notify_all();
+        Handled::Ok
+    }
+}
+
+/// The master of a cluster.
+pub struct Master {
+    system: KompactSystem,
+    graphs: Graphs,
+    shared: Arc<Shared>,
+}
+
+impl Master {
+    /// Starts the master inside `runtime`, which must have been created with networking.
+    pub fn start(runtime: &Runtime, graphs: Graphs) -> Self {
+        let shared = Arc::new(Shared::default());
+        let coordinator = runtime.system.create({
+            let shared = shared.clone();
+            move || Coordinator::new(shared)
+        });
+        runtime
+            .system
+            .register_by_alias(&coordinator, ALIAS)
+            .wait_expect(Duration::from_secs(5), "Failed to register the master");
+        runtime.system.start(&coordinator);
+        Self {
+            system: runtime.system.clone(),
+            graphs,
+            shared,
+        }
+    }
+
+    /// Returns the number of registered workers.
+    pub fn workers(&self) -> usize {
+        self.shared.state.lock().unwrap().workers.len()
+    }
+
+    /// Blocks until at least `n` workers have registered, or until `timeout` has passed.
+    /// Returns whether enough workers registered.
+    pub fn await_workers(&self, n: usize, timeout: Duration) -> bool {
+        self.shared
+            .wait_until(Some(timeout), |state| state.workers.len() >= n)
+    }
+
+    /// Places the nodes of the graph named `name` on the registered workers, round-robin, and
+    /// tells every worker to deploy its part.
+    pub fn submit(&self, name: &str) -> Result<ClusterJob, DataflowError> {
+        let graph = self
+            .graphs
+            .get(name)
+            .ok_or_else(|| DataflowError::UnknownGraph(name.to_string()))?;
+        let graph = graph();
+        graph.validate()?;
+        graph.validate_sendable()?;
+        let mut state = self.shared.state.lock().unwrap();
+        if state.workers.is_empty() {
+            return Err(DataflowError::NoWorkers);
+        }
+        let workers = state.workers.clone();
+        let placement = (0..graph.len())
+            .map(|node| node % workers.len())
+            .collect::<std::vec::Vec<_>>();
+        let job = state.next_job;
+        state.next_job += 1;
+        state.jobs.insert(
+            job,
+            Progress {
+                remaining: workers.len(),
+                errors: std::vec::Vec::new(),
+            },
+        );
+        let paths = workers
+            .iter()
+            .map(|worker| worker.to_string())
+            .collect::<std::vec::Vec<_>>();
+        for (index, worker) in workers.iter().enumerate() {
+            let deploy = ClusterMessage::Deploy {
+                job,
+                graph: name.to_string(),
+                worker: index,
+                placement: placement.clone(),
+                workers: paths.clone(),
+            };
+            worker.tell(deploy, &self.system);
+        }
+        Ok(ClusterJob {
+            id: JobId(job),
+            shared: self.shared.clone(),
+        })
+    }
+}
+
+/// A job which has been submitted to a cluster.
+pub struct ClusterJob {
+    id: JobId,
+    shared: Arc<Shared>,
+}
+
+impl ClusterJob {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// Blocks until every worker has completed its part of the job, and returns the final
+    /// status of the job. The job fails if any of its parts failed.
+    pub fn await_completion(&self) -> JobStatus {
+        let job = self.id.0;
+        self.shared
+            .wait_until(None, |state| state.jobs[&job].remaining == 0);
+        let state = self.shared.state.lock().unwrap();
+        match state.jobs[&job].errors.first() {
+            Some(error) => JobStatus::Failed(TaskError::new(error.clone())),
+            None => JobStatus::Finished,
+        }
+    }
+}
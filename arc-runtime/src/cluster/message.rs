@@ -0,0 +1,75 @@
+use kompact::prelude::*;
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use std::any::Any;
+
+/// Identifies the serialiser of cluster messages on the wire.
+const SER_ID: SerId = 4200;
+
+/// An edge of a dataflow graph, identified by the node which consumes it and the index of the
+/// input of the node.
+pub(crate) type Edge = (usize, usize);
+
+/// A message between the processes of a cluster.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum ClusterMessage {
+    /// Sent by a worker to the master when it starts.
+    Register,
+    /// Tells a worker to deploy the nodes of a graph which have been placed on it.
+    Deploy {
+        job: u64,
+        graph: std::string::String,
+        /// The index of the receiving worker.
+        worker: usize,
+        /// The index of the worker of every node.
+        placement: std::vec::Vec<usize>,
+        /// The paths of all workers.
+        workers: std::vec::Vec<std::string::String>,
+    },
+    /// A serialised value of a stream which crosses an edge between workers.
+    Data {
+        job: u64,
+        edge: Edge,
+        value: std::vec::Vec<u8>,
+    },
+    /// Tells the consumer of an edge that the stream has ended.
+    Close { job: u64, edge: Edge },
+    /// Tells the master that a worker has completed its part of a job.
+    Completed {
+        job: u64,
+        outcome: Result<(), std::string::String>,
+    },
+    /// Tells a worker that the master has received the completion of a job.
+    Acknowledged { job: u64 },
+}
+
+impl Serialisable for ClusterMessage {
+    fn ser_id(&self) -> SerId {
+        SER_ID
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        None
+    }
+
+    fn serialise(&self, buf: &mut dyn BufMut) -> Result<(), SerError> {
+        let bytes = bincode::serialize(self).map_err(|e| SerError::InvalidData(e.to_string()))?;
+        buf.put_slice(&bytes);
+        Ok(())
+    }
+
+    fn local(self: Box<Self>) -> Result<Box<dyn Any + Send>, Box<dyn Serialisable>> {
+        Ok(self)
+    }
+}
+
+impl Deserialiser<ClusterMessage> for ClusterMessage {
+    const SER_ID: SerId = SER_ID;
+
+    fn deserialise(buf: &mut dyn Buf) -> Result<ClusterMessage, SerError> {
+        let bytes = buf.copy_to_bytes(buf.remaining());
+        bincode::deserialize(&bytes).map_err(|e| SerError::InvalidData(e.to_string()))
+    }
+}
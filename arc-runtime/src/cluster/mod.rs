@@ -0,0 +1,145 @@
+//! Cluster mode, in which a master places the nodes of dataflow graphs on workers.
+//!
+//! Every process of a cluster runs the same program, and registers the same [`Graphs`]. A
+//! [`Worker`] registers with the [`Master`] over Kompact networking. When a graph is submitted
+//! to the master, it assigns each node of the graph to a worker and tells every worker to
+//! deploy its part. Streams between nodes on different workers are turned into remote channels:
+//! the producing worker launches a task which serialises the values of the stream and sends them
+//! to the consuming worker, which pushes them into a local channel. Only streams which have been
+//! marked through [`DataflowBuilder::set_sendable`] can be sent, and the master rejects graphs
+//! which consume any other stream.
+//!
+//! ```no_run
+//! use arc_runtime::cluster::Graphs;
+//! use arc_runtime::cluster::Master;
+//! use arc_runtime::cluster::Worker;
+//! use arc_runtime::data::channels::local::multicast::channel;
+//! use arc_runtime::data::channels::local::multicast::Pullable;
+//! use arc_runtime::prelude::*;
+//!
+//! #[rewrite(nonpersistent)]
+//! async fn print(mut i: Pullable<i32>) {
+//!     loop {
+//!         let x: i32 = pull!(i);
+//!         println!("{}", x);
+//!     }
+//! }
+//!
+//! fn graph() -> DataflowBuilder {
+//!     let mut builder = DataflowBuilder::new();
+//!     let s = builder.add_source("numbers", |ctx| {
+//!         let (o, i) = channel(ctx);
+//!         for x in 1..=10 {
+//!             o.try_push(x, ctx);
+//!         }
+//!         i
+//!     });
+//!     builder.set_sendable(s);
+//!     let s = builder.add_map("double", s, |x: i32, _| x * 2);
+//!     builder.set_sendable(s);
+//!     builder.add_sink("print", s, |s, ctx| print((s,), ctx));
+//!     builder
+//! }
+//!
+//! fn main() {
+//!     let graphs = Graphs::new().with("graph", graph);
+//!     let runtime = Runtime::with_network("127.0.0.1:2020".parse().unwrap());
+//!     let master = Master::start(&runtime, graphs.clone());
+//!     let worker = Worker::start(&runtime, runtime.address(), graphs);
+//!     master.await_workers(1, std::time::Duration::from_secs(1));
+//!     master.submit("graph").unwrap().await_completion();
+//! }
+//! ```
+
+pub(crate) mod bridge;
+pub mod master;
+pub(crate) mod message;
+pub mod worker;
+
+pub use master::ClusterJob;
+pub use master::Master;
+pub use worker::Worker;
+
+use crate::cluster::bridge::Import;
+use crate::cluster::bridge::Link;
+use crate::cluster::message::Edge;
+use crate::dataflow::DataflowBuilder;
+
+use kompact::prelude::ActorPath;
+
+use std::collections::HashMap;
+
+/// Builds a dataflow graph. Every process of a cluster builds the same graph, and deploys the
+/// nodes which have been placed on it.
+pub type GraphFactory = fn() -> DataflowBuilder;
+
+/// The graphs which can be submitted to a cluster, by name.
+#[derive(Clone, Default)]
+pub struct Graphs(HashMap<std::string::String, GraphFactory>);
+
+impl Graphs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<std::string::String>, graph: GraphFactory) -> Self {
+        self.0.insert(name.into(), graph);
+        self
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<GraphFactory> {
+        self.0.get(name).copied()
+    }
+}
+
+/// The part of a graph which is deployed by one worker.
+pub(crate) struct Placement {
+    job: u64,
+    /// The index of the worker of every node.
+    workers: std::vec::Vec<usize>,
+    /// The index of this worker.
+    local: usize,
+    /// The paths of all workers.
+    peers: std::vec::Vec<ActorPath>,
+    /// The edges whose values are received from other workers.
+    pub(crate) imports: std::vec::Vec<(Edge, Import)>,
+}
+
+impl Placement {
+    /// Places all `nodes` on this worker.
+    pub(crate) fn single(nodes: usize) -> Self {
+        Self::new(0, vec![0; nodes], 0, std::vec::Vec::new())
+    }
+
+    pub(crate) fn new(
+        job: u64,
+        workers: std::vec::Vec<usize>,
+        local: usize,
+        peers: std::vec::Vec<ActorPath>,
+    ) -> Self {
+        Self {
+            job,
+            workers,
+            local,
+            peers,
+            imports: std::vec::Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_distributed(&self) -> bool {
+        self.workers.iter().any(|worker| *worker != self.local)
+    }
+
+    pub(crate) fn is_local(&self, node: usize) -> bool {
+        self.workers[node] == self.local
+    }
+
+    /// Returns the link to the worker of `node`, over which `edge` is sent.
+    pub(crate) fn link(&self, node: usize, edge: Edge) -> Link {
+        Link {
+            target: self.peers[self.workers[node]].clone(),
+            job: self.job,
+            edge,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! The workers of a cluster, which deploy the parts of graphs which the master placed on them.
+
+use crate::cluster::bridge::Import;
+use crate::cluster::master::ALIAS;
+use crate::cluster::message::ClusterMessage;
+use crate::cluster::message::Edge;
+use crate::cluster::Graphs;
+use crate::cluster::Placement;
+use crate::control::TaskError;
+use crate::prelude::*;
+use crate::task::supervisor::Supervisor;
+
+use kompact::prelude::*;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::sync::Condvar;
+use std::sync::Mutex;
+
+/// Identifies an edge of a job.
+type Key = (u64, Edge);
+
+/// Messages which a worker sends to itself.
+#[derive(Debug)]
+enum Local {
+    /// All tasks of a job have exited on this worker.
+    Completed(u64, JobStatus),
+}
+
+/// A message for an edge which has not been imported yet.
+enum Pending {
+    Data(std::vec::Vec<u8>),
+    Close,
+}
+
+/// The number of jobs which have completed on a worker, and whose completion the master has
+/// acknowledged.
+#[derive(Default)]
+struct Completed {
+    count: Mutex<usize>,
+    changed: Condvar,
+}
+
+/// Deploys graphs on behalf of the master and receives the values of remote channels.
+#[derive(ComponentDefinition)]
+struct Executor {
+    ctx: ComponentContext<Self>,
+    master: ActorPath,
+    graphs: Graphs,
+    imports: HashMap<Key, Import>,
+    /// Messages which arrived before the consuming node was deployed.
+    pending: HashMap<Key, std::vec::Vec<Pending>>,
+    jobs: HashMap<u64, JobHandle>,
+    completed: Arc<Completed>,
+}
+
+impl Executor {
+    fn new(master: ActorPath, graphs: Graphs, completed: Arc<Completed>) -> Self {
+        Self {
+            ctx: ComponentContext::uninitialised(),
+            master,
+            graphs,
+            imports: HashMap::new(),
+            pending: HashMap::new(),
+            jobs: HashMap::new(),
+            completed,
+        }
+    }
+
+    fn deploy(
+        &mut self,
+        job: u64,
+        graph: std::string::String,
+        worker: usize,
+        placement: std::vec::Vec<usize>,
+        workers: std::vec::Vec<std::string::String>,
+    ) {
+        let id = JobId(job);
+        let component = self.ctx().component();
+        let mutator = instantiate_immix(ImmixOptions::default());
+        let ctx = Context::for_job(component, mutator, Supervisor::for_job(id));
+        let mut placement = Placement::new(job, placement, worker, std::vec::Vec::new());
+        let graphs = self.graphs.clone();
+        let error = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let graph = graphs.get(&graph).expect("Unknown graph")();
+            placement.peers = workers
+                .iter()
+                .map(|path| path.parse().expect("Invalid worker path"))
+                .collect();
+            graph.run_placed(ctx, &mut placement);
+        }))
+        .err()
+        .map(|panic| TaskError::from_panic(&*panic));
+        for (edge, import) in placement.imports.drain(..) {
+            self.imports.insert((job, edge), import);
+            for message in self.pending.remove(&(job, edge)).unwrap_or_default() {
+                match message {
+                    Pending::Data(value) => self.receive(job, edge, value),
+                    Pending::Close => self.close(job, edge),
+                }
+            }
+        }
+        let handle = JobHandle::new(id, ctx.supervisor().clone(), ctx.launched().to_vec(), error);
+        // The launched tasks have moved their inputs onto their own heaps, and the imports have
+        // contexts of their own.
+        ctx.destroy();
+        self.jobs.insert(job, handle.clone());
+        let this = self.actor_ref();
+        std::thread::spawn(move || {
+            let status = handle.await_completion();
+            this.tell(Local::Completed(job, status));
+        });
+    }
+
+    fn receive(&mut self, job: u64, edge: Edge, value: std::vec::Vec<u8>) {
+        match self.imports.get_mut(&(job, edge)) {
+            Some(import) => {
+                if let Err(error) = import.push(&value) {
+                    error!(
+                        self.log(),
+                        "Failed to decode a value of {}: {}",
+                        JobId(job),
+                        error
+                    );
+                }
+            }
+            None => self
+                .pending
+                .entry((job, edge))
+                .or_default()
+                .push(Pending::Data(value)),
+        }
+    }
+
+    fn close(&mut self, job: u64, edge: Edge) {
+        // Dropping the import closes the local channel.
+        if self.imports.remove(&(job, edge)).is_none() {
+            self.pending
+                .entry((job, edge))
+                .or_default()
+                .push(Pending::Close);
+        }
+    }
+}
+
+impl ComponentLifecycle for Executor {
+    fn on_start(&mut self) -> Handled {
+        self.master.tell(ClusterMessage::Register, self);
+        Handled::Ok
+    }
+}
+
+impl Actor for Executor {
+    type Message = Local;
+
+    fn receive_local(&mut self, msg: Self::Message) -> Handled {
+        let Local::Completed(job, status) = msg;
+        self.jobs.remove(&job);
+        self.imports.retain(|(j, _), _| *j != job);
+        let outcome = match status {
+            JobStatus::Finished => Ok(()),
+            JobStatus::Failed(error) => Err(error.to_string()),
+            status => Err(format!("Job ended with status {:?}", status)),
+        };
+        self.master
+            .tell(ClusterMessage::Completed { job, outcome }, self);
+        Handled::Ok
+    }
+
+    fn receive_network(&mut self, msg: NetMessage) -> Handled {
+        match msg.try_deserialise::<ClusterMessage, ClusterMessage>() {
+            Ok(ClusterMessage::Deploy {
+                job,
+                graph,
+                worker,
+                placement,
+                workers,
+            }) => self.deploy(job, graph, worker, placement, workers),
+            Ok(ClusterMessage::Data { job, edge, value }) => self.receive(job, edge, value),
+            Ok(ClusterMessage::Close { job, edge }) => self.close(job, edge),
+            Ok(ClusterMessage::Acknowledged { .. }) => {
+                *self.completed.count.lock().unwrap() += 1;
+                self.completed.changed.notify_all();
+            }
+            Ok(msg) => warn!(self.log(), "Ignoring unexpected message {:?}", msg),
+            Err(error) => warn!(self.log(), "Ignoring undecodable message: {:?}", error),
+        }
+        Handled::Ok
+    }
+}
+
+/// A worker of a cluster.
+pub struct Worker {
+    completed: Arc<Completed>,
+}
+
+impl Worker {
+    /// Starts a worker inside `runtime`, which must have been created with networking, and
+    /// registers it with the master listening on `master`.
+    pub fn start(runtime: &Runtime, master: SocketAddr, graphs: Graphs) -> Self {
+        let path = NamedPath::new(
+            Transport::Tcp,
+            master.ip(),
+            master.port(),
+            vec![ALIAS.to_string()],
+        );
+        let completed = Arc::new(Completed::default());
+        let executor = runtime.system.create({
+            let completed = completed.clone();
+            move || Executor::new(ActorPath::Named(path), graphs, completed)
+        });
+        runtime.system.start(&executor);
+        Self { completed }
+    }
+
+    /// Blocks until `n` jobs have completed on this worker, and the master has acknowledged
+    /// their completion.
+    pub fn await_completed(&self, n: usize) {
+        let mut count = self.completed.count.lock().unwrap();
+        while *count < n {
+            count = self.completed.changed.wait(count).unwrap();
+        }
+    }
+}
@@ -1,12 +1,25 @@
+use comet::api::Collectable;
+use comet::api::Gc;
+use comet::gc_base::AllocationSpace;
 use comet::immix::Immix;
 use comet::immix::ImmixOptions;
 use comet::mutator::MutatorRef;
 use derive_more::Constructor as New;
 use kompact::prelude::*;
 
+use crate::data::channels::local::multicast::Pushable;
+use crate::job::metrics::Metric;
+use crate::job::JobId;
 use crate::prelude::Send;
 use crate::prelude::Sync;
 use crate::prelude::Unpin;
+use crate::task::dead_letter::DeadLetter;
+use crate::task::dead_letter::DeadLetters;
+use crate::task::handle::TaskHandle;
+use crate::task::message::TaskMessage;
+use crate::task::state::TaskState;
+use crate::task::supervisor::RestartPolicy;
+use crate::task::supervisor::Supervisor;
 use std::sync::Arc;
 
 /// The context of a single task.
@@ -18,8 +31,25 @@ pub struct Context(*mut Core);
 struct Core {
     pub component: Arc<dyn CoreContainer>,
     pub mutator: MutatorRef<Immix>,
+    pub state: Arc<TaskState>,
+    /// Handles of the tasks which have been launched through this context.
+    pub launched: Vec<TaskHandle>,
+    /// Supervises the tasks which are launched through this context. This is the supervisor of
+    /// the job which the task belongs to, if any.
+    pub supervisor: Supervisor,
+    /// The restart policy of the tasks which are launched through this context.
+    pub policy: RestartPolicy,
+    /// How the tasks which are launched through this context handle failed records.
+    pub dead_letters: DeadLetters,
+    /// Objects which have been allocated since the metrics of the job were last updated.
+    pub allocations: u64,
+    /// Bytes which have been allocated since the metrics of the job were last updated.
+    pub allocated_bytes: u64,
 }
 
+/// How many objects a task allocates before it adds them to the metrics of its job.
+const ALLOCATIONS_PER_FLUSH: u64 = 1024;
+
 impl Context {
     #[allow(clippy::mut_from_ref)]
     fn as_mut(&self) -> &mut Core {
@@ -30,9 +60,47 @@ impl Context {
 
 impl Context {
     pub fn new(component: Arc<dyn CoreContainer>, mutator: MutatorRef<Immix>) -> Self {
-        Self(Box::leak(Box::new(Core::new(component, mutator))) as *mut Core)
+        Self::with_state(component, mutator, Arc::default())
+    }
+    /// Creates the context of a task which is controlled through `state`. The tasks which it
+    /// launches belong to the same job as the task.
+    pub fn with_state(
+        component: Arc<dyn CoreContainer>,
+        mutator: MutatorRef<Immix>,
+        state: Arc<TaskState>,
+    ) -> Self {
+        let supervisor = state.supervisor().unwrap_or_default();
+        Self::create(component, mutator, state, supervisor)
+    }
+    /// Creates the context in which the body of a job runs.
+    pub(crate) fn for_job(
+        component: Arc<dyn CoreContainer>,
+        mutator: MutatorRef<Immix>,
+        supervisor: Supervisor,
+    ) -> Self {
+        Self::create(component, mutator, Arc::default(), supervisor)
+    }
+    fn create(
+        component: Arc<dyn CoreContainer>,
+        mutator: MutatorRef<Immix>,
+        state: Arc<TaskState>,
+        supervisor: Supervisor,
+    ) -> Self {
+        let core = Core::new(
+            component,
+            mutator,
+            state,
+            Vec::new(),
+            supervisor,
+            RestartPolicy::default(),
+            DeadLetters::default(),
+            0,
+            0,
+        );
+        Self(Box::leak(Box::new(core)) as *mut Core)
     }
     pub fn destroy(self) {
+        self.flush_metrics();
         // SAFETY: This is safe because the context is managed entirely by the code generator. This
         // function is only ever called once.
         unsafe {
@@ -43,17 +111,93 @@ impl Context {
     pub fn mutator(&self) -> &mut MutatorRef<Immix> {
         &mut self.as_mut().mutator
     }
+    /// Allocates `value` on the heap of the task, and accounts for it in the metrics of its job.
+    /// The metrics are updated in batches, and when the context is destroyed.
+    pub fn allocate<T: Collectable + Sized + 'static>(&self, value: T) -> Gc<T, Immix> {
+        let core = self.as_mut();
+        core.allocations += 1;
+        core.allocated_bytes += value.allocation_size() as u64;
+        if core.allocations >= ALLOCATIONS_PER_FLUSH {
+            self.flush_metrics();
+        }
+        self.mutator().allocate(value, AllocationSpace::New)
+    }
+    /// Adds the allocations which have not yet been accounted for to the metrics of the job.
+    pub(crate) fn flush_metrics(&self) {
+        let core = self.as_mut();
+        if core.allocations > 0 {
+            let metrics = core.supervisor.metrics();
+            metrics.add(Metric::HeapAllocations, core.allocations);
+            metrics.add(Metric::HeapBytes, core.allocated_bytes);
+            core.allocations = 0;
+            core.allocated_bytes = 0;
+        }
+    }
     #[allow(clippy::mut_from_ref)]
     pub fn component(&self) -> &mut Arc<dyn CoreContainer> {
         &mut self.as_mut().component
     }
-    pub fn launch<C, F>(&self, f: F)
+    pub fn state(&self) -> &Arc<TaskState> {
+        &self.as_mut().state
+    }
+    /// Returns handles to the tasks which have been launched through this context.
+    pub fn launched(&self) -> &[TaskHandle] {
+        &self.as_mut().launched
+    }
+    pub fn supervisor(&self) -> &Supervisor {
+        &self.as_mut().supervisor
+    }
+    /// Returns the job which the task belongs to, if any.
+    pub fn job(&self) -> Option<JobId> {
+        self.supervisor().job()
+    }
+    /// Sets the restart policy of the tasks which are launched through this context from now on.
+    pub fn set_restart_policy(&self, policy: RestartPolicy) {
+        self.as_mut().policy = policy;
+    }
+    /// Sets how many times the tasks which are launched through this context from now on retry
+    /// a record which fails, before routing it to their dead letters.
+    pub fn set_retries(&self, retries: usize) {
+        self.as_mut().dead_letters.retries = retries;
+    }
+    /// Sets the name which the tasks which are launched through this context from now on record
+    /// in their dead letters.
+    pub fn set_task_name(&self, name: Option<std::string::String>) {
+        self.as_mut().dead_letters.task = name;
+    }
+    /// Sets where the tasks which are launched through this context from now on route the
+    /// records which fail. The sink is held by each task until it exits.
+    pub fn set_dead_letter_sink(&self, sink: Option<Pushable<DeadLetter>>) {
+        self.as_mut().dead_letters.sink = sink;
+    }
+    /// Launches a task which cannot be restarted. The task is still tracked by the supervisor
+    /// of the context, so that it is killed together with its job.
+    pub fn launch<C, F>(&self, f: F) -> TaskHandle
     where
         F: FnOnce() -> C,
-        C: ComponentDefinition + 'static,
+        C: ComponentDefinition + ActorRaw<Message = TaskMessage> + 'static,
     {
         let system = self.as_mut().component.system();
         let c = system.create(f);
         system.start(&c);
+        let handle = TaskHandle::new(c.actor_ref());
+        self.supervisor().track(handle.clone());
+        self.as_mut().launched.push(handle.clone());
+        handle
+    }
+    /// Launches a supervised task, which is restarted according to the restart policy of this
+    /// context. The task is created by `f` from the state which it keeps across restarts.
+    pub fn supervise<C, F>(&self, f: F) -> TaskHandle
+    where
+        F: Fn(Arc<TaskState>) -> C + Send + Sync + 'static,
+        C: ComponentDefinition + 'static,
+    {
+        let core = self.as_mut();
+        let system = core.component.system();
+        let handle =
+            core.supervisor
+                .launch(&system, core.policy.clone(), core.dead_letters.clone(), f);
+        core.launched.push(handle.clone());
+        handle
     }
 }
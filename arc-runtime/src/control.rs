@@ -1,29 +1,129 @@
+use std::any::Any;
+use std::convert::Infallible;
+use std::fmt::Display;
 use std::ops::ControlFlow;
 use std::ops::FromResidual;
 use std::ops::Try;
+use std::panic::Location;
 
 #[derive(Debug)]
 pub enum Control<T> {
     Continue(T),
+    /// The task ended normally, for example because its input reached end-of-stream.
     Finished,
+    /// The task failed.
+    Failed(TaskError),
 }
 
-impl<T> FromResidual<()> for Control<T> {
-    fn from_residual(residual: ()) -> Self {
-        Control::Finished
+/// An error which made a task fail, together with where in the source it was raised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskError {
+    message: std::string::String,
+    location: &'static Location<'static>,
+}
+
+impl TaskError {
+    #[track_caller]
+    pub fn new(message: impl Display) -> Self {
+        Self {
+            message: message.to_string(),
+            location: Location::caller(),
+        }
+    }
+
+    /// Creates an error out of the payload of a panic, such as the one returned by
+    /// `std::panic::catch_unwind`.
+    #[track_caller]
+    pub fn from_panic(panic: &(dyn Any + Send)) -> Self {
+        if let Some(message) = panic.downcast_ref::<&str>() {
+            Self::new(message)
+        } else if let Some(message) = panic.downcast_ref::<std::string::String>() {
+            Self::new(message)
+        } else {
+            Self::new("Unknown panic")
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+impl Display for TaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.location)
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+impl<T> FromResidual<Control<Infallible>> for Control<T> {
+    fn from_residual(residual: Control<Infallible>) -> Self {
+        match residual {
+            Control::Continue(never) => match never {},
+            Control::Finished => Control::Finished,
+            Control::Failed(error) => Control::Failed(error),
+        }
+    }
+}
+
+/// Lets `?` on a `Result` fail the task, for example when calling an extern function.
+impl<T, E: Display> FromResidual<Result<Infallible, E>> for Control<T> {
+    #[track_caller]
+    fn from_residual(residual: Result<Infallible, E>) -> Self {
+        match residual {
+            Ok(never) => match never {},
+            Err(error) => Control::Failed(TaskError::new(error)),
+        }
+    }
+}
+
+impl<T, E: Display> From<Result<T, E>> for Control<T> {
+    #[track_caller]
+    fn from(result: Result<T, E>) -> Self {
+        match result {
+            Ok(output) => Control::Continue(output),
+            Err(error) => Control::Failed(TaskError::new(error)),
+        }
     }
 }
 
 impl<T> Try for Control<T> {
     type Output = T;
-    type Residual = ();
+    type Residual = Control<Infallible>;
     fn from_output(output: T) -> Self {
         Control::Continue(output)
     }
-    fn branch(self) -> ControlFlow<(), T> {
+    fn branch(self) -> ControlFlow<Control<Infallible>, T> {
         match self {
             Control::Continue(output) => ControlFlow::Continue(output),
-            Control::Finished => ControlFlow::Break(()),
+            Control::Finished => ControlFlow::Break(Control::Finished),
+            Control::Failed(error) => ControlFlow::Break(Control::Failed(error)),
+        }
+    }
+}
+
+/// The outcome of the body of a task.
+pub trait Outcome {
+    /// Returns the error which the task failed with, if any.
+    fn into_error(self) -> Option<TaskError>;
+}
+
+impl Outcome for () {
+    fn into_error(self) -> Option<TaskError> {
+        None
+    }
+}
+
+impl<T> Outcome for Control<T> {
+    fn into_error(self) -> Option<TaskError> {
+        match self {
+            Control::Failed(error) => Some(error),
+            _ => None,
         }
     }
 }
@@ -1,5 +1,8 @@
 use kompact::prelude::*;
 use std::marker::PhantomData;
+use std::sync::Weak;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::error::TryRecvError;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::broadcast::Sender;
 
@@ -8,8 +11,9 @@ use crate::data::Sharable;
 
 use crate::prelude::*;
 
+/// The sending end of a channel. The channel closes once all of its pushables are dropped.
 #[derive(Collectable, Finalize, NoTrace, NoSerde, NoDebug)]
-pub struct Pushable<T: Sharable>(Sender<T::T>);
+pub struct Pushable<T: Sharable>(Arc<Sender<T::T>>);
 
 impl<T: Sharable> Clone for Pushable<T> {
     fn clone(&self) -> Self {
@@ -17,12 +21,19 @@ impl<T: Sharable> Clone for Pushable<T> {
     }
 }
 
+/// The receiving end of a channel. It only holds a weak reference to the sender, through which
+/// clones subscribe to the channel, so that it does not keep the channel open.
 #[derive(Collectable, Finalize, NoTrace, NoSerde, NoDebug)]
-pub struct Pullable<T: Sharable>(Sender<T::T>, Receiver<T::T>);
+pub struct Pullable<T: Sharable>(Weak<Sender<T::T>>, Receiver<T::T>);
 
 impl<T: Sharable> Clone for Pullable<T> {
     fn clone(&self) -> Self {
-        Pullable(self.0.clone(), self.0.subscribe())
+        let receiver = match self.0.upgrade() {
+            Some(sender) => sender.subscribe(),
+            // The channel is closed, so the clone is given a receiver which is closed as well.
+            None => tokio::sync::broadcast::channel(1).1,
+        };
+        Pullable(self.0.clone(), receiver)
     }
 }
 
@@ -31,17 +42,23 @@ crate::data::convert_reflexive!({T: Sharable} Pullable<T>);
 
 crate::data::channels::impl_channel!();
 
-/// TODO: Processing will currently only stop if all pullers are dropped.
 pub fn channel<T: Sharable>(_: Context) -> (Pushable<T>, Pullable<T>)
 where
     T::T: Sendable,
 {
     let (l, r) = tokio::sync::broadcast::channel(100);
-    (Pushable(l.clone()), Pullable(l, r))
+    let l = Arc::new(l);
+    (Pushable(l.clone()), Pullable(Arc::downgrade(&l), r))
 }
 
 impl<T: Sharable> Pushable<T> {
     pub async fn push(&self, data: T, ctx: Context) -> Control<()> {
+        self.try_push(data, ctx)
+    }
+
+    /// Pushing into a multicast channel never has to wait, so this can also be called from
+    /// outside of an async context.
+    pub fn try_push(&self, data: T, ctx: Context) -> Control<()> {
         self.0
             .send(data.into_sendable(ctx))
             .map(|_| Control::Continue(()))
@@ -50,11 +67,36 @@ impl<T: Sharable> Pushable<T> {
 }
 
 impl<T: Sharable> Pullable<T> {
+    /// Pulls the next value. Once the task is draining, only values which are already in the
+    /// channel are returned, after which the channel is considered finished.
     pub async fn pull(&mut self, ctx: Context) -> Control<<T::T as DynSendable>::T> {
-        self.1
-            .recv()
+        let state = ctx.state().clone();
+        let received = {
+            let recv = self.1.recv();
+            futures::pin_mut!(recv);
+            // The task is woken up when it starts draining, so the flag is checked on every poll.
+            futures::future::poll_fn(|cx| match state.is_draining() {
+                true => Poll::Ready(None),
+                false => recv.as_mut().poll(cx).map(Some),
+            })
             .await
-            .map(|v| Control::Continue(v.into_sharable(ctx)))
-            .unwrap_or(Control::Finished)
+        };
+        let received = match received {
+            Some(received) => received,
+            None => match self.1.try_recv() {
+                Ok(v) => Ok(v),
+                Err(TryRecvError::Lagged(n)) => Err(RecvError::Lagged(n)),
+                Err(_) => Err(RecvError::Closed),
+            },
+        };
+        match received {
+            Ok(v) => Control::Continue(v.into_sharable(ctx)),
+            // The puller fell behind and missed values, which is not a normal end-of-stream.
+            Err(RecvError::Lagged(n)) => Control::Failed(TaskError::new(format!(
+                "Pullable lagged behind by {} values",
+                n
+            ))),
+            Err(RecvError::Closed) => Control::Finished,
+        }
     }
 }
@@ -14,7 +14,7 @@ pub mod sharable {
 
     impl Alloc<DataFrame> for ConcreteDataFrame {
         fn alloc(self, ctx: Context) -> DataFrame {
-            DataFrame(ctx.mutator().allocate(self, AllocationSpace::New).into())
+            DataFrame(ctx.allocate(self).into())
         }
     }
 }
@@ -1,45 +1,441 @@
+use crate::data::schema::fingerprint;
 use crate::prelude::*;
 
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::fmt::Formatter;
+use std::hash::Hasher;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+
+/// The type of an erased value, which is checked when the value is unerased. Types are compared
+/// by their fingerprints, while their names are only used in errors.
+#[derive(Debug, Clone, Serialize, Deserialize, NoTrace)]
+pub struct ErasedType {
+    fingerprint: u64,
+    name: Cow<'static, str>,
+}
+
+impl PartialEq for ErasedType {
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint
+    }
+}
+
+impl Eq for ErasedType {}
+
+impl Hash for ErasedType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fingerprint.hash(state)
+    }
+}
+
+/// The fingerprints of the types which have been erased or unerased, since describing a type
+/// on every conversion would be expensive.
+static FINGERPRINTS: OnceLock<RwLock<std::collections::HashMap<TypeId, u64>>> = OnceLock::new();
+
+impl ErasedType {
+    pub fn of<T: Schema + ?Sized + 'static>() -> Self {
+        Self {
+            fingerprint: Self::fingerprint::<T>(),
+            name: Cow::Borrowed(std::any::type_name::<T>()),
+        }
+    }
+
+    /// Returns the fingerprint of `T`, which is computed once per type.
+    fn fingerprint<T: Schema + ?Sized + 'static>() -> u64 {
+        let fingerprints = FINGERPRINTS.get_or_init(Default::default);
+        let id = TypeId::of::<T>();
+        if let Some(fingerprint) = fingerprints.read().unwrap().get(&id) {
+            return *fingerprint;
+        }
+        let fingerprint = fingerprint::<T>();
+        fingerprints.write().unwrap().insert(id, fingerprint);
+        fingerprint
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check<T: Schema + ?Sized + 'static>(&self) -> Result<(), UneraseError> {
+        let expected = Self::of::<T>();
+        if self.fingerprint == expected.fingerprint {
+            Ok(())
+        } else {
+            Err(UneraseError {
+                expected: expected.name,
+                found: self.name.clone(),
+            })
+        }
+    }
+}
+
+/// An error from unerasing a value as another type than the one it was erased as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UneraseError {
+    expected: Cow<'static, str>,
+    found: Cow<'static, str>,
+}
+
+impl std::fmt::Display for UneraseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot unerase a value of type `{}` as `{}`",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for UneraseError {}
+
+/// The implementations of `Debug`, `PartialEq`, `Hash` and `Format` for an erased type, if it
+/// has them. Each function takes pointers to values of the erased type.
+#[derive(Clone, Copy, Default, NoTrace)]
+pub struct ErasedVtable {
+    pub debug: Option<unsafe fn(*const (), &mut Formatter<'_>) -> std::fmt::Result>,
+    pub format: Option<unsafe fn(*const (), &mut Formatter<'_>) -> std::fmt::Result>,
+    pub eq: Option<unsafe fn(*const (), *const ()) -> bool>,
+    pub hash: Option<unsafe fn(*const (), &mut dyn Hasher)>,
+    /// Whether the erased type is `Eq`, and not only `PartialEq`.
+    pub total_eq: bool,
+}
+
+/// An error from using an erased value as a key, when its type is not `Eq` and `Hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyError {
+    name: std::string::String,
+}
+
+impl std::fmt::Display for KeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "a value of type `{}` cannot be used as a key, since it is not `Eq` and `Hash`",
+            self.name
+        )
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// Finds the traits which a type implements, for the vtable which `erase!` captures. Methods
+/// are resolved on `&Probe<T>`: the `With*` traits are implemented for `Probe<T>` if `T` has
+/// the trait, and take precedence over the `Without*` fallbacks, which are implemented for
+/// `&Probe<T>`.
+pub mod probe {
+    use super::Formatter;
+    use super::Hasher;
+    use crate::data::format::Format;
+    use std::fmt::Debug;
+    use std::hash::Hash;
+    use std::marker::PhantomData;
+
+    type DebugFn = unsafe fn(*const (), &mut Formatter<'_>) -> std::fmt::Result;
+    type FormatFn = unsafe fn(*const (), &mut Formatter<'_>) -> std::fmt::Result;
+    type EqFn = unsafe fn(*const (), *const ()) -> bool;
+    type HashFn = unsafe fn(*const (), &mut dyn Hasher);
+
+    pub struct Probe<T>(PhantomData<T>);
+
+    impl<T> Probe<T> {
+        #[allow(clippy::new_without_default)]
+        pub fn new() -> Self {
+            Self(PhantomData)
+        }
+    }
+
+    unsafe fn debug<T: Debug>(value: *const (), f: &mut Formatter<'_>) -> std::fmt::Result {
+        (*(value as *const T)).fmt(f)
+    }
+
+    unsafe fn format<T: Format>(value: *const (), f: &mut Formatter<'_>) -> std::fmt::Result {
+        (*(value as *const T)).format(f)
+    }
+
+    unsafe fn eq<T: PartialEq>(a: *const (), b: *const ()) -> bool {
+        *(a as *const T) == *(b as *const T)
+    }
+
+    unsafe fn hash<T: Hash>(value: *const (), mut state: &mut dyn Hasher) {
+        (*(value as *const T)).hash(&mut state)
+    }
+
+    pub trait WithDebug {
+        fn debug_fn(&self) -> Option<DebugFn>;
+    }
+
+    impl<T: Debug> WithDebug for Probe<T> {
+        fn debug_fn(&self) -> Option<DebugFn> {
+            Some(debug::<T>)
+        }
+    }
+
+    pub trait WithoutDebug {
+        fn debug_fn(&self) -> Option<DebugFn>;
+    }
+
+    impl<T> WithoutDebug for &Probe<T> {
+        fn debug_fn(&self) -> Option<DebugFn> {
+            None
+        }
+    }
+
+    pub trait WithFormat {
+        fn format_fn(&self) -> Option<FormatFn>;
+    }
+
+    impl<T: Format> WithFormat for Probe<T> {
+        fn format_fn(&self) -> Option<FormatFn> {
+            Some(format::<T>)
+        }
+    }
+
+    pub trait WithoutFormat {
+        fn format_fn(&self) -> Option<FormatFn>;
+    }
+
+    impl<T> WithoutFormat for &Probe<T> {
+        fn format_fn(&self) -> Option<FormatFn> {
+            None
+        }
+    }
+
+    pub trait WithEq {
+        fn eq_fn(&self) -> Option<EqFn>;
+    }
+
+    impl<T: PartialEq> WithEq for Probe<T> {
+        fn eq_fn(&self) -> Option<EqFn> {
+            Some(eq::<T>)
+        }
+    }
+
+    pub trait WithoutEq {
+        fn eq_fn(&self) -> Option<EqFn>;
+    }
+
+    impl<T> WithoutEq for &Probe<T> {
+        fn eq_fn(&self) -> Option<EqFn> {
+            None
+        }
+    }
+
+    pub trait WithHash {
+        fn hash_fn(&self) -> Option<HashFn>;
+    }
+
+    impl<T: Hash> WithHash for Probe<T> {
+        fn hash_fn(&self) -> Option<HashFn> {
+            Some(hash::<T>)
+        }
+    }
+
+    pub trait WithoutHash {
+        fn hash_fn(&self) -> Option<HashFn>;
+    }
+
+    impl<T> WithoutHash for &Probe<T> {
+        fn hash_fn(&self) -> Option<HashFn> {
+            None
+        }
+    }
+
+    pub trait WithTotalEq {
+        fn is_total_eq(&self) -> bool;
+    }
+
+    impl<T: Eq> WithTotalEq for Probe<T> {
+        fn is_total_eq(&self) -> bool {
+            true
+        }
+    }
+
+    pub trait WithoutTotalEq {
+        fn is_total_eq(&self) -> bool;
+    }
+
+    impl<T> WithoutTotalEq for &Probe<T> {
+        fn is_total_eq(&self) -> bool {
+            false
+        }
+    }
+}
+
 mod sharable {
     use crate::prelude::*;
 
+    use super::ErasedType;
+    use super::ErasedVtable;
+    use super::KeyError;
+
     #[derive(Unpin, Sync, Send, Clone, Collectable, Trace, Finalize)]
     pub struct Erased(pub Gc<ConcreteErased>);
 
     #[derive(Unpin, Sync, Send, Collectable, Trace, Finalize)]
-    pub struct ConcreteErased(pub Box<dyn DynSharable<T = super::sendable::Erased>>);
+    pub struct ConcreteErased(
+        pub Box<dyn DynSharable<T = super::sendable::Erased>>,
+        pub ErasedType,
+        pub ErasedVtable,
+    );
+
+    impl Erased {
+        /// Returns a pointer to the erased value.
+        fn value(&self) -> *const () {
+            &*(self.0).0 as *const dyn DynSharable<T = super::sendable::Erased> as *const ()
+        }
+
+        /// Compares the values, or returns `None` if their type is not `PartialEq`. Values which
+        /// were erased as different types are never equal.
+        pub fn try_eq(&self, other: &Self) -> Option<bool> {
+            if (self.0).1 != (other.0).1 {
+                return Some(false);
+            }
+            // SAFETY: Both values have the type which the vtable was captured for.
+            (self.0)
+                .2
+                .eq
+                .map(|eq| unsafe { eq(self.value(), other.value()) })
+        }
+
+        /// Hashes the type and the value, or returns `None` without hashing anything if the
+        /// type is not `Hash`.
+        pub fn try_hash<H: std::hash::Hasher>(&self, state: &mut H) -> Option<()> {
+            let hash = (self.0).2.hash?;
+            (self.0).1.hash(state);
+            // SAFETY: The vtable was captured for the type of the value.
+            unsafe { hash(self.value(), state) };
+            Some(())
+        }
+
+        /// Turns the value into a key, or fails if its type is not `Eq` and `Hash`.
+        pub fn into_key(self) -> Result<super::ErasedKey, KeyError> {
+            let vtable = &(self.0).2;
+            if vtable.total_eq && vtable.eq.is_some() && vtable.hash.is_some() {
+                Ok(super::ErasedKey(self))
+            } else {
+                Err(KeyError {
+                    name: (self.0).1.name().to_string(),
+                })
+            }
+        }
+    }
 
     impl Debug for Erased {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            f.debug_tuple("<erased>").finish()
+            match (self.0).2.debug {
+                // SAFETY: The vtable was captured for the type of the value.
+                Some(debug) => unsafe { debug(self.value(), f) },
+                None => write!(f, "<erased {}>", (self.0).1.name()),
+            }
+        }
+    }
+
+    impl Format for Erased {
+        fn format(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match (self.0).2.format {
+                // SAFETY: The vtable was captured for the type of the value.
+                Some(format) => unsafe { format(self.value(), f) },
+                None => write!(f, "<erased {}>", (self.0).1.name()),
+            }
+        }
+    }
+
+    /// Values are equal if they were erased as the same type and are equal as that type. Values
+    /// whose type is not `PartialEq` are not even equal to themselves. Erased values are not
+    /// `Eq`, since their type might not be, but they can be checked through `into_key`.
+    impl PartialEq for Erased {
+        fn eq(&self, other: &Self) -> bool {
+            self.try_eq(other).unwrap_or(false)
+        }
+    }
+
+    /// Values whose type is not `Hash` are hashed by their type only.
+    impl Hash for Erased {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            if self.try_hash(state).is_none() {
+                (self.0).1.hash(state);
+            }
         }
     }
 
     impl Alloc<Erased> for ConcreteErased {
         fn alloc(self, ctx: Context) -> Erased {
-            Erased(ctx.mutator().allocate(self, AllocationSpace::New).into())
+            Erased(ctx.allocate(self).into())
         }
     }
 }
 
 pub use sharable::Erased;
 
+/// An erased value whose type is `Eq` and `Hash`, so that it can be used as a key of maps, sets
+/// and keyed channels. Values are checked when they are turned into keys by
+/// [`Erased::into_key`], rather than when they are compared.
+#[derive(Unpin, Sync, Send, Clone, Collectable, Trace, Finalize)]
+pub struct ErasedKey(Erased);
+
+impl ErasedKey {
+    pub fn get(&self) -> &Erased {
+        &self.0
+    }
+
+    pub fn into_erased(self) -> Erased {
+        self.0
+    }
+}
+
+impl Debug for ErasedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl PartialEq for ErasedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+/// Keys are only created for values whose type is `Eq`.
+impl Eq for ErasedKey {}
+
+impl Hash for ErasedKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
 pub mod sendable {
     use crate::prelude::*;
 
+    use super::ErasedType;
+
+    /// The sendable form of an erased value, which formats as the value.
+    pub trait DynErased: DynSendable<T = super::sharable::Erased> + Debug {}
+
+    impl<T: DynSendable<T = super::sharable::Erased> + Debug> DynErased for T {}
+
+    dyn_clone::clone_trait_object!(DynErased);
+
     #[derive(Clone, From, Serialize, Deserialize)]
     pub struct Erased(pub ConcreteErased);
 
     #[derive(Clone, Serialize, Deserialize)]
     pub struct ConcreteErased(
-        #[serde(with = "serde_traitobject")] pub Box<dyn DynSendable<T = super::sharable::Erased>>,
+        #[serde(with = "serde_traitobject")] pub Box<dyn DynErased>,
+        pub ErasedType,
     );
 
     impl Debug for Erased {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            f.debug_tuple("<erased>").finish()
+            (self.0).0.fmt(f)
         }
     }
+
+    /// The sendable form of a key, which is checked again when it is turned back into a key.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct ErasedKey(pub Erased);
 }
 
 /// NOTE: The object inside must be convertible to `sendable::Erased`.
@@ -57,27 +453,87 @@ impl DynSendable for sendable::Erased {
     }
 }
 
+impl DynSharable for ErasedKey {
+    type T = sendable::ErasedKey;
+    fn into_sendable(&self, ctx: Context) -> Self::T {
+        sendable::ErasedKey(self.0.into_sendable(ctx))
+    }
+}
+
+impl DynSendable for sendable::ErasedKey {
+    type T = ErasedKey;
+    fn into_sharable(&self, ctx: Context) -> Self::T {
+        // The value was checked when it became a key, and keeps its type when it is sent.
+        self.0.into_sharable(ctx).into_key().unwrap()
+    }
+}
+
+/// Both forms of `Erased` hold a wrapper generated by `erase!`, which is `#[repr(transparent)]`
+/// over the erased type `ty`. Unerasing checks `ty` before casting the wrapper to the type.
 impl sharable::Erased {
-    pub fn erase<T: DynSharable<T = sendable::Erased> + 'static>(x: T, ctx: Context) -> Self {
-        sharable::ConcreteErased(Box::new(x) as Box<dyn DynSharable<T = sendable::Erased>>)
-            .alloc(ctx)
+    pub fn erase<T: DynSharable<T = sendable::Erased> + 'static>(
+        x: T,
+        ty: ErasedType,
+        vtable: ErasedVtable,
+        ctx: Context,
+    ) -> Self {
+        let x = Box::new(x) as Box<dyn DynSharable<T = sendable::Erased>>;
+        sharable::ConcreteErased(x, ty, vtable).alloc(ctx)
     }
 
-    pub fn unerase<T: Sharable + 'static>(self, ctx: Context) -> T {
+    /// Returns the name of the type which the value was erased as.
+    pub fn type_name(&self) -> &str {
+        (self.0).1.name()
+    }
+
+    /// Unerases the value, or fails if it was erased as another type than `T`.
+    pub fn unerase<T: Sharable + Schema + 'static>(self, ctx: Context) -> Result<T, UneraseError> {
+        (self.0).1.check::<T>()?;
         let raw = Box::into_raw((self.0).0.clone());
-        let raw = raw as *const dyn DynSharable<T = sendable::Erased> as *const T as *mut T;
-        unsafe { *Box::from_raw(raw) }
+        let raw = raw as *mut T;
+        // SAFETY: The wrapper is transparent over `T`, which was checked above.
+        Ok(unsafe { *Box::from_raw(raw) })
+    }
+
+    /// Unerases the value if it was erased as `T`.
+    pub fn try_unerase<T: Sharable + Schema + 'static>(self, ctx: Context) -> Option<T> {
+        self.unerase(ctx).ok()
     }
 }
 
 impl sendable::Erased {
-    pub fn erase<T: DynSendable<T = sharable::Erased> + 'static>(x: T, ctx: Context) -> Self {
-        sendable::ConcreteErased(Box::new(x) as Box<dyn DynSendable<T = sharable::Erased>>).into()
+    pub fn erase<T: DynSendable<T = sharable::Erased> + Debug + 'static>(
+        x: T,
+        ty: ErasedType,
+        ctx: Context,
+    ) -> Self {
+        let x = Box::new(x) as Box<dyn sendable::DynErased>;
+        sendable::ConcreteErased(x, ty).into()
+    }
+
+    /// Returns the name of the type which the value was erased as.
+    pub fn type_name(&self) -> &str {
+        (self.0).1.name()
     }
 
-    pub fn unerase<T: Sendable + 'static>(self, ctx: Context) -> T {
+    /// Unerases the value, or fails if it was erased as another type than `T`. Both forms of
+    /// a value are erased as the sharable form, which is the one that is checked.
+    pub fn unerase<T: Sendable + 'static>(self, ctx: Context) -> Result<T, UneraseError>
+    where
+        T::T: Schema + 'static,
+    {
+        (self.0).1.check::<T::T>()?;
         let raw = Box::into_raw((self.0).0);
-        let raw = raw as *const dyn DynSendable<T = sendable::Erased> as *const T as *mut T;
-        unsafe { *Box::from_raw(raw) }
+        let raw = raw as *mut T;
+        // SAFETY: The wrapper is transparent over `T`, which was checked above.
+        Ok(unsafe { *Box::from_raw(raw) })
+    }
+
+    /// Unerases the value if it was erased as `T`.
+    pub fn try_unerase<T: Sendable + 'static>(self, ctx: Context) -> Option<T>
+    where
+        T::T: Schema + 'static,
+    {
+        self.unerase(ctx).ok()
     }
 }
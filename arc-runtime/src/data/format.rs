@@ -0,0 +1,121 @@
+//! Formatting of runtime values into strings, for logging and string sinks.
+
+use crate::prelude::*;
+
+use std::fmt::Formatter;
+use std::fmt::Write;
+
+/// Formats a value in the syntax of arc-lang. Unlike `Debug`, this looks through the `Gc`
+/// pointers of rewritten structs and enums, which are formatted as records and variants.
+pub trait Format {
+    fn format(&self, f: &mut Formatter<'_>) -> std::fmt::Result;
+
+    /// Formats the value when it is nested inside another value, where strings are quoted.
+    fn format_field(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.format(f)
+    }
+}
+
+/// Adapts a `Format` value to `Display`, and to `Debug` as a nested value.
+pub struct Formatted<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Format + ?Sized> std::fmt::Display for Formatted<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.format(f)
+    }
+}
+
+impl<T: Format + ?Sized> std::fmt::Debug for Formatted<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.format_field(f)
+    }
+}
+
+/// Formats a value into a string.
+pub fn to_string<T: Format>(x: T, ctx: Context) -> String {
+    String::from_str(&Formatted(&x).to_string(), ctx)
+}
+
+macro_rules! format_display {
+    { $($ty:ty),* } => {
+        $(
+            impl Format for $ty {
+                fn format(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                    std::fmt::Display::fmt(self, f)
+                }
+            }
+        )*
+    }
+}
+
+format_display!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64, bool);
+
+impl Format for char {
+    fn format(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_char(*self)
+    }
+
+    fn format_field(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl Format for unit {
+    fn format(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("unit")
+    }
+}
+
+impl Format for String {
+    fn format(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+
+    fn format_field(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.0.as_str(), f)
+    }
+}
+
+impl<T: Sharable + Format> Format for Vec<T> {
+    fn format(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(Formatted))
+            .finish()
+    }
+}
+
+/// Formats the fields of rewritten structs and enums, which fall back to `Debug` if they do not
+/// implement `Format`. Methods are resolved on `&Field<T>`, like the probes of erased types.
+pub mod field {
+    use super::Format;
+    use super::Formatted;
+    use std::fmt::Debug;
+
+    pub struct Field<'a, T>(pub Formatted<'a, T>);
+
+    impl<'a, T> Field<'a, T> {
+        pub fn new(value: &'a T) -> Self {
+            Self(Formatted(value))
+        }
+    }
+
+    pub trait WithFormat {
+        fn as_debug(&self) -> &dyn Debug;
+    }
+
+    impl<T: Format> WithFormat for Field<'_, T> {
+        fn as_debug(&self) -> &dyn Debug {
+            &self.0
+        }
+    }
+
+    pub trait WithoutFormat {
+        fn as_debug(&self) -> &dyn Debug;
+    }
+
+    impl<T: Debug> WithoutFormat for &Field<'_, T> {
+        fn as_debug(&self) -> &dyn Debug {
+            (self.0).0
+        }
+    }
+}
@@ -1,11 +1,129 @@
 use crate::context::Context;
+use crate::data::schema::fingerprint;
+use crate::data::schema::fnv1a;
+use crate::data::schema::Schema;
 use crate::data::DynSharable;
 use comet::api::Collectable;
 use comet::api::Finalize;
 use comet::api::Trace;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::any::TypeId;
 use std::fmt::Debug;
 use std::ptr::NonNull;
 
+/// Identifies a function by its name and signature, independently of the binary which it is
+/// compiled into. Functions are shipped between processes as keys, and turned back into
+/// pointers by the registry which `declare_functions!` generates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FunctionKey {
+    name: u64,
+    input: u64,
+    output: u64,
+}
+
+impl FunctionKey {
+    pub fn new<I: Schema, O: Schema>(name: &str) -> Self {
+        Self {
+            name: fnv1a(name.as_bytes()),
+            input: fingerprint::<I>(),
+            output: fingerprint::<O>(),
+        }
+    }
+
+    /// Resolves the key to `f` if `f` is registered under the name of the key. Fails if `f`
+    /// has another signature than the key, or than the requested signature `(I) -> O`.
+    pub fn resolve<I: 'static, O: 'static, J: Schema + 'static, P: Schema + 'static>(
+        self,
+        name: &'static str,
+        f: fn(J, Context) -> P,
+    ) -> Option<Result<fn(I, Context) -> O, FunctionError>> {
+        if self.name != fnv1a(name.as_bytes()) {
+            return None;
+        }
+        if self != Self::new::<J, P>(name) || TypeId::of::<(I, O)>() != TypeId::of::<(J, P)>() {
+            return Some(Err(FunctionError::Mismatch {
+                name,
+                signature: std::any::type_name::<fn(J) -> P>(),
+            }));
+        }
+        // SAFETY: `I` and `O` are the same types as `J` and `P`.
+        Some(Ok(unsafe {
+            *(&f as *const fn(J, Context) -> P as *const fn(I, Context) -> O)
+        }))
+    }
+}
+
+/// An error from resolving a [`FunctionKey`], for example because it was created by a different
+/// version of the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionError {
+    /// No function is registered under the name of the key.
+    Unknown(FunctionKey),
+    /// The function which is registered under the name of the key has another signature.
+    Mismatch {
+        name: &'static str,
+        signature: &'static str,
+    },
+}
+
+impl std::fmt::Display for FunctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FunctionError::Unknown(key) => write!(f, "no function is registered as {:?}", key),
+            FunctionError::Mismatch { name, signature } => write!(
+                f,
+                "function `{}` has signature `{}`, which differs from the requested one",
+                name, signature
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FunctionError {}
+
+/// Appends a value to the end of a tuple, such as the environment of a closure to its arguments.
+pub trait Append<T> {
+    type Output;
+    fn append(self, value: T) -> Self::Output;
+}
+
+macro_rules! append {
+    ($($arg:ident),*) => {
+        impl<$($arg,)* T> Append<T> for ($($arg,)*) {
+            type Output = ($($arg,)* T,);
+            #[allow(non_snake_case)]
+            fn append(self, value: T) -> Self::Output {
+                let ($($arg,)*) = self;
+                ($($arg,)* value,)
+            }
+        }
+    };
+}
+
+append!();
+append!(A0);
+append!(A0, A1);
+append!(A0, A1, A2);
+append!(A0, A1, A2, A3);
+append!(A0, A1, A2, A3, A4);
+append!(A0, A1, A2, A3, A4, A5);
+append!(A0, A1, A2, A3, A4, A5, A6);
+append!(A0, A1, A2, A3, A4, A5, A6, A7);
+
+/// Calls a function value with a tuple of arguments. This lets the runtime library call the
+/// `Function` and `closures::Closure` types, which are generated by `declare_functions!` in each
+/// program.
+pub trait Callable<I, O> {
+    fn call(&self, input: I, ctx: Context) -> O;
+}
+
+impl<I, O> Callable<I, O> for fn(I, Context) -> O {
+    fn call(&self, input: I, ctx: Context) -> O {
+        self(input, ctx)
+    }
+}
+
 #[macro_export]
 macro_rules! declare_functions {
     ($($id:ident),*) => {
@@ -14,12 +132,39 @@ macro_rules! declare_functions {
             pub ptr: fn(I, Context) -> O,
             pub tag: FunctionTag<I, O>,
         }
-        #[derive(Debug, Copy, Send, Sync, Unpin, Serialize, Deserialize)]
-        pub struct FunctionTag<I, O>(Tag, std::marker::PhantomData<(I, O)>);
-        #[derive(Debug, Clone, Copy, Send, Serialize, Deserialize)]
-        #[allow(non_camel_case_types)]
-        pub enum Tag {
-            $($id,)*
+        #[derive(Debug, Copy, Send, Sync, Unpin)]
+        pub struct FunctionTag<I, O>(FunctionKey, std::marker::PhantomData<(I, O)>);
+        impl<I: Schema + 'static, O: Schema + 'static> Function<I, O> {
+            pub fn new(name: &'static str, ptr: fn(I, Context) -> O) -> Self {
+                Self { ptr, tag: FunctionTag(FunctionKey::new::<I, O>(name), std::marker::PhantomData) }
+            }
+        }
+        impl<I: 'static, O: 'static> Function<I, O> {
+            pub fn call(&self, input: I, ctx: Context) -> O {
+                (self.ptr)(input, ctx)
+            }
+        }
+        impl<I: 'static, O: 'static> FunctionTag<I, O> {
+            /// Looks up the declared function with the name and signature of the tag.
+            pub fn resolve(&self) -> Result<fn(I, Context) -> O, FunctionError> {
+                $(if let Some(ptr) = self.0.resolve(stringify!($id), $id) {
+                    return ptr;
+                })*
+                Err(FunctionError::Unknown(self.0))
+            }
+        }
+        impl<I: 'static, O: 'static> Callable<I, O> for Function<I, O> {
+            fn call(&self, input: I, ctx: Context) -> O {
+                (self.ptr)(input, ctx)
+            }
+        }
+        impl<I: Schema, O: Schema> Schema for Function<I, O> {
+            fn describe(schema: &mut SchemaWriter) {
+                schema.compound::<Self>("Function", |schema| {
+                    schema.param::<I>();
+                    schema.param::<O>();
+                });
+            }
         }
         impl<I, O> Clone for Function<I, O> {
             fn clone(&self) -> Self {
@@ -45,30 +190,120 @@ macro_rules! declare_functions {
         impl<I: 'static, O: 'static> DynSendable for FunctionTag<I, O> {
             type T = Function<I, O>;
             fn into_sharable(&self, ctx: Context) -> Self::T {
-                unsafe {
-                    match self.0 {
-                        $(Tag::$id => Function {
-                            ptr: std::mem::transmute($id as usize),
-                            tag: self.clone()
-                        }),*
-                    }
-                }
+                // Tags are only created for declared functions, and deserialised tags are resolved.
+                Function { ptr: self.resolve().unwrap(), tag: self.clone() }
+            }
+        }
+        impl<I, O> Serialize for FunctionTag<I, O> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.serialize(serializer)
+            }
+        }
+        impl<'i, I: 'static, O: 'static> Deserialize<'i> for FunctionTag<I, O> {
+            fn deserialize<D: Deserializer<'i>>(deserializer: D) -> Result<Self, D::Error> {
+                let tag = FunctionTag(FunctionKey::deserialize(deserializer)?, std::marker::PhantomData);
+                tag.resolve().map_err(<D::Error as DeserializeError>::custom)?;
+                Ok(tag)
             }
         }
         impl<I, O> Serialize for Function<I, O> {
             fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-                self.tag.0.serialize(serializer)
+                self.tag.serialize(serializer)
             }
         }
-        impl<'i, I, O> Deserialize<'i> for Function<I, O> {
+        impl<'i, I: 'static, O: 'static> Deserialize<'i> for Function<I, O> {
             fn deserialize<D: Deserializer<'i>>(deserializer: D) -> Result<Self, D::Error> {
-                unsafe {
-                    match Tag::deserialize(deserializer)? {
-                        $(Tag::$id => Ok(Function {
-                            ptr: std::mem::transmute($id as usize),
-                            tag: FunctionTag(Tag::$id, std::marker::PhantomData)
-                        }),)*
-                    }
+                let tag = FunctionTag::<I, O>::deserialize(deserializer)?;
+                let ptr = tag.resolve().map_err(<D::Error as DeserializeError>::custom)?;
+                Ok(Function { ptr, tag })
+            }
+        }
+        /// Closures live in their own module, so that they do not clash with types of the
+        /// program which are named `Closure`.
+        pub mod closures {
+            use super::*;
+            /// A function value together with the environment which it captured. The environment
+            /// is passed to the function after the arguments of each call.
+            #[derive(Send, Sync, Unpin, Collectable, Finalize, Trace)]
+            pub struct Closure<I: 'static, E: Sharable + 'static, O: 'static> {
+                pub fun: Function<I, O>,
+                pub env: E,
+            }
+            /// The sendable form of a closure.
+            #[derive(Send, Sync, Unpin)]
+            pub struct ClosureTag<I: 'static, E: Sharable + 'static, O: 'static> {
+                pub tag: FunctionTag<I, O>,
+                pub env: E::T,
+            }
+            impl<I: 'static, E: Sharable + 'static, O: 'static> Closure<I, E, O> {
+                pub fn new(fun: Function<I, O>, env: E) -> Self {
+                    Self { fun, env }
+                }
+                pub fn call<A: Append<E, Output = I>>(&self, input: A, ctx: Context) -> O {
+                    self.fun.call(input.append(self.env.clone()), ctx)
+                }
+            }
+            impl<A: Append<E, Output = I>, I, E: Sharable, O> Callable<A, O> for Closure<I, E, O> {
+                fn call(&self, input: A, ctx: Context) -> O {
+                    self.fun.call(input.append(self.env.clone()), ctx)
+                }
+            }
+            impl<I: Schema, E: Sharable + Schema, O: Schema> Schema for Closure<I, E, O> {
+                fn describe(schema: &mut SchemaWriter) {
+                    schema.compound::<Self>("Closure", |schema| {
+                        schema.param::<I>();
+                        schema.param::<E>();
+                        schema.param::<O>();
+                    });
+                }
+            }
+            impl<I, E: Sharable, O> Clone for Closure<I, E, O> {
+                fn clone(&self) -> Self {
+                    Self { fun: self.fun.clone(), env: self.env.clone() }
+                }
+            }
+            impl<I, E: Sharable, O> Clone for ClosureTag<I, E, O> {
+                fn clone(&self) -> Self {
+                    Self { tag: self.tag.clone(), env: self.env.clone() }
+                }
+            }
+            impl<I, E: Sharable, O> std::fmt::Debug for Closure<I, E, O> {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.debug_struct("Closure").field("env", &self.env).finish()
+                }
+            }
+            impl<I, E: Sharable, O> std::fmt::Debug for ClosureTag<I, E, O> {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.debug_struct("ClosureTag").field("tag", &self.tag.0).finish()
+                }
+            }
+            impl<I, E: Sharable, O> DynSharable for Closure<I, E, O>
+            where
+                E::T: Sendable<T = E>,
+            {
+                type T = ClosureTag<I, E, O>;
+                fn into_sendable(&self, ctx: Context) -> Self::T {
+                    ClosureTag { tag: self.fun.tag.clone(), env: self.env.into_sendable(ctx) }
+                }
+            }
+            impl<I: 'static, E: Sharable, O: 'static> DynSendable for ClosureTag<I, E, O>
+            where
+                E::T: Sendable<T = E>,
+            {
+                type T = Closure<I, E, O>;
+                fn into_sharable(&self, ctx: Context) -> Self::T {
+                    Closure { fun: self.tag.into_sharable(ctx), env: self.env.into_sharable(ctx) }
+                }
+            }
+            impl<I, E: Sharable, O> Serialize for ClosureTag<I, E, O> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    (&self.tag, &self.env).serialize(serializer)
+                }
+            }
+            impl<'i, I: 'static, E: Sharable, O: 'static> Deserialize<'i> for ClosureTag<I, E, O> {
+                fn deserialize<D: Deserializer<'i>>(deserializer: D) -> Result<Self, D::Error> {
+                    let (tag, env) = <(FunctionTag<I, O>, E::T)>::deserialize(deserializer)?;
+                    Ok(ClosureTag { tag, env })
                 }
             }
         }
@@ -93,13 +328,23 @@ macro_rules! function {
 macro_rules! function {
     // Create a function value
     ($fun:ident) => {
-        Function {
-            ptr: $fun,
-            tag: FunctionTag(Tag::$fun, std::marker::PhantomData),
-        }
+        Function::new(stringify!($fun), $fun)
     };
     // Create a function type
     (($($input:ty),* $(,)?) -> $output:ty) => {
         Function<($($input,)*), $output>
     };
 }
+
+#[cfg(not(feature = "legacy"))]
+#[macro_export]
+macro_rules! closure {
+    // Create a closure value
+    ($fun:ident, $env:expr) => {
+        closures::Closure::new(function!($fun), $env)
+    };
+    // Create a closure type, whose environment has type `$env`
+    (|$env:ty| ($($input:ty),* $(,)?) -> $output:ty) => {
+        closures::Closure<($($input,)* $env,), $env, $output>
+    };
+}
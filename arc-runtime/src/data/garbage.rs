@@ -22,6 +22,41 @@ impl<T: Garbage> Clone for Gc<T> {
     }
 }
 
+impl<T: Garbage + 'static> Gc<T> {
+    /// Notifies the collector that the value behind the pointer has been mutated. This must be
+    /// done after writing a field of a value which has already been allocated.
+    pub fn write_barrier(&self, ctx: Context) {
+        ctx.mutator().write_barrier(self.0.to_dyn())
+    }
+}
+
+/// Values behind a `Gc` are compared and hashed structurally, like values behind a `Box`.
+impl<T: Garbage + PartialEq> PartialEq for Gc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Garbage + Eq> Eq for Gc<T> {}
+
+impl<T: Garbage + PartialOrd> PartialOrd for Gc<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Garbage + Ord> Ord for Gc<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Garbage + Hash> Hash for Gc<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
 pub trait Alloc<T> {
     fn alloc(self, ctx: Context) -> T;
 }
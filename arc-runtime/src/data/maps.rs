@@ -0,0 +1,261 @@
+use crate::prelude::*;
+
+use std::cell::UnsafeCell;
+
+pub mod sharable {
+    use crate::prelude::*;
+
+    use super::Key;
+
+    #[derive(
+        Clone, From, Deref, DerefMut, Debug, Collectable, Finalize, Send, Sync, Unpin, Trace,
+    )]
+    #[from(forward)]
+    pub struct HashMap<K: Sharable, V: Sharable>(pub Gc<ConcreteHashMap<K, V>>);
+
+    /// The table of a map. The table itself is allocated by `std` rather than on the heap of the
+    /// task, and is freed by the finalizer of the map once the map is collected.
+    #[derive(Debug, Collectable, Finalize, Send, Sync, Unpin)]
+    pub struct ConcreteHashMap<K: Sharable, V: Sharable>(pub std::collections::HashMap<Key<K>, V>);
+
+    unsafe impl<K: Sharable, V: Sharable> Trace for ConcreteHashMap<K, V> {
+        fn trace(&mut self, vis: &mut dyn Visitor) {
+            for (k, v) in self.0.iter_mut() {
+                k.trace(vis);
+                v.trace(vis);
+            }
+        }
+    }
+
+    impl<K: Sharable, V: Sharable> Alloc<HashMap<K, V>> for ConcreteHashMap<K, V> {
+        fn alloc(self, ctx: Context) -> HashMap<K, V> {
+            HashMap(ctx.allocate(self).into())
+        }
+    }
+}
+
+mod sendable {
+    use crate::prelude::*;
+
+    #[derive(Clone, From, Send, Serialize, Deserialize)]
+    #[serde(bound = "")]
+    #[from(forward)]
+    pub struct HashMap<K: Sendable + Eq + Hash, V: Sendable>(pub ConcreteHashMap<K, V>);
+
+    pub type ConcreteHashMap<K, V> = std::collections::HashMap<K, V>;
+}
+
+/// A key of a map or set on the heap. Keys are immutable while they are in a table, but they may
+/// contain pointers which must be traced, so they are kept in a cell.
+pub struct Key<K>(UnsafeCell<K>);
+
+impl<K> Key<K> {
+    pub fn new(key: K) -> Self {
+        Self(UnsafeCell::new(key))
+    }
+
+    pub fn get(&self) -> &K {
+        // SAFETY: The key is only mutated by `trace`, which has exclusive access to the table.
+        unsafe { &*self.0.get() }
+    }
+
+    pub fn into_inner(self) -> K {
+        self.0.into_inner()
+    }
+
+    /// Traces the key. Tracing only marks the values which the key points to, so it does not
+    /// change the hash of the key.
+    pub fn trace(&self, vis: &mut dyn Visitor)
+    where
+        K: Trace,
+    {
+        // SAFETY: See `get`.
+        unsafe { (*self.0.get()).trace(vis) }
+    }
+}
+
+impl<K: PartialEq> PartialEq for Key<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<K: Eq> Eq for Key<K> {}
+
+impl<K: Hash> Hash for Key<K> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.get().hash(state)
+    }
+}
+
+impl<K: Clone> Clone for Key<K> {
+    fn clone(&self) -> Self {
+        Self::new(self.get().clone())
+    }
+}
+
+impl<K: std::fmt::Debug> std::fmt::Debug for Key<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+impl<K, V> DynSharable for sharable::HashMap<K, V>
+where
+    K: Sharable + Eq + Hash,
+    V: Sharable,
+    K::T: DynSendable<T = K> + Eq + Hash,
+{
+    type T = sendable::HashMap<K::T, V::T>;
+    fn into_sendable(&self, ctx: Context) -> Self::T {
+        self.table()
+            .iter()
+            .map(|(k, v)| (k.get().into_sendable(ctx), v.into_sendable(ctx)))
+            .collect::<std::collections::HashMap<_, _>>()
+            .into()
+    }
+}
+
+impl<K, V> DynSendable for sendable::HashMap<K, V>
+where
+    K: Sendable + Eq + Hash,
+    V: Sendable,
+    K::T: DynSharable<T = K> + Eq + Hash,
+{
+    type T = sharable::HashMap<K::T, V::T>;
+    fn into_sharable(&self, ctx: Context) -> Self::T {
+        let mut s = HashMap::with_capacity(self.0.len(), ctx);
+        for (k, v) in self.0.iter() {
+            let k = k.into_sharable(ctx);
+            let v = v.into_sharable(ctx);
+            s.insert(k, v, ctx);
+        }
+        s
+    }
+}
+
+pub use sharable::HashMap;
+
+impl<K: Sharable + Eq + Hash, V: Sharable> HashMap<K, V> {
+    pub fn new(ctx: Context) -> Self {
+        Self::with_capacity(0, ctx)
+    }
+
+    pub fn with_capacity(capacity: usize, ctx: Context) -> Self {
+        let map = std::collections::HashMap::with_capacity(capacity);
+        sharable::ConcreteHashMap(map).alloc(ctx)
+    }
+
+    fn table(&self) -> &std::collections::HashMap<Key<K>, V> {
+        &(self.0).0
+    }
+
+    /// Inserts a value under a key, and returns the value which it replaced.
+    pub fn insert(&mut self, key: K, value: V, ctx: Context) -> Option<V> {
+        let old = (self.0).0.insert(Key::new(key), value);
+        self.0.write_barrier(ctx);
+        old
+    }
+
+    pub fn get(&self, key: K, ctx: Context) -> Option<V> {
+        self.table().get(&Key::new(key)).cloned()
+    }
+
+    pub fn contains_key(&self, key: K, ctx: Context) -> bool {
+        self.table().contains_key(&Key::new(key))
+    }
+
+    pub fn remove(&mut self, key: K, ctx: Context) -> Option<V> {
+        (self.0).0.remove(&Key::new(key))
+    }
+
+    pub fn len(&self, ctx: Context) -> usize {
+        self.table().len()
+    }
+
+    pub fn is_empty(&self, ctx: Context) -> bool {
+        self.table().is_empty()
+    }
+
+    pub fn clear(&mut self, ctx: Context) {
+        (self.0).0.clear();
+    }
+
+    /// Returns the keys of the map, in an unspecified order.
+    pub fn keys(&self, ctx: Context) -> Vec<K> {
+        let stack = ctx.mutator().shadow_stack();
+        letroot!(keys = stack, Some(Vec::with_capacity(self.len(ctx), ctx)));
+        for k in self.table().keys() {
+            keys.as_mut().unwrap().push(k.get().clone(), ctx);
+        }
+        keys.take().unwrap()
+    }
+
+    /// Returns the values of the map, in the same order as the keys.
+    pub fn values(&self, ctx: Context) -> Vec<V> {
+        let stack = ctx.mutator().shadow_stack();
+        letroot!(values = stack, Some(Vec::with_capacity(self.len(ctx), ctx)));
+        for v in self.table().values() {
+            values.as_mut().unwrap().push(v.clone(), ctx);
+        }
+        values.take().unwrap()
+    }
+}
+
+// Functions which are declared as externs in the standard library.
+
+pub fn new_hash_map<K: Sharable + Eq + Hash, V: Sharable>(ctx: Context) -> HashMap<K, V> {
+    HashMap::new(ctx)
+}
+
+pub fn insert_hash_map<K: Sharable + Eq + Hash, V: Sharable>(
+    mut m: HashMap<K, V>,
+    k: K,
+    v: V,
+    ctx: Context,
+) {
+    m.insert(k, v, ctx);
+}
+
+/// Returns the value of a key, or `None` if the key is not in the map.
+pub fn get_hash_map<K: Sharable + Eq + Hash, V: Sharable>(
+    m: HashMap<K, V>,
+    k: K,
+    ctx: Context,
+) -> Option<V> {
+    m.get(k, ctx)
+}
+
+pub fn contains_hash_map<K: Sharable + Eq + Hash, V: Sharable>(
+    m: HashMap<K, V>,
+    k: K,
+    ctx: Context,
+) -> bool {
+    m.contains_key(k, ctx)
+}
+
+pub fn remove_hash_map<K: Sharable + Eq + Hash, V: Sharable>(
+    mut m: HashMap<K, V>,
+    k: K,
+    ctx: Context,
+) {
+    m.remove(k, ctx);
+}
+
+pub fn len_hash_map<K: Sharable + Eq + Hash, V: Sharable>(m: HashMap<K, V>, ctx: Context) -> i32 {
+    m.len(ctx) as i32
+}
+
+pub fn keys_hash_map<K: Sharable + Eq + Hash, V: Sharable>(
+    m: HashMap<K, V>,
+    ctx: Context,
+) -> Vec<K> {
+    m.keys(ctx)
+}
+
+pub fn values_hash_map<K: Sharable + Eq + Hash, V: Sharable>(
+    m: HashMap<K, V>,
+    ctx: Context,
+) -> Vec<V> {
+    m.values(ctx)
+}
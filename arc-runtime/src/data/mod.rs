@@ -1,7 +1,12 @@
 pub mod erased;
+pub mod format;
 pub mod functions;
 pub mod garbage;
+pub mod maps;
 pub mod primitives;
+pub mod regex;
+pub mod schema;
+pub mod sets;
 pub mod strings;
 pub mod vectors;
 #[cfg(feature = "dataframes")]
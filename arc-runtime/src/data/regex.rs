@@ -0,0 +1,132 @@
+use crate::prelude::*;
+
+pub mod sharable {
+    use crate::prelude::*;
+
+    #[derive(
+        Clone, From, Deref, DerefMut, Debug, Collectable, Finalize, Send, Sync, Unpin, NoTrace,
+    )]
+    #[from(forward)]
+    pub struct Regex(pub Gc<ConcreteRegex>);
+
+    #[derive(Debug, Collectable, NoTrace, Finalize, Send, Sync, Unpin)]
+    pub struct ConcreteRegex(pub regex::Regex);
+
+    impl Alloc<Regex> for ConcreteRegex {
+        fn alloc(self, ctx: Context) -> Regex {
+            Regex(ctx.allocate(self).into())
+        }
+    }
+}
+
+/// Regexes are sent as their pattern, which is compiled again when they are received.
+mod sendable {
+    use crate::prelude::*;
+
+    #[derive(Clone, From, Send, Serialize, Deserialize)]
+    #[from(forward)]
+    pub struct Regex(pub ConcreteRegex);
+
+    pub type ConcreteRegex = Box<str>;
+}
+
+impl DynSharable for sharable::Regex {
+    type T = sendable::Regex;
+    fn into_sendable(&self, ctx: Context) -> Self::T {
+        self.inner().as_str().into()
+    }
+}
+
+impl DynSendable for sendable::Regex {
+    type T = sharable::Regex;
+    fn into_sharable(&self, ctx: Context) -> Self::T {
+        let regex = regex::Regex::new(&self.0).expect("Received an invalid regex");
+        sharable::ConcreteRegex(regex).alloc(ctx)
+    }
+}
+
+pub use sharable::Regex;
+
+impl Format for Regex {
+    fn format(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.inner().as_str())
+    }
+}
+
+impl Regex {
+    fn inner(&self) -> &regex::Regex {
+        &(self.0).0
+    }
+
+    /// Compiles a regex, or panics if the pattern is invalid.
+    pub fn new(pattern: String, ctx: Context) -> Regex {
+        Regex::try_new(pattern, ctx).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_new(pattern: String, ctx: Context) -> Result<Regex, regex::Error> {
+        let regex = regex::Regex::new(pattern.0.as_str())?;
+        Ok(sharable::ConcreteRegex(regex).alloc(ctx))
+    }
+
+    pub fn pattern(self, ctx: Context) -> String {
+        String::from_str(self.inner().as_str(), ctx)
+    }
+
+    pub fn is_match(self, text: String, _: Context) -> bool {
+        self.inner().is_match(text.0.as_str())
+    }
+
+    /// Returns the groups captured by the first match, starting with the match itself. Groups
+    /// which did not participate in the match are empty.
+    pub fn captures(self, text: String, ctx: Context) -> Vec<String> {
+        let mut groups = Vec::new(ctx);
+        if let Some(captures) = self.inner().captures(text.0.as_str()) {
+            for group in captures.iter() {
+                let group = String::from_str(group.map_or("", |m| m.as_str()), ctx);
+                groups.0.push(ctx.mutator(), group);
+            }
+        }
+        groups
+    }
+
+    /// Returns all non-overlapping matches.
+    pub fn find_all(self, text: String, ctx: Context) -> Vec<String> {
+        let mut matches = Vec::new(ctx);
+        for m in self.inner().find_iter(text.0.as_str()) {
+            let m = String::from_str(m.as_str(), ctx);
+            matches.0.push(ctx.mutator(), m);
+        }
+        matches
+    }
+
+    /// Replaces all non-overlapping matches, where `$name` and `$1` in the replacement refer to
+    /// the captured groups.
+    pub fn replace_all(self, text: String, replacement: String, ctx: Context) -> String {
+        let s = self
+            .inner()
+            .replace_all(text.0.as_str(), replacement.0.as_str());
+        String::from_str(&s, ctx)
+    }
+}
+
+// Functions which are declared as externs in the standard library.
+
+pub fn regex(pattern: String, ctx: Context) -> Regex {
+    Regex::new(pattern, ctx)
+}
+
+pub fn is_match(regex: Regex, text: String, ctx: Context) -> bool {
+    regex.is_match(text, ctx)
+}
+
+pub fn captures(regex: Regex, text: String, ctx: Context) -> Vec<String> {
+    regex.captures(text, ctx)
+}
+
+pub fn find_all(regex: Regex, text: String, ctx: Context) -> Vec<String> {
+    regex.find_all(text, ctx)
+}
+
+pub fn replace_all(regex: Regex, text: String, replacement: String, ctx: Context) -> String {
+    regex.replace_all(text, replacement, ctx)
+}
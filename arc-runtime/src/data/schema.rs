@@ -0,0 +1,187 @@
+//! Structural descriptions of types, from which the fingerprints of erased values and function
+//! keys are derived.
+//!
+//! A fingerprint is a hash of the description of a type, rather than of its name, so it changes
+//! when a field of a struct is added, removed, renamed or retyped, and it is the same in every
+//! build of a program. The descriptions of rewritten structs and enums are generated by
+//! `#[rewrite]` from their fields.
+
+use crate::prelude::*;
+
+use std::cmp::Ordering;
+
+/// The version of the descriptions of types. It is part of every fingerprint, and must be bumped
+/// when the representation of a built-in type changes, so that values of older builds are
+/// rejected instead of misread.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The offset basis of FNV-1a.
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// Continues an FNV-1a hash with `bytes`.
+const fn fnv1a_with(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+        i += 1;
+    }
+    hash
+}
+
+/// Hashes `bytes` with FNV-1a. Unlike the hashers of the standard library, it hashes the same
+/// bytes to the same value in every build.
+pub(crate) const fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_with(FNV_OFFSET, bytes)
+}
+
+/// Returns a fingerprint of the type `T`, which is derived from its description and the
+/// version of the descriptions.
+pub fn fingerprint<T: Schema + ?Sized>() -> u64 {
+    let mut schema = SchemaWriter::new();
+    schema.write(&SCHEMA_VERSION.to_string());
+    T::describe(&mut schema);
+    schema.hash
+}
+
+/// A type whose structure can be described.
+pub trait Schema {
+    fn describe(schema: &mut SchemaWriter);
+}
+
+/// Hashes the description of a type.
+pub struct SchemaWriter {
+    hash: u64,
+    /// The types which are being described, to cut off recursive types.
+    visiting: std::vec::Vec<&'static str>,
+}
+
+impl SchemaWriter {
+    fn new() -> Self {
+        Self {
+            hash: FNV_OFFSET,
+            visiting: std::vec::Vec::new(),
+        }
+    }
+
+    /// Adds a token to the description.
+    pub fn write(&mut self, token: &str) {
+        self.hash = fnv1a_with(self.hash, token.as_bytes());
+        // Separates tokens, so that `ab, c` and `a, bc` are described differently.
+        self.hash = fnv1a_with(self.hash, &[0]);
+    }
+
+    /// Describes a type which is built out of other types, such as a struct or a generic type.
+    /// The parts are described by `parts`, unless the type is nested inside itself, in which
+    /// case only its name is written.
+    pub fn compound<T: ?Sized>(&mut self, name: &str, parts: impl FnOnce(&mut Self)) {
+        self.write(name);
+        let key = std::any::type_name::<T>();
+        if !self.visiting.contains(&key) {
+            self.visiting.push(key);
+            self.write("{");
+            parts(self);
+            self.write("}");
+            self.visiting.pop();
+        }
+    }
+
+    /// Describes a field of a struct or variant, by its name and type.
+    pub fn field<T: Schema + ?Sized>(&mut self, name: &str) {
+        self.write(name);
+        T::describe(self);
+    }
+
+    /// Describes a type parameter of a generic type.
+    pub fn param<T: Schema + ?Sized>(&mut self) {
+        T::describe(self);
+    }
+}
+
+macro_rules! schema_primitive {
+    { $($ty:ty),* } => {
+        $(
+            impl Schema for $ty {
+                fn describe(schema: &mut SchemaWriter) {
+                    schema.write(stringify!($ty));
+                }
+            }
+        )*
+    }
+}
+
+schema_primitive!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64, bool, char);
+
+macro_rules! schema_tuple {
+    ($($arg:ident),*) => {
+        impl<$($arg: Schema),*> Schema for ($($arg,)*) {
+            fn describe(schema: &mut SchemaWriter) {
+                schema.compound::<Self>("tuple", |schema| {
+                    $(schema.param::<$arg>();)*
+                });
+            }
+        }
+    };
+}
+
+schema_tuple!();
+schema_tuple!(A0);
+schema_tuple!(A0, A1);
+schema_tuple!(A0, A1, A2);
+schema_tuple!(A0, A1, A2, A3);
+schema_tuple!(A0, A1, A2, A3, A4);
+schema_tuple!(A0, A1, A2, A3, A4, A5);
+schema_tuple!(A0, A1, A2, A3, A4, A5, A6);
+schema_tuple!(A0, A1, A2, A3, A4, A5, A6, A7);
+schema_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8);
+
+impl Schema for Ordering {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.write("Ordering");
+    }
+}
+
+impl Schema for String {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.write("String");
+    }
+}
+
+impl Schema for Regex {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.write("Regex");
+    }
+}
+
+impl Schema for Erased {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.write("Erased");
+    }
+}
+
+impl Schema for ErasedKey {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.write("ErasedKey");
+    }
+}
+
+impl<T: Sharable + Schema> Schema for Vec<T> {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.compound::<Self>("Vec", |schema| schema.param::<T>());
+    }
+}
+
+impl<T: Sharable + Schema> Schema for HashSet<T> {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.compound::<Self>("HashSet", |schema| schema.param::<T>());
+    }
+}
+
+impl<K: Sharable + Schema, V: Sharable + Schema> Schema for HashMap<K, V> {
+    fn describe(schema: &mut SchemaWriter) {
+        schema.compound::<Self>("HashMap", |schema| {
+            schema.param::<K>();
+            schema.param::<V>();
+        });
+    }
+}
@@ -0,0 +1,156 @@
+use crate::prelude::*;
+
+use crate::data::maps::Key;
+
+pub mod sharable {
+    use crate::prelude::*;
+
+    use crate::data::maps::Key;
+
+    #[derive(
+        Clone, From, Deref, DerefMut, Debug, Collectable, Finalize, Send, Sync, Unpin, Trace,
+    )]
+    #[from(forward)]
+    pub struct HashSet<T: Sharable>(pub Gc<ConcreteHashSet<T>>);
+
+    /// The table of a set, which like the table of a map is freed by the finalizer of the set.
+    #[derive(Debug, Collectable, Finalize, Send, Sync, Unpin)]
+    pub struct ConcreteHashSet<T: Sharable>(pub std::collections::HashSet<Key<T>>);
+
+    unsafe impl<T: Sharable> Trace for ConcreteHashSet<T> {
+        fn trace(&mut self, vis: &mut dyn Visitor) {
+            for x in self.0.iter() {
+                x.trace(vis);
+            }
+        }
+    }
+
+    impl<T: Sharable> Alloc<HashSet<T>> for ConcreteHashSet<T> {
+        fn alloc(self, ctx: Context) -> HashSet<T> {
+            HashSet(ctx.allocate(self).into())
+        }
+    }
+}
+
+mod sendable {
+    use crate::prelude::*;
+
+    #[derive(Clone, From, Send, Serialize, Deserialize)]
+    #[serde(bound = "")]
+    #[from(forward)]
+    pub struct HashSet<T: Sendable + Eq + Hash>(pub ConcreteHashSet<T>);
+
+    pub type ConcreteHashSet<T> = std::collections::HashSet<T>;
+}
+
+impl<T> DynSharable for sharable::HashSet<T>
+where
+    T: Sharable + Eq + Hash,
+    T::T: DynSendable<T = T> + Eq + Hash,
+{
+    type T = sendable::HashSet<T::T>;
+    fn into_sendable(&self, ctx: Context) -> Self::T {
+        self.table()
+            .iter()
+            .map(|x| x.get().into_sendable(ctx))
+            .collect::<std::collections::HashSet<_>>()
+            .into()
+    }
+}
+
+impl<T> DynSendable for sendable::HashSet<T>
+where
+    T: Sendable + Eq + Hash,
+    T::T: DynSharable<T = T> + Eq + Hash,
+{
+    type T = sharable::HashSet<T::T>;
+    fn into_sharable(&self, ctx: Context) -> Self::T {
+        let mut s = HashSet::with_capacity(self.0.len(), ctx);
+        for x in self.0.iter() {
+            let x = x.into_sharable(ctx);
+            s.insert(x, ctx);
+        }
+        s
+    }
+}
+
+pub use sharable::HashSet;
+
+impl<T: Sharable + Eq + Hash> HashSet<T> {
+    pub fn new(ctx: Context) -> Self {
+        Self::with_capacity(0, ctx)
+    }
+
+    pub fn with_capacity(capacity: usize, ctx: Context) -> Self {
+        let set = std::collections::HashSet::with_capacity(capacity);
+        sharable::ConcreteHashSet(set).alloc(ctx)
+    }
+
+    fn table(&self) -> &std::collections::HashSet<Key<T>> {
+        &(self.0).0
+    }
+
+    /// Inserts a value, and returns whether it was not already in the set.
+    pub fn insert(&mut self, value: T, ctx: Context) -> bool {
+        let inserted = (self.0).0.insert(Key::new(value));
+        self.0.write_barrier(ctx);
+        inserted
+    }
+
+    pub fn contains(&self, value: T, ctx: Context) -> bool {
+        self.table().contains(&Key::new(value))
+    }
+
+    /// Removes a value, and returns whether it was in the set.
+    pub fn remove(&mut self, value: T, ctx: Context) -> bool {
+        (self.0).0.remove(&Key::new(value))
+    }
+
+    pub fn len(&self, ctx: Context) -> usize {
+        self.table().len()
+    }
+
+    pub fn is_empty(&self, ctx: Context) -> bool {
+        self.table().is_empty()
+    }
+
+    pub fn clear(&mut self, ctx: Context) {
+        (self.0).0.clear();
+    }
+
+    /// Returns the values of the set, in an unspecified order.
+    pub fn values(&self, ctx: Context) -> Vec<T> {
+        let stack = ctx.mutator().shadow_stack();
+        letroot!(values = stack, Some(Vec::with_capacity(self.len(ctx), ctx)));
+        for x in self.table().iter() {
+            values.as_mut().unwrap().push(x.get().clone(), ctx);
+        }
+        values.take().unwrap()
+    }
+}
+
+// Functions which are declared as externs in the standard library.
+
+pub fn new_hash_set<T: Sharable + Eq + Hash>(ctx: Context) -> HashSet<T> {
+    HashSet::new(ctx)
+}
+
+pub fn insert_hash_set<T: Sharable + Eq + Hash>(mut s: HashSet<T>, x: T, ctx: Context) -> bool {
+    s.insert(x, ctx)
+}
+
+pub fn contains_hash_set<T: Sharable + Eq + Hash>(s: HashSet<T>, x: T, ctx: Context) -> bool {
+    s.contains(x, ctx)
+}
+
+pub fn remove_hash_set<T: Sharable + Eq + Hash>(mut s: HashSet<T>, x: T, ctx: Context) -> bool {
+    s.remove(x, ctx)
+}
+
+pub fn len_hash_set<T: Sharable + Eq + Hash>(s: HashSet<T>, ctx: Context) -> i32 {
+    s.len(ctx) as i32
+}
+
+pub fn values_hash_set<T: Sharable + Eq + Hash>(s: HashSet<T>, ctx: Context) -> Vec<T> {
+    s.values(ctx)
+}
@@ -1,5 +1,8 @@
 use crate::prelude::*;
 
+use std::ops::Range;
+use std::str::FromStr;
+
 pub mod sharable {
     use crate::prelude::*;
 
@@ -11,7 +14,7 @@ pub mod sharable {
 
     impl Alloc<String> for ConcreteString {
         fn alloc(self, ctx: Context) -> String {
-            String(ctx.mutator().allocate(self, AllocationSpace::New).into())
+            String(ctx.allocate(self).into())
         }
     }
 
@@ -48,6 +51,52 @@ impl DynSendable for sendable::String {
 
 pub use sharable::String;
 
+/// Both forms of strings compare and hash as `str`, so that they hash to the same value.
+macro_rules! compare_as_str {
+    ($ty:ty) => {
+        impl PartialEq for $ty {
+            fn eq(&self, other: &Self) -> bool {
+                self.as_key() == other.as_key()
+            }
+        }
+
+        impl Eq for $ty {}
+
+        impl PartialOrd for $ty {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $ty {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.as_key().cmp(other.as_key())
+            }
+        }
+
+        impl Hash for $ty {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.as_key().hash(state)
+            }
+        }
+    };
+}
+
+impl sharable::String {
+    fn as_key(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl sendable::String {
+    fn as_key(&self) -> &str {
+        &self.0
+    }
+}
+
+compare_as_str!(sharable::String);
+compare_as_str!(sendable::String);
+
 impl String {
     pub fn new(ctx: Context) -> String {
         sharable::ConcreteString::new(ctx.mutator()).alloc(ctx)
@@ -94,4 +143,90 @@ impl String {
     pub fn len(&self, _: Context) -> usize {
         self.0.len()
     }
+
+    pub fn concat(self, other: String, ctx: Context) -> String {
+        let len = self.0.len() + other.0.len();
+        let mut new = sharable::ConcreteString::with_capacity(ctx.mutator(), len);
+        new.push_str(ctx.mutator(), self.0.as_str());
+        new.push_str(ctx.mutator(), other.0.as_str());
+        new.alloc(ctx)
+    }
+
+    /// Returns the number of characters, unlike `len` which returns the number of bytes.
+    pub fn char_len(self, _: Context) -> usize {
+        self.0.as_str().chars().count()
+    }
+
+    /// Returns the characters in `range`, which is given in characters rather than bytes, or
+    /// `None` if the range is reversed or extends past the end of the string.
+    pub fn slice(self, range: Range<usize>, ctx: Context) -> Option<String> {
+        if range.start > range.end {
+            return None;
+        }
+        let s = self.0.as_str();
+        let start = byte_offset(s, range.start)?;
+        let end = byte_offset(s, range.end)?;
+        Some(String::from_str(&s[start..end], ctx))
+    }
+
+    pub fn char_at(self, index: usize, _: Context) -> Option<char> {
+        self.0.as_str().chars().nth(index)
+    }
+
+    pub fn contains(self, pat: String, _: Context) -> bool {
+        self.0.as_str().contains(pat.0.as_str())
+    }
+
+    /// Returns the index of the first character where `pat` occurs.
+    pub fn find(self, pat: String, _: Context) -> Option<usize> {
+        let s = self.0.as_str();
+        s.find(pat.0.as_str()).map(|i| s[..i].chars().count())
+    }
+
+    pub fn split(self, sep: String, ctx: Context) -> Vec<String> {
+        let mut parts = Vec::new(ctx);
+        for part in self.0.as_str().split(sep.0.as_str()) {
+            let part = String::from_str(part, ctx);
+            parts.0.push(ctx.mutator(), part);
+        }
+        parts
+    }
+
+    pub fn replace(self, from: String, to: String, ctx: Context) -> String {
+        String::from_str(
+            &self.0.as_str().replace(from.0.as_str(), to.0.as_str()),
+            ctx,
+        )
+    }
+
+    pub fn trim(self, ctx: Context) -> String {
+        String::from_str(self.0.as_str().trim(), ctx)
+    }
+
+    pub fn to_uppercase(self, ctx: Context) -> String {
+        String::from_str(&self.0.as_str().to_uppercase(), ctx)
+    }
+
+    pub fn to_lowercase(self, ctx: Context) -> String {
+        String::from_str(&self.0.as_str().to_lowercase(), ctx)
+    }
+
+    pub fn starts_with(self, pat: String, _: Context) -> bool {
+        self.0.as_str().starts_with(pat.0.as_str())
+    }
+
+    pub fn ends_with(self, pat: String, _: Context) -> bool {
+        self.0.as_str().ends_with(pat.0.as_str())
+    }
+
+    /// Parses the string into a number, or any other type which implements `FromStr`.
+    pub fn parse<T: FromStr>(self, _: Context) -> Result<T, T::Err> {
+        self.0.as_str().parse()
+    }
+}
+
+/// Returns the byte offset of the character at `index`, which may be one past the last character,
+/// or `None` if the index is out of bounds.
+fn byte_offset(s: &str, index: usize) -> Option<usize> {
+    s.char_indices().map(|(i, _)| i).chain([s.len()]).nth(index)
 }
@@ -1,5 +1,8 @@
 use crate::prelude::*;
 
+use std::cmp::Ordering;
+use std::ops::Range;
+
 pub mod sharable {
     use crate::prelude::*;
 
@@ -13,7 +16,7 @@ pub mod sharable {
 
     impl<T: Sharable> Alloc<Vec<T>> for ConcreteVec<T> {
         fn alloc(self, ctx: Context) -> Vec<T> {
-            Vec(ctx.mutator().allocate(self, AllocationSpace::New).into())
+            Vec(ctx.allocate(self).into())
         }
     }
 }
@@ -55,6 +58,55 @@ impl<T: Sendable> DynSendable for sendable::Vec<T> {
 
 pub use sharable::Vec;
 
+/// Both forms of vectors compare and hash as slices, so that they hash to the same value if
+/// their elements do.
+macro_rules! compare_as_slice {
+    ($vec:ident, $bound:ident) => {
+        impl<T: $bound + PartialEq> PartialEq for $vec<T> {
+            fn eq(&self, other: &Self) -> bool {
+                self.as_key() == other.as_key()
+            }
+        }
+
+        impl<T: $bound + Eq> Eq for $vec<T> {}
+
+        impl<T: $bound + PartialOrd> PartialOrd for $vec<T> {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                self.as_key().partial_cmp(other.as_key())
+            }
+        }
+
+        impl<T: $bound + Ord> Ord for $vec<T> {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.as_key().cmp(other.as_key())
+            }
+        }
+
+        impl<T: $bound + Hash> Hash for $vec<T> {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.as_key().hash(state)
+            }
+        }
+    };
+}
+
+use sendable::Vec as SendableVec;
+
+impl<T: Sharable> Vec<T> {
+    fn as_key(&self) -> &[T] {
+        self.0.as_slice()
+    }
+}
+
+impl<T: Sendable> SendableVec<T> {
+    fn as_key(&self) -> &[T] {
+        &self.0
+    }
+}
+
+compare_as_slice!(Vec, Sharable);
+compare_as_slice!(SendableVec, Sendable);
+
 impl<T: Sharable> Vec<T> {
     pub fn new(ctx: Context) -> Self {
         sharable::ConcreteVec::<T>::new(ctx.mutator()).alloc(ctx)
@@ -64,8 +116,10 @@ impl<T: Sharable> Vec<T> {
         sharable::ConcreteVec::<T>::with_capacity(ctx.mutator(), capacity).alloc(ctx)
     }
 
-    pub fn write_barrier(&mut self, ctx: Context) {
-        self.0.write_barrier(ctx.mutator())
+    /// Notifies the collector that the elements of the vector have been mutated. This uses the
+    /// barrier of the vector itself, which records its element storage rather than the `Gc` cell.
+    pub fn write_barrier(&self, ctx: Context) {
+        (*self.0).write_barrier(ctx.mutator())
     }
 
     pub fn as_slice(&self, ctx: Context) -> &[T] {
@@ -76,61 +130,209 @@ impl<T: Sharable> Vec<T> {
         self.0.as_slice_mut()
     }
 
-    pub fn capacity(self, ctx: Context) -> usize {
+    pub fn capacity(&self, ctx: Context) -> usize {
         self.0.capacity()
     }
 
-    pub fn len(self, ctx: Context) -> usize {
+    pub fn len(&self, ctx: Context) -> usize {
         self.0.len()
     }
 
-    pub fn shrink_to(mut self, min_capacity: usize, ctx: Context) {
+    pub fn shrink_to(&mut self, min_capacity: usize, ctx: Context) {
         self.0.shrink_to(ctx.mutator(), min_capacity);
+        self.write_barrier(ctx);
     }
 
-    pub fn retain<F>(mut self, f: F, ctx: Context)
+    pub fn retain<F>(&mut self, f: F, ctx: Context)
     where
         F: FnMut(&T) -> bool,
     {
         self.0.retain(f);
     }
 
-    pub fn clear(mut self, ctx: Context) {
+    pub fn clear(&mut self, ctx: Context) {
         self.0.clear();
     }
 
-    pub fn resize(mut self, new_len: usize, value: T, ctx: Context) {
+    pub fn resize(&mut self, new_len: usize, value: T, ctx: Context) {
         self.0.resize(ctx.mutator(), new_len, value);
+        self.write_barrier(ctx);
     }
 
-    pub fn push(mut self, value: T, ctx: Context) {
+    pub fn push(&mut self, value: T, ctx: Context) {
         self.0.push(ctx.mutator(), value);
+        self.write_barrier(ctx);
     }
 
-    pub fn pop(mut self, ctx: Context) -> Option<T> {
+    pub fn pop(&mut self, ctx: Context) -> Option<T> {
         self.0.pop()
     }
 
-    pub fn remove(mut self, index: usize, ctx: Context) -> T {
+    pub fn remove(&mut self, index: usize, ctx: Context) -> T {
         self.0.remove(index)
     }
 
-    pub fn at(self, index: usize, ctx: Context) -> T {
+    pub fn at(&self, index: usize, ctx: Context) -> T {
         self.0.at(index).clone()
     }
 
-    pub fn insert(mut self, index: usize, value: T, ctx: Context) {
+    pub fn insert(&mut self, index: usize, value: T, ctx: Context) {
         self.0.insert(ctx.mutator(), index, value);
+        self.write_barrier(ctx);
     }
 
-    pub fn is_empty(self, ctx: Context) -> bool {
+    pub fn is_empty(&self, ctx: Context) -> bool {
         self.0.is_empty()
     }
 
-    pub fn dedup(mut self, ctx: Context)
+    pub fn dedup(&mut self, ctx: Context)
     where
         T: PartialEq,
     {
         self.0.dedup();
     }
+
+    /// Appends the elements of `other`, which may be the vector itself.
+    pub fn extend(&mut self, other: Vec<T>, ctx: Context) {
+        let len = other.len(ctx);
+        for i in 0..len {
+            let value = other.at(i, ctx);
+            self.0.push(ctx.mutator(), value);
+        }
+        self.write_barrier(ctx);
+    }
+
+    pub fn contains(&self, value: T, ctx: Context) -> bool
+    where
+        T: PartialEq,
+    {
+        self.0.as_slice().contains(&value)
+    }
+
+    /// Returns an iterator over clones of the elements. The iterator holds a handle to the
+    /// vector and reads it by index, so it stays valid if the vector grows or is reallocated.
+    pub fn iter(&self, ctx: Context) -> Iter<T> {
+        Iter {
+            vec: self.clone(),
+            index: 0,
+        }
+    }
+
+    /// Returns a new vector of the elements in `range`, or `None` if the range is reversed or
+    /// extends past the end of the vector.
+    pub fn slice(&self, range: Range<usize>, ctx: Context) -> Option<Vec<T>> {
+        if range.start > range.end || range.end > self.len(ctx) {
+            return None;
+        }
+        let stack = ctx.mutator().shadow_stack();
+        letroot!(slice = stack, Some(Vec::with_capacity(range.len(), ctx)));
+        for i in range {
+            let value = self.at(i, ctx);
+            slice.as_mut().unwrap().push(value, ctx);
+        }
+        slice.take()
+    }
+
+    pub fn map<O: Sharable>(&self, f: impl Callable<(T,), O>, ctx: Context) -> Vec<O> {
+        let stack = ctx.mutator().shadow_stack();
+        letroot!(mapped = stack, Some(Vec::with_capacity(self.len(ctx), ctx)));
+        for x in self.iter(ctx) {
+            let y = f.call((x,), ctx);
+            mapped.as_mut().unwrap().push(y, ctx);
+        }
+        mapped.take().unwrap()
+    }
+
+    pub fn filter(&self, f: impl Callable<(T,), bool>, ctx: Context) -> Vec<T> {
+        let stack = ctx.mutator().shadow_stack();
+        letroot!(filtered = stack, Some(Vec::new(ctx)));
+        for x in self.iter(ctx) {
+            if f.call((x.clone(),), ctx) {
+                filtered.as_mut().unwrap().push(x, ctx);
+            }
+        }
+        filtered.take().unwrap()
+    }
+
+    pub fn fold<A>(&self, init: A, f: impl Callable<(A, T), A>, ctx: Context) -> A {
+        self.iter(ctx).fold(init, |acc, x| f.call((acc, x), ctx))
+    }
+
+    pub fn sort(&mut self, ctx: Context)
+    where
+        T: Ord,
+    {
+        self.0.as_slice_mut().sort();
+    }
+
+    /// Sorts the vector with a comparator, which is called with clones of the elements. Since
+    /// the comparator may use the vector, a copy of the elements is sorted and then pushed back.
+    pub fn sort_by(&mut self, f: impl Callable<(T, T), Ordering>, ctx: Context) {
+        let mut elements = self.0.as_slice().to_vec();
+        elements.sort_by(|a, b| f.call((a.clone(), b.clone()), ctx));
+        self.0.clear();
+        for x in elements {
+            self.0.push(ctx.mutator(), x);
+        }
+        self.write_barrier(ctx);
+    }
+
+    /// Searches a sorted vector for `value`, returning its index if it is found and otherwise
+    /// the index where it could be inserted.
+    pub fn binary_search(&self, value: T, ctx: Context) -> Result<usize, usize>
+    where
+        T: Ord,
+    {
+        self.0.as_slice().binary_search(&value)
+    }
+}
+
+/// An iterator over a vector, see [`Vec::iter`].
+#[derive(Clone, Debug)]
+pub struct Iter<T: Sharable> {
+    vec: Vec<T>,
+    index: usize,
+}
+
+impl<T: Sharable> Iterator for Iter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let value = self.vec.0.as_slice().get(self.index)?.clone();
+        self.index += 1;
+        Some(value)
+    }
+}
+
+// Functions which are declared as externs in the standard library, where arrays are indexed by
+// `i32`.
+
+pub fn array<T: Sharable>(ctx: Context) -> Vec<T> {
+    Vec::new(ctx)
+}
+
+pub fn push<T: Sharable>(mut a: Vec<T>, x: T, ctx: Context) {
+    a.push(x, ctx)
+}
+
+pub fn pop<T: Sharable>(mut a: Vec<T>, ctx: Context) {
+    a.pop(ctx);
+}
+
+/// Returns the element at index `i`, or `None` if the index is negative or out of bounds.
+pub fn select<T: Sharable>(a: Vec<T>, i: i32, ctx: Context) -> Option<T> {
+    let i = usize::try_from(i).ok()?;
+    (i < a.len(ctx)).then(|| a.at(i, ctx))
+}
+
+pub fn len<T: Sharable>(a: Vec<T>, ctx: Context) -> i32 {
+    a.len(ctx) as i32
+}
+
+pub fn extend<T: Sharable>(mut a: Vec<T>, other: Vec<T>, ctx: Context) {
+    a.extend(other, ctx)
+}
+
+pub fn contains<T: Sharable + PartialEq>(x: T, a: Vec<T>, ctx: Context) -> bool {
+    a.contains(x, ctx)
 }
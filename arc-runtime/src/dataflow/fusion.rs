@@ -0,0 +1,236 @@
+//! Fusion of one-to-one operators.
+//!
+//! A chain of one-to-one operators (such as `map -> filter -> map`) is deployed as a single task
+//! which pulls from the input of the first operator and passes each value through the chain by
+//! direct calls. Only the output of the last operator goes through a channel.
+
+use crate::data::channels::local::multicast::channel;
+use crate::data::channels::local::multicast::Pullable;
+use crate::data::channels::local::multicast::Pushable;
+use crate::prelude::*;
+use crate::task::state::HandBack;
+
+use kompact::prelude::*;
+
+use std::any::Any;
+
+/// A type-erased `Box<dyn Emit<T>>`.
+pub(crate) type AnyEmit = Box<dyn Any + Send>;
+
+/// A type-erased `Pullable<T>`.
+pub(crate) type AnyPullable = Box<dyn Any + Send>;
+
+/// A stage of a fused chain, which consumes values of type `T`.
+pub(crate) trait Emit<T>: Send + Sync {
+    fn emit(&mut self, data: T, ctx: Context) -> Control<()>;
+    /// Clones the rest of the chain, for when the chain is restarted.
+    fn boxed(&self) -> Box<dyn Emit<T>>;
+}
+
+struct Map<I, O> {
+    f: fn(I, Context) -> O,
+    next: Box<dyn Emit<O>>,
+}
+
+impl<I: Sharable + 'static, O: 'static> Emit<I> for Map<I, O> {
+    fn emit(&mut self, data: I, ctx: Context) -> Control<()> {
+        match protect(self.f, data, ctx) {
+            Some(data) => self.next.emit(data, ctx),
+            None => Control::Continue(()),
+        }
+    }
+
+    fn boxed(&self) -> Box<dyn Emit<I>> {
+        Box::new(Map {
+            f: self.f,
+            next: self.next.boxed(),
+        })
+    }
+}
+
+struct Filter<T> {
+    f: fn(T, Context) -> bool,
+    next: Box<dyn Emit<T>>,
+}
+
+impl<T: Sharable + 'static> Emit<T> for Filter<T> {
+    fn emit(&mut self, data: T, ctx: Context) -> Control<()> {
+        if protect(self.f, data.clone(), ctx) == Some(true) {
+            self.next.emit(data, ctx)
+        } else {
+            Control::Continue(())
+        }
+    }
+
+    fn boxed(&self) -> Box<dyn Emit<T>> {
+        Box::new(Filter {
+            f: self.f,
+            next: self.next.boxed(),
+        })
+    }
+}
+
+/// The end of a chain, which pushes into the output channel.
+struct Tail<T: Sharable>(Pushable<T>);
+
+impl<T: Sharable + 'static> Emit<T> for Tail<T> {
+    fn emit(&mut self, data: T, ctx: Context) -> Control<()> {
+        self.0.try_push(data, ctx)
+    }
+
+    fn boxed(&self) -> Box<dyn Emit<T>> {
+        Box::new(Tail(self.0.clone()))
+    }
+}
+
+/// A one-to-one operator. Since the types of neighbouring operators are only known when the
+/// graph is deployed, the chain is assembled through type-erased constructors.
+pub(crate) struct Stage {
+    /// Turns the `Box<dyn Emit<O>>` of the rest of the chain into a `Box<dyn Emit<I>>`.
+    wrap: Box<dyn FnOnce(AnyEmit) -> AnyEmit + Send>,
+    /// Creates the output channel, if this is the last operator of the chain.
+    tail: fn(Context) -> (AnyEmit, AnyPullable),
+    /// Launches the chain, if this is the first operator of the chain.
+    launch: fn(AnyPullable, AnyEmit, Context),
+}
+
+impl Stage {
+    pub(crate) fn map<I, O>(f: fn(I, Context) -> O) -> Self
+    where
+        I: Sharable + 'static,
+        O: Sharable + 'static,
+        I::T: Sendable<T = I>,
+        O::T: Sendable<T = O>,
+    {
+        Self {
+            wrap: Box::new(move |next| {
+                let next = *next.downcast::<Box<dyn Emit<O>>>().unwrap();
+                Box::new(Box::new(Map { f, next }) as Box<dyn Emit<I>>)
+            }),
+            tail: tail::<O>,
+            launch: launch::<I>,
+        }
+    }
+
+    pub(crate) fn filter<T>(f: fn(T, Context) -> bool) -> Self
+    where
+        T: Sharable + 'static,
+        T::T: Sendable<T = T>,
+    {
+        Self {
+            wrap: Box::new(move |next| {
+                let next = *next.downcast::<Box<dyn Emit<T>>>().unwrap();
+                Box::new(Box::new(Filter { f, next }) as Box<dyn Emit<T>>)
+            }),
+            tail: tail::<T>,
+            launch: launch::<T>,
+        }
+    }
+}
+
+fn tail<T>(ctx: Context) -> (AnyEmit, AnyPullable)
+where
+    T: Sharable + 'static,
+    T::T: Sendable<T = T>,
+{
+    let (pushable, pullable) = channel::<T>(ctx);
+    let tail = Box::new(Tail(pushable)) as Box<dyn Emit<T>>;
+    (Box::new(tail), Box::new(pullable))
+}
+
+fn launch<T>(input: AnyPullable, head: AnyEmit, ctx: Context)
+where
+    T: Sharable + 'static,
+    T::T: Sendable<T = T>,
+{
+    let input = *input.downcast::<Pullable<T>>().unwrap();
+    let head = *head.downcast::<Box<dyn Emit<T>>>().unwrap();
+    // A restarted chain reattaches to the same channels, and is handed back the receiver of
+    // the failed chain.
+    ctx.supervise(move |state| Chain::new(state, input.clone(), head.boxed()));
+}
+
+/// Deploys a chain of stages as a single task, and returns the output of the last stage.
+pub(crate) fn deploy(chain: std::vec::Vec<Stage>, input: AnyPullable, ctx: Context) -> AnyPullable {
+    let launch = chain.first().unwrap().launch;
+    let (mut emit, output) = (chain.last().unwrap().tail)(ctx);
+    for stage in chain.into_iter().rev() {
+        emit = (stage.wrap)(emit);
+    }
+    launch(input, emit, ctx);
+    output
+}
+
+/// The task of a fused chain.
+#[derive(ComponentDefinition)]
+struct Chain<T: Sharable + 'static>
+where
+    T::T: Sendable<T = T>,
+{
+    ctx: ComponentContext<Self>,
+    state: Arc<TaskState>,
+    input: Option<Pullable<T>>,
+    head: Option<Box<dyn Emit<T>>>,
+}
+
+impl<T: Sharable + 'static> Chain<T>
+where
+    T::T: Sendable<T = T>,
+{
+    fn new(state: Arc<TaskState>, input: Pullable<T>, head: Box<dyn Emit<T>>) -> Self {
+        Self {
+            ctx: ComponentContext::uninitialised(),
+            state,
+            input: Some(input),
+            head: Some(head),
+        }
+    }
+
+    async fn run(
+        mut input: HandBack<Pullable<T>>,
+        mut head: Box<dyn Emit<T>>,
+        ctx: Context,
+    ) -> Control<()> {
+        loop {
+            let data = input.pull(ctx).await?;
+            head.emit(data, ctx)?;
+        }
+    }
+}
+
+impl<T: Sharable + 'static> Actor for Chain<T>
+where
+    T::T: Sendable<T = T>,
+{
+    type Message = TaskMessage;
+
+    fn receive_local(&mut self, msg: Self::Message) -> Handled {
+        self.state.handle(msg);
+        Handled::Ok
+    }
+
+    fn receive_network(&mut self, _: NetMessage) -> Handled {
+        warn!(self.log(), "Ignoring network message sent to a local task");
+        Handled::Ok
+    }
+}
+
+impl<T: Sharable + 'static> ComponentLifecycle for Chain<T>
+where
+    T::T: Sendable<T = T>,
+{
+    fn on_start(&mut self) -> Handled {
+        let input = HandBack::new("input", self.input.take().unwrap(), &self.state);
+        let head = self.head.take().unwrap();
+        let state = self.state.clone();
+        self.spawn_local(move |async_self| async move {
+            let component = async_self.ctx().component();
+            let mutator = instantiate_immix(ImmixOptions::default());
+            let ctx = Context::with_state(component, mutator, state.clone());
+            state.control(Self::run(input, head, ctx)).await;
+            ctx.destroy();
+            Handled::DieNow
+        });
+        Handled::Ok
+    }
+}
@@ -0,0 +1,632 @@
+//! A programmatic builder for dataflow graphs.
+//!
+//! Pipelines are normally assembled by code generated through `#[rewrite]`. The builder lets
+//! Rust code assemble the same pipelines at runtime: sources, operators and sinks are added as
+//! nodes, connected through typed [`Stream`]s, validated, and then deployed onto a [`Runtime`].
+//!
+//! Chains of one-to-one operators added through [`DataflowBuilder::add_map`] and
+//! [`DataflowBuilder::add_filter`] are fused into a single task when deployed, unless fusion is
+//! disabled for the graph or for one of the operators.
+//!
+//! ```no_run
+//! use arc_runtime::data::channels::local::multicast::channel;
+//! use arc_runtime::data::channels::local::multicast::Pullable;
+//! use arc_runtime::prelude::*;
+//!
+//! #[rewrite(nonpersistent)]
+//! async fn print(mut i: Pullable<i32>) {
+//!     loop {
+//!         let x: i32 = pull!(i);
+//!         println!("{}", x);
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let runtime = Runtime::new();
+//!     let mut builder = DataflowBuilder::new();
+//!     let s = builder.add_source("numbers", |ctx| {
+//!         let (o, i) = channel(ctx);
+//!         for x in 1..=10 {
+//!             o.try_push(x, ctx);
+//!         }
+//!         i
+//!     });
+//!     let s = builder.add_map("double", s, |x: i32, _| x * 2);
+//!     builder.add_sink("print", s, |s, ctx| print((s,), ctx));
+//!     builder.deploy(&runtime).unwrap();
+//! }
+//! ```
+
+mod fusion;
+
+use crate::cluster::bridge;
+use crate::cluster::bridge::Import;
+use crate::cluster::bridge::Link;
+use crate::cluster::Placement;
+use crate::data::channels::local::multicast::channel;
+use crate::data::channels::local::multicast::Pullable;
+use crate::dataflow::fusion::Stage;
+use crate::prelude::*;
+
+use kompact::prelude::*;
+
+use std::any::Any;
+use std::marker::PhantomData;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+/// Used to tell apart streams of different builders.
+static NEXT_GRAPH_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Identifies a node within a dataflow graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// A typed edge out of a node. A stream can be connected to any number of downstream nodes.
+pub struct Stream<T: Sharable> {
+    graph: usize,
+    node: NodeId,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Sharable> Clone for Stream<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Sharable> Copy for Stream<T> {}
+
+impl<T: Sharable> Stream<T> {
+    /// Returns the node which produces this stream.
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Source,
+    Operator,
+    Sink,
+}
+
+/// An error found while validating a dataflow graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataflowError {
+    /// The graph contains no sources.
+    NoSource,
+    /// The graph contains no sinks.
+    NoSink,
+    /// Two nodes share the same name.
+    DuplicateName(std::string::String),
+    /// A node consumes a stream which was created by a different builder.
+    ForeignStream(std::string::String),
+    /// The output of a node is not consumed by any other node.
+    Unconsumed(std::string::String),
+    /// No graph with this name is registered with the cluster.
+    UnknownGraph(std::string::String),
+    /// The cluster has no workers to place the graph on.
+    NoWorkers,
+    /// The output of a node is consumed, but cannot be sent between the workers of a cluster.
+    Unsendable(std::string::String),
+}
+
+impl std::fmt::Display for DataflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataflowError::NoSource => write!(f, "dataflow graph has no sources"),
+            DataflowError::NoSink => write!(f, "dataflow graph has no sinks"),
+            DataflowError::DuplicateName(name) => write!(f, "node name `{}` is not unique", name),
+            DataflowError::ForeignStream(name) => {
+                write!(f, "node `{}` consumes a stream of another graph", name)
+            }
+            DataflowError::Unconsumed(name) => write!(f, "output of node `{}` is unused", name),
+            DataflowError::UnknownGraph(name) => write!(f, "no graph is named `{}`", name),
+            DataflowError::NoWorkers => write!(f, "cluster has no workers"),
+            DataflowError::Unsendable(name) => {
+                write!(f, "output of node `{}` cannot cross workers", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DataflowError {}
+
+/// The output of a deployed node, handed out to its consumers.
+type Output = Box<dyn Any + Send>;
+
+/// Holds the output of a deployed node until all of its consumers have been deployed.
+struct Slot {
+    output: Option<Output>,
+    consumers: usize,
+}
+
+/// An incoming edge of a node.
+struct Input {
+    node: NodeId,
+    /// Takes (or, if more consumers remain, clones) the channel out of the upstream slot.
+    fetch: fn(&mut Slot) -> Output,
+}
+
+/// Moves the output of a node between the workers of a cluster.
+#[derive(Clone, Copy)]
+struct Bridge {
+    /// Sends the channel to the worker of a consumer, if it is placed on another worker.
+    export: fn(Output, Link, Context),
+    /// Receives the channel from the worker of the node.
+    import: fn(Context) -> (Import, Output),
+}
+
+enum Deploy {
+    /// A node which launches its own tasks.
+    Task(Box<dyn FnOnce(std::vec::Vec<Output>, Context) -> Option<Output> + Send>),
+    /// A one-to-one operator which can be fused with its neighbours.
+    Stage(Stage),
+}
+
+struct Node {
+    name: std::string::String,
+    kind: NodeKind,
+    inputs: std::vec::Vec<Input>,
+    deploy: Deploy,
+    fusible: bool,
+    policy: RestartPolicy,
+    retries: usize,
+    /// Set if the output of the node can be sent between workers.
+    bridge: Option<Bridge>,
+}
+
+fn fetch<T: Sharable + 'static>(slot: &mut Slot) -> Output {
+    slot.consumers -= 1;
+    if slot.consumers == 0 {
+        slot.output.take().unwrap()
+    } else {
+        let pullable = slot.output.as_ref().unwrap().downcast_ref::<Pullable<T>>();
+        Box::new(pullable.unwrap().clone())
+    }
+}
+
+fn downcast<T: Sharable + 'static>(output: Output) -> Pullable<T> {
+    *output.downcast::<Pullable<T>>().unwrap()
+}
+
+/// Assembles a dataflow graph out of sources, operators and sinks.
+pub struct DataflowBuilder {
+    id: usize,
+    nodes: std::vec::Vec<Node>,
+    fusion: bool,
+    /// The node which produces the dead letters of the graph, if any.
+    dead_letters: Option<NodeId>,
+}
+
+impl Default for DataflowBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataflowBuilder {
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_GRAPH_ID.fetch_add(1, Ordering::Relaxed),
+            nodes: std::vec::Vec::new(),
+            fusion: true,
+            dead_letters: None,
+        }
+    }
+
+    /// Enables or disables operator fusion for the whole graph. Fusion is enabled by default.
+    pub fn set_fusion(&mut self, enabled: bool) -> &mut Self {
+        self.fusion = enabled;
+        self
+    }
+
+    /// Prevents a one-to-one operator from being fused with its neighbours.
+    pub fn disable_fusion(&mut self, node: NodeId) -> &mut Self {
+        self.nodes[node.0].fusible = false;
+        self
+    }
+
+    /// Sets the restart policy of the tasks which are launched by a node. Fused operators share a
+    /// single task, so this also prevents the node from being fused.
+    pub fn set_restart_policy(&mut self, node: NodeId, policy: RestartPolicy) -> &mut Self {
+        let node = &mut self.nodes[node.0];
+        node.fusible &= policy == RestartPolicy::Never;
+        node.policy = policy;
+        self
+    }
+
+    /// Sets how many times the tasks of a node retry a record on which a function called
+    /// through `call_protected!` panics, before routing it to the dead letters of the graph.
+    /// Operators with retries are not fused, since the retries apply to the whole task.
+    pub fn set_retries(&mut self, node: NodeId, retries: usize) -> &mut Self {
+        let node = &mut self.nodes[node.0];
+        node.fusible &= retries == 0;
+        node.retries = retries;
+        self
+    }
+
+    /// Allows `stream` to be sent between the workers of a cluster. Graphs which are submitted
+    /// to a cluster must allow this for every stream which they consume, while graphs which run
+    /// on a single runtime never send their streams.
+    pub fn set_sendable<T>(&mut self, stream: Stream<T>) -> &mut Self
+    where
+        T: Sharable + 'static,
+        T::T: Sendable<T = T>,
+    {
+        if stream.graph == self.id {
+            self.nodes[stream.node.0].bridge = Some(Bridge {
+                export: bridge::export::<T>,
+                import: bridge::import::<T>,
+            });
+        }
+        self
+    }
+
+    /// Adds a node which produces the records that failed in any task of the graph. Without
+    /// this node, a record which fails makes its task fail.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the graph already has a dead-letter node.
+    pub fn add_dead_letters(&mut self, name: impl Into<std::string::String>) -> Stream<DeadLetter> {
+        assert!(
+            self.dead_letters.is_none(),
+            "graph already has dead letters"
+        );
+        // The channel is created by `run`, before any task is deployed.
+        let deploy = Deploy::Task(Box::new(|_, _| None));
+        let node = self.add_node(name.into(), NodeKind::Source, vec![], deploy);
+        self.dead_letters = Some(node);
+        let stream = self.stream(node);
+        self.set_sendable(stream);
+        stream
+    }
+
+    /// Adds a node which produces a stream, for example by launching a source task.
+    pub fn add_source<T, F>(&mut self, name: impl Into<std::string::String>, f: F) -> Stream<T>
+    where
+        T: Sharable + 'static,
+        F: FnOnce(Context) -> Pullable<T> + Send + 'static,
+    {
+        let deploy = Deploy::Task(Box::new(move |_, ctx| Some(Box::new(f(ctx)) as Output)));
+        let node = self.add_node(name.into(), NodeKind::Source, vec![], deploy);
+        self.stream(node)
+    }
+
+    /// Adds a one-to-one operator which transforms each value of a stream.
+    pub fn add_map<I, O>(
+        &mut self,
+        name: impl Into<std::string::String>,
+        input: Stream<I>,
+        f: fn(I, Context) -> O,
+    ) -> Stream<O>
+    where
+        I: Sharable + 'static,
+        O: Sharable + 'static,
+        I::T: Sendable<T = I>,
+        O::T: Sendable<T = O>,
+    {
+        let inputs = vec![self.input(input)];
+        let deploy = Deploy::Stage(Stage::map(f));
+        let node = self.add_node(name.into(), NodeKind::Operator, inputs, deploy);
+        self.stream(node)
+    }
+
+    /// Adds a one-to-one operator which only keeps the values of a stream that satisfy `f`.
+    pub fn add_filter<T>(
+        &mut self,
+        name: impl Into<std::string::String>,
+        input: Stream<T>,
+        f: fn(T, Context) -> bool,
+    ) -> Stream<T>
+    where
+        T: Sharable + 'static,
+        T::T: Sendable<T = T>,
+    {
+        let inputs = vec![self.input(input)];
+        let deploy = Deploy::Stage(Stage::filter(f));
+        let node = self.add_node(name.into(), NodeKind::Operator, inputs, deploy);
+        self.stream(node)
+    }
+
+    /// Adds a node which transforms a stream, for example by launching an operator task.
+    pub fn add_operator<I, O, F>(
+        &mut self,
+        name: impl Into<std::string::String>,
+        input: Stream<I>,
+        f: F,
+    ) -> Stream<O>
+    where
+        I: Sharable + 'static,
+        O: Sharable + 'static,
+        F: FnOnce(Pullable<I>, Context) -> Pullable<O> + Send + 'static,
+    {
+        let inputs = vec![self.input(input)];
+        let deploy = Deploy::Task(Box::new(move |inputs, ctx| {
+            let input = downcast::<I>(inputs.into_iter().next().unwrap());
+            Some(Box::new(f(input, ctx)) as Output)
+        }));
+        let node = self.add_node(name.into(), NodeKind::Operator, inputs, deploy);
+        self.stream(node)
+    }
+
+    /// Adds a node which transforms two streams into one, for example by launching a join task.
+    pub fn add_binary_operator<A, B, O, F>(
+        &mut self,
+        name: impl Into<std::string::String>,
+        a: Stream<A>,
+        b: Stream<B>,
+        f: F,
+    ) -> Stream<O>
+    where
+        A: Sharable + 'static,
+        B: Sharable + 'static,
+        O: Sharable + 'static,
+        F: FnOnce(Pullable<A>, Pullable<B>, Context) -> Pullable<O> + Send + 'static,
+    {
+        let inputs = vec![self.input(a), self.input(b)];
+        let deploy = Deploy::Task(Box::new(move |inputs, ctx| {
+            let mut inputs = inputs.into_iter();
+            let a = downcast::<A>(inputs.next().unwrap());
+            let b = downcast::<B>(inputs.next().unwrap());
+            Some(Box::new(f(a, b, ctx)) as Output)
+        }));
+        let node = self.add_node(name.into(), NodeKind::Operator, inputs, deploy);
+        self.stream(node)
+    }
+
+    /// Adds a node which consumes a stream, for example by launching a sink task.
+    pub fn add_sink<T, F>(&mut self, name: impl Into<std::string::String>, input: Stream<T>, f: F)
+    where
+        T: Sharable + 'static,
+        F: FnOnce(Pullable<T>, Context) + Send + 'static,
+    {
+        let inputs = vec![self.input(input)];
+        let deploy = Deploy::Task(Box::new(move |inputs, ctx| {
+            f(downcast::<T>(inputs.into_iter().next().unwrap()), ctx);
+            None
+        }));
+        self.add_node(name.into(), NodeKind::Sink, inputs, deploy);
+    }
+
+    /// Returns the name and kind of a node.
+    pub fn node(&self, id: NodeId) -> (&str, NodeKind) {
+        let node = &self.nodes[id.0];
+        (&node.name, node.kind)
+    }
+
+    /// Checks that the graph can be deployed.
+    pub fn validate(&self) -> Result<(), DataflowError> {
+        if !self.nodes.iter().any(|n| n.kind == NodeKind::Source) {
+            return Err(DataflowError::NoSource);
+        }
+        if !self.nodes.iter().any(|n| n.kind == NodeKind::Sink) {
+            return Err(DataflowError::NoSink);
+        }
+        let mut names = std::collections::HashSet::new();
+        for node in &self.nodes {
+            if !names.insert(node.name.as_str()) {
+                return Err(DataflowError::DuplicateName(node.name.clone()));
+            }
+            if node.inputs.iter().any(|i| i.node.0 >= self.nodes.len()) {
+                return Err(DataflowError::ForeignStream(node.name.clone()));
+            }
+        }
+        let consumers = self.consumers();
+        for (node, consumers) in self.nodes.iter().zip(consumers) {
+            if node.kind != NodeKind::Sink && consumers == 0 {
+                return Err(DataflowError::Unconsumed(node.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the graph and deploys it onto the runtime. Nodes are deployed in the order in
+    /// which they were added, which is always a topological order of the graph. Returns handles
+    /// to the tasks of the deployed graph, through which it can be stopped or paused.
+    pub fn deploy(self, runtime: &Runtime) -> Result<std::vec::Vec<TaskHandle>, DataflowError> {
+        Ok(self.submit(runtime)?.tasks().to_vec())
+    }
+
+    /// Validates the graph and submits it to the runtime as a job, which completes once all
+    /// tasks of the graph have exited.
+    pub fn submit(self, runtime: &Runtime) -> Result<JobHandle, DataflowError> {
+        self.validate()?;
+        Ok(runtime.submit(move |ctx| self.run(ctx)))
+    }
+
+    /// Checks that every stream which is consumed can be sent between workers, so that the
+    /// graph can be placed on a cluster.
+    pub(crate) fn validate_sendable(&self) -> Result<(), DataflowError> {
+        let consumers = self.consumers();
+        for (node, consumers) in self.nodes.iter().zip(consumers) {
+            if consumers > 0 && node.bridge.is_none() {
+                return Err(DataflowError::Unsendable(node.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of nodes in the graph.
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn input<T>(&self, stream: Stream<T>) -> Input
+    where
+        T: Sharable + 'static,
+    {
+        let node = if stream.graph == self.id {
+            stream.node
+        } else {
+            // Validation reports edges which point outside of the graph.
+            NodeId(usize::MAX)
+        };
+        Input {
+            node,
+            fetch: fetch::<T>,
+        }
+    }
+
+    fn add_node(
+        &mut self,
+        name: std::string::String,
+        kind: NodeKind,
+        inputs: std::vec::Vec<Input>,
+        deploy: Deploy,
+    ) -> NodeId {
+        let node = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name,
+            kind,
+            inputs,
+            deploy,
+            fusible: true,
+            policy: RestartPolicy::Never,
+            retries: 0,
+            bridge: None,
+        });
+        node
+    }
+
+    fn stream<T: Sharable>(&self, node: NodeId) -> Stream<T> {
+        Stream {
+            graph: self.id,
+            node,
+            marker: PhantomData,
+        }
+    }
+
+    fn consumers(&self) -> std::vec::Vec<usize> {
+        let mut consumers = vec![0; self.nodes.len()];
+        for input in self.nodes.iter().flat_map(|n| n.inputs.iter()) {
+            if let Some(n) = consumers.get_mut(input.node.0) {
+                *n += 1;
+            }
+        }
+        consumers
+    }
+
+    /// Returns the node which `id` can be fused into, if any. This is the case when both nodes
+    /// are fusible one-to-one operators, and the output of `id` has exactly one consumer, which
+    /// is then the node that it is fused into.
+    fn fused_into(&self, id: usize, consumers: &[usize]) -> Option<usize> {
+        let node = &self.nodes[id];
+        if !self.fusion || !node.fusible || consumers[id] != 1 {
+            return None;
+        }
+        if !matches!(node.deploy, Deploy::Stage(_)) {
+            return None;
+        }
+        self.nodes.iter().position(|next| {
+            next.fusible
+                && matches!(next.deploy, Deploy::Stage(_))
+                && next.inputs.iter().any(|input| input.node.0 == id)
+        })
+    }
+
+    /// Deploys the graph onto a single worker.
+    fn run(self, ctx: Context) {
+        let mut placement = Placement::single(self.nodes.len());
+        self.run_placed(ctx, &mut placement);
+    }
+
+    /// Deploys the nodes of the graph which are placed on this worker. Streams from and to
+    /// nodes on other workers are imported and exported through `placement`.
+    pub(crate) fn run_placed(mut self, ctx: Context, placement: &mut Placement) {
+        // Fused chains cannot span workers.
+        self.fusion &= !placement.is_distributed();
+        let consumers = self.consumers();
+        let next = (0..self.nodes.len())
+            .map(|id| self.fused_into(id, &consumers))
+            .collect::<std::vec::Vec<_>>();
+        let mut fused = vec![false; self.nodes.len()];
+        next.iter().flatten().for_each(|id| fused[*id] = true);
+        let bridges = self
+            .nodes
+            .iter()
+            .map(|node| node.bridge)
+            .collect::<std::vec::Vec<_>>();
+        let mut slots = consumers
+            .into_iter()
+            .map(|consumers| Slot {
+                output: None,
+                consumers,
+            })
+            .collect::<std::vec::Vec<_>>();
+        let mut nodes = self
+            .nodes
+            .into_iter()
+            .map(Some)
+            .collect::<std::vec::Vec<_>>();
+        if let Some(NodeId(id)) = self.dead_letters {
+            // Only the tasks on the worker of the dead-letter node can route to it.
+            if placement.is_local(id) {
+                let (o, i): (_, Pullable<DeadLetter>) = channel(ctx);
+                ctx.set_dead_letter_sink(Some(o));
+                slots[id].output = Some(Box::new(i));
+            }
+            nodes[id] = None;
+        }
+        for id in 0..nodes.len() {
+            // Fused operators are deployed together with the first operator of their chain.
+            if fused[id] || nodes[id].is_none() {
+                continue;
+            }
+            let node = nodes[id].take().unwrap();
+            let mut inputs = std::vec::Vec::new();
+            for (index, input) in node.inputs.iter().enumerate() {
+                let edge = (id, index);
+                let upstream = input.node.0;
+                let bridge = || bridges[upstream].expect("stream cannot be sent between workers");
+                match (placement.is_local(upstream), placement.is_local(id)) {
+                    (true, true) => inputs.push((input.fetch)(&mut slots[upstream])),
+                    (true, false) => {
+                        let output = (input.fetch)(&mut slots[upstream]);
+                        (bridge().export)(output, placement.link(id, edge), ctx);
+                    }
+                    (false, true) => {
+                        let (import, output) = (bridge().import)(ctx);
+                        placement.imports.push((edge, import));
+                        inputs.push(output);
+                    }
+                    (false, false) => {}
+                }
+            }
+            if !placement.is_local(id) {
+                continue;
+            }
+            ctx.set_restart_policy(node.policy);
+            ctx.set_retries(node.retries);
+            match node.deploy {
+                Deploy::Task(deploy) => {
+                    ctx.set_task_name(Some(node.name));
+                    slots[id].output = deploy(inputs, ctx);
+                }
+                Deploy::Stage(stage) => {
+                    let mut chain = vec![stage];
+                    let mut names = vec![node.name];
+                    let mut last = id;
+                    while let Some(id) = next[last] {
+                        let node = nodes[id].take().unwrap();
+                        match node.deploy {
+                            Deploy::Stage(stage) => chain.push(stage),
+                            Deploy::Task(_) => unreachable!(),
+                        }
+                        names.push(node.name);
+                        last = id;
+                    }
+                    // A fused chain is named after all of its operators.
+                    ctx.set_task_name(Some(names.join(" -> ")));
+                    let input = inputs.into_iter().next().unwrap();
+                    slots[last].output = Some(fusion::deploy(chain, input, ctx));
+                }
+            }
+        }
+        // Every task holds its own sink, so that the dead letters close once all tasks exited.
+        ctx.set_dead_letter_sink(None);
+        ctx.set_task_name(None);
+    }
+}
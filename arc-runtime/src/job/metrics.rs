@@ -0,0 +1,69 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// A counter which is kept for every job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// Tasks launched by the job, not counting restarts.
+    TasksLaunched,
+    /// Restarts of tasks after a failure.
+    TaskRestarts,
+    /// Tasks which failed, whether or not they were restarted afterwards.
+    TaskFailures,
+    /// Records which were routed to dead letters.
+    DeadLetters,
+    /// Objects allocated on the garbage-collected heaps of the job.
+    HeapAllocations,
+    /// Bytes allocated on the garbage-collected heaps of the job, as reported by the
+    /// `allocation_size` of each object.
+    HeapBytes,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 6] = [
+        Metric::TasksLaunched,
+        Metric::TaskRestarts,
+        Metric::TaskFailures,
+        Metric::DeadLetters,
+        Metric::HeapAllocations,
+        Metric::HeapBytes,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::TasksLaunched => "tasks.launched",
+            Metric::TaskRestarts => "tasks.restarts",
+            Metric::TaskFailures => "tasks.failures",
+            Metric::DeadLetters => "dead_letters",
+            Metric::HeapAllocations => "heap.allocations",
+            Metric::HeapBytes => "heap.bytes",
+        }
+    }
+}
+
+/// The metrics of a single job.
+#[derive(Debug, Default)]
+pub struct JobMetrics {
+    counters: [AtomicU64; Metric::ALL.len()],
+}
+
+impl JobMetrics {
+    pub fn get(&self, metric: Metric) -> u64 {
+        self.counters[metric as usize].load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add(&self, metric: Metric, n: u64) {
+        self.counters[metric as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Returns the value of every metric, named within `namespace`.
+    pub fn snapshot(&self, namespace: &str) -> std::vec::Vec<(std::string::String, u64)> {
+        Metric::ALL
+            .iter()
+            .map(|metric| {
+                let name = format!("{}.{}", namespace, metric.name());
+                (name, self.get(*metric))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,193 @@
+//! Jobs, which are the unit of work submitted to a [`Runtime`](crate::runtime::Runtime).
+//!
+//! A job is a function which is run inside the runtime with a fresh [`Context`]. Every task
+//! which it launches through the context is supervised by the job, which completes once all of
+//! them have exited.
+//!
+//! Jobs in the same runtime are isolated from each other: each has its own tasks, metrics and
+//! cancellation scope, and tasks which fail or escalate only affect their own job.
+
+pub mod metrics;
+
+use crate::control::TaskError;
+use crate::job::metrics::JobMetrics;
+use crate::job::metrics::Metric;
+use crate::prelude::*;
+use crate::task::supervisor::Supervisor;
+
+use kompact::prelude::*;
+
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+/// Identifies a job within a runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(pub(crate) u64);
+
+impl std::fmt::Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "job-{}", self.0)
+    }
+}
+
+/// The status of a job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    /// Some tasks of the job are still running, or waiting to be restarted.
+    Running,
+    /// All tasks of the job finished.
+    Finished,
+    /// The job failed, either while launching its tasks or because one of them failed.
+    Failed(TaskError),
+    /// The job was cancelled, or one of its tasks was killed.
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Returns the exit code of a process whose job ended with this status.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            JobStatus::Running | JobStatus::Finished => 0,
+            JobStatus::Failed(_) => 1,
+            JobStatus::Cancelled => 2,
+        }
+    }
+}
+
+/// A handle to a submitted job.
+#[derive(Clone)]
+pub struct JobHandle(Arc<Core>);
+
+struct Core {
+    id: JobId,
+    supervisor: Supervisor,
+    tasks: std::vec::Vec<TaskHandle>,
+    /// The error which the body of the job panicked with, if any.
+    error: Option<TaskError>,
+    cancelled: AtomicBool,
+}
+
+impl JobHandle {
+    pub(crate) fn new(
+        id: JobId,
+        supervisor: Supervisor,
+        tasks: std::vec::Vec<TaskHandle>,
+        error: Option<TaskError>,
+    ) -> Self {
+        Self(Arc::new(Core {
+            id,
+            supervisor,
+            tasks,
+            error,
+            cancelled: AtomicBool::new(false),
+        }))
+    }
+
+    pub fn id(&self) -> JobId {
+        self.0.id
+    }
+
+    /// Returns handles to the tasks which the job launched.
+    pub fn tasks(&self) -> &[TaskHandle] {
+        &self.0.tasks
+    }
+
+    pub fn status(&self) -> JobStatus {
+        if let Some(error) = &self.0.error {
+            return JobStatus::Failed(error.clone());
+        }
+        let tasks = self.0.supervisor.tasks();
+        let statuses = tasks
+            .iter()
+            .map(|task| task.status())
+            .collect::<std::vec::Vec<_>>();
+        let any = |status| statuses.contains(&status);
+        if any(TaskStatus::Running) || any(TaskStatus::Restarting) {
+            return JobStatus::Running;
+        }
+        if self.0.cancelled.load(Ordering::SeqCst) {
+            return JobStatus::Cancelled;
+        }
+        if any(TaskStatus::Failed) {
+            let error = tasks.iter().find_map(|task| task.error());
+            return JobStatus::Failed(error.unwrap_or_else(|| TaskError::new("Task failed")));
+        }
+        if any(TaskStatus::Killed) {
+            return JobStatus::Cancelled;
+        }
+        JobStatus::Finished
+    }
+
+    /// Blocks until all tasks of the job have exited, and returns the final status of the job.
+    pub fn await_completion(&self) -> JobStatus {
+        let mut status = JobStatus::Running;
+        self.0.supervisor.wait_until(|| {
+            status = self.status();
+            status != JobStatus::Running
+        });
+        status
+    }
+
+    pub fn metric(&self, metric: Metric) -> u64 {
+        self.0.supervisor.metrics().get(metric)
+    }
+
+    /// Returns the value of every metric of the job, named within the namespace of the job,
+    /// such as `job-0.tasks.launched`.
+    pub fn metrics(&self) -> std::vec::Vec<(std::string::String, u64)> {
+        self.0.supervisor.metrics().snapshot(&self.0.id.to_string())
+    }
+
+    /// Kills all tasks of the job.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.supervisor.kill_all();
+    }
+}
+
+/// A short-lived component which runs the body of a job from inside the runtime.
+#[derive(ComponentDefinition, Actor)]
+pub(crate) struct Launcher {
+    ctx: ComponentContext<Self>,
+    id: JobId,
+    body: Option<Box<dyn FnOnce(Context) + Send>>,
+    job: std::sync::mpsc::Sender<JobHandle>,
+}
+
+impl Launcher {
+    pub(crate) fn new(
+        id: JobId,
+        body: Box<dyn FnOnce(Context) + Send>,
+        job: std::sync::mpsc::Sender<JobHandle>,
+    ) -> Self {
+        Self {
+            ctx: ComponentContext::uninitialised(),
+            id,
+            body: Some(body),
+            job,
+        }
+    }
+}
+
+impl ComponentLifecycle for Launcher {
+    fn on_start(&mut self) -> Handled {
+        let component = self.ctx().component();
+        let mutator = instantiate_immix(ImmixOptions::default());
+        let ctx = Context::for_job(component, mutator, Supervisor::for_job(self.id));
+        let body = self.body.take().unwrap();
+        let error = std::panic::catch_unwind(AssertUnwindSafe(|| body(ctx)))
+            .err()
+            .map(|panic| TaskError::from_panic(&*panic));
+        let job = JobHandle::new(
+            self.id,
+            ctx.supervisor().clone(),
+            ctx.launched().to_vec(),
+            error,
+        );
+        // The launched tasks have moved their inputs onto their own heaps.
+        ctx.destroy();
+        self.job.send(job).unwrap();
+        Handled::DieNow
+    }
+}
@@ -14,9 +14,12 @@
 #![allow(clippy::wrong_self_convention)]
 #![allow(clippy::len_without_is_empty)]
 
+pub mod cluster;
 pub mod context;
 pub mod control;
 pub mod data;
+pub mod dataflow;
+pub mod job;
 pub mod macros;
 pub mod operators;
 pub mod runtime;
@@ -28,12 +31,35 @@ pub mod prelude {
     pub use crate::context::Context;
     pub use crate::control::Control;
     pub use crate::control::Control::Continue;
+    pub use crate::control::Control::Failed;
     pub use crate::control::Control::Finished;
+    pub use crate::control::TaskError;
     pub use crate::data::channels;
     pub use crate::data::channels::Channel;
     pub use crate::data::erased::Erased;
+    pub use crate::data::erased::ErasedKey;
+    pub use crate::data::erased::ErasedType;
+    pub use crate::data::erased::ErasedVtable;
+    pub use crate::data::erased::KeyError;
+    pub use crate::data::erased::UneraseError;
+    pub use crate::data::format::to_string;
+    pub use crate::data::format::Format;
+    pub use crate::data::format::Formatted;
+    pub use crate::data::functions::Append;
+    pub use crate::data::functions::Callable;
+    pub use crate::data::functions::FunctionError;
+    pub use crate::data::functions::FunctionKey;
     pub use crate::data::garbage::Alloc;
     pub use crate::data::garbage::Gc;
+    pub use crate::data::maps::contains_hash_map;
+    pub use crate::data::maps::get_hash_map;
+    pub use crate::data::maps::insert_hash_map;
+    pub use crate::data::maps::keys_hash_map;
+    pub use crate::data::maps::len_hash_map;
+    pub use crate::data::maps::new_hash_map;
+    pub use crate::data::maps::remove_hash_map;
+    pub use crate::data::maps::values_hash_map;
+    pub use crate::data::maps::HashMap;
     pub use crate::data::primitives::bool;
     pub use crate::data::primitives::char;
     pub use crate::data::primitives::f32;
@@ -50,7 +76,29 @@ pub mod prelude {
     pub use crate::data::primitives::u8;
     pub use crate::data::primitives::unit;
     pub use crate::data::primitives::Unit;
+    pub use crate::data::regex::captures;
+    pub use crate::data::regex::find_all;
+    pub use crate::data::regex::is_match;
+    pub use crate::data::regex::regex;
+    pub use crate::data::regex::replace_all;
+    pub use crate::data::regex::Regex;
+    pub use crate::data::schema::Schema;
+    pub use crate::data::schema::SchemaWriter;
+    pub use crate::data::sets::contains_hash_set;
+    pub use crate::data::sets::insert_hash_set;
+    pub use crate::data::sets::len_hash_set;
+    pub use crate::data::sets::new_hash_set;
+    pub use crate::data::sets::remove_hash_set;
+    pub use crate::data::sets::values_hash_set;
+    pub use crate::data::sets::HashSet;
     pub use crate::data::strings::String;
+    pub use crate::data::vectors::array;
+    pub use crate::data::vectors::contains;
+    pub use crate::data::vectors::extend;
+    pub use crate::data::vectors::len;
+    pub use crate::data::vectors::pop;
+    pub use crate::data::vectors::push;
+    pub use crate::data::vectors::select;
     pub use crate::data::vectors::Vec;
     pub use crate::data::Abstract;
     pub use crate::data::Concrete;
@@ -58,17 +106,33 @@ pub mod prelude {
     pub use crate::data::DynSharable;
     pub use crate::data::Sendable;
     pub use crate::data::Sharable;
+    pub use crate::dataflow::DataflowBuilder;
+    pub use crate::job::JobHandle;
+    pub use crate::job::JobId;
+    pub use crate::job::JobStatus;
     pub use crate::runtime::Runtime;
+    pub use crate::task::dead_letter::protect;
+    pub use crate::task::dead_letter::DeadLetter;
+    pub use crate::task::handle::TaskHandle;
     pub use crate::task::message::TaskMessage;
+    pub use crate::task::state::HandBack;
+    pub use crate::task::state::TaskState;
+    pub use crate::task::state::TaskStatus;
+    pub use crate::task::supervisor::RestartPolicy;
 
     // Declarative macros
     pub use crate::access;
+    #[cfg(not(feature = "legacy"))]
+    pub use crate::closure;
     pub use crate::convert_reflexive;
     pub use crate::declare_functions;
     pub use crate::fun_type;
     pub use crate::function;
     pub use crate::letroot;
+    #[cfg(not(feature = "legacy"))]
+    pub use crate::update;
     pub use crate::val;
+    pub use crate::with;
 
     // Hidden macros
     pub use crate::_vector;
@@ -76,9 +140,11 @@ pub mod prelude {
     // Procedural macros
     pub use macros::call;
     pub use macros::call_indirect;
+    pub use macros::call_protected;
     pub use macros::enwrap;
     pub use macros::erase;
     pub use macros::is;
+    pub use macros::match_enum;
     pub use macros::new;
     pub use macros::pull;
     pub use macros::pull_transition;
@@ -104,6 +170,7 @@ pub mod prelude {
     pub use macros::Unpin;
 
     // Re-exports
+    pub use kompact::prelude::error;
     pub use kompact::prelude::info;
     pub use kompact::prelude::warn;
     pub use kompact::prelude::Actor;
@@ -160,6 +227,7 @@ pub mod prelude {
     pub use hexf::hexf64;
 
     pub use serde::de::DeserializeOwned;
+    pub use serde::de::Error as DeserializeError;
     pub use serde::Deserialize;
     pub use serde::Deserializer;
     pub use serde::Serialize;
@@ -47,6 +47,68 @@ macro_rules! access {
     };
 }
 
+/// Update a struct's field in place. The struct is mutated through its pointer, so all copies
+/// of the struct observe the update.
+///
+/// ```
+/// use arc_runtime::prelude::*;
+/// #[rewrite]
+/// pub struct Bar {
+///     pub x: i32,
+///     pub y: i32
+/// }
+/// #[rewrite(main)]
+/// fn main() {
+///     let a = new!(Bar { x: 0, y: 1 });
+///     update!(a, x, 2);
+/// }
+/// ```
+#[cfg(not(feature = "legacy"))]
+#[macro_export]
+macro_rules! update {
+    ($arg:expr, $field:tt, $value:expr) => {{
+        let value = $value;
+        let mut data = $arg.0.clone();
+        data.$field = value;
+        data.write_barrier(ctx);
+    }};
+}
+
+/// Copy a struct with some of its fields replaced.
+///
+/// ```
+/// use arc_runtime::prelude::*;
+/// #[rewrite]
+/// pub struct Bar {
+///     pub x: i32,
+///     pub y: i32
+/// }
+/// #[rewrite(main)]
+/// fn main() {
+///     let a = new!(Bar { x: 0, y: 1 });
+///     let b = with!(a, x = 2, y = 3);
+/// }
+/// ```
+#[cfg(feature = "legacy")]
+#[macro_export]
+macro_rules! with {
+    ($arg:expr, $($field:tt = $value:expr),+ $(,)?) => {{
+        let mut data = (*$arg.0).clone();
+        $(data.$field = $value;)+
+        data.into()
+    }};
+}
+
+#[cfg(not(feature = "legacy"))]
+#[macro_export]
+macro_rules! with {
+    ($arg:expr, $($field:tt = $value:expr),+ $(,)?) => {{
+        let mut data = (*$arg.0).clone();
+        $(data.$field = $value;)+
+        data.alloc(ctx)
+    }};
+}
+
 /// Construct a new function type.
 #[macro_export]
 macro_rules! fun_type {
@@ -107,7 +169,7 @@ macro_rules! _vector {
 
         $(
             vec.as_mut().unwrap().0.push($ctx.mutator(), $x);
-            vec.as_mut().unwrap().0.write_barrier($ctx.mutator());
+            (*vec.as_mut().unwrap().0).write_barrier($ctx.mutator());
         )*
         vec.take().unwrap()
     }}
@@ -3,14 +3,86 @@ use comet::immix::ImmixOptions;
 use comet::mutator::MutatorRef;
 use kompact::prelude::*;
 
+use crate::context::Context;
+use crate::job::JobHandle;
+use crate::job::JobId;
+use crate::job::JobStatus;
+use crate::job::Launcher;
+
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+/// Hosts any number of jobs, which share the threads of the runtime.
 pub struct Runtime {
     pub system: KompactSystem,
+    next_job: AtomicU64,
+    /// The jobs which have not been observed to complete yet.
+    jobs: Mutex<Vec<JobHandle>>,
 }
 
 impl Runtime {
     pub fn new() -> Self {
-        let system = KompactConfig::default().build().unwrap();
-        Self { system }
+        Self::with_system(KompactConfig::default().build().unwrap())
+    }
+
+    /// Creates a runtime which listens for messages from other runtimes on `addr`, such as the
+    /// master and workers of a cluster.
+    pub fn with_network(addr: SocketAddr) -> Self {
+        let mut cfg = KompactConfig::default();
+        cfg.system_components(DeadletterBox::new, NetworkConfig::new(addr).build());
+        Self::with_system(cfg.build().expect("Failed to start the network"))
+    }
+
+    fn with_system(system: KompactSystem) -> Self {
+        Self {
+            system,
+            next_job: AtomicU64::new(0),
+            jobs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs `job` inside the runtime and returns a handle to it, once `job` has returned. The
+    /// tasks which `job` launches through its context belong to the job.
+    pub fn submit<F>(&self, job: F) -> JobHandle
+    where
+        F: FnOnce(Context) + Send + 'static,
+    {
+        let id = JobId(self.next_job.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let launcher = self
+            .system
+            .create(move || Launcher::new(id, Box::new(job), tx));
+        self.system.start_notify(&launcher).wait();
+        let job = rx.recv().unwrap();
+        self.jobs.lock().unwrap().push(job.clone());
+        job
+    }
+
+    /// Returns the jobs which are still running.
+    pub fn jobs(&self) -> Vec<JobHandle> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job| job.status() == JobStatus::Running);
+        jobs.clone()
+    }
+
+    /// Returns a running job.
+    pub fn job(&self, id: JobId) -> Option<JobHandle> {
+        self.jobs().into_iter().find(|job| job.id() == id)
+    }
+
+    /// Returns the address which other runtimes reach this runtime on.
+    pub fn address(&self) -> SocketAddr {
+        let path = self.system.system_path();
+        SocketAddr::new(*path.address(), path.port())
+    }
+
+    /// Stops all components of the runtime.
+    pub fn shutdown(self) {
+        self.system
+            .shutdown()
+            .expect("Failed to shut down the runtime");
     }
 }
 
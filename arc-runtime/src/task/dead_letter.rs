@@ -0,0 +1,77 @@
+//! Dead-letter routing for records which fail in user functions.
+//!
+//! When a function called through `call_protected!` panics, the call is retried with the same
+//! record. Once the retries of the task are used up, the record is wrapped into a [`DeadLetter`]
+//! and pushed into the dead-letter channel of the task, and the task moves on to its next
+//! record. A task without a dead-letter channel fails as before.
+
+use crate::data::channels::local::multicast::Pushable;
+use crate::data::convert_reflexive;
+use crate::job::metrics::Metric;
+use crate::prelude::*;
+
+use std::panic::AssertUnwindSafe;
+
+/// A record which a task failed to process.
+#[derive(Clone, Debug, Serialize, Deserialize, Collectable, Finalize, NoTrace)]
+pub struct DeadLetter {
+    /// The name of the task.
+    pub task: std::string::String,
+    /// The error of the last attempt.
+    pub error: std::string::String,
+    /// The record, formatted with `Debug`.
+    pub record: std::string::String,
+    /// How many times processing the record was attempted.
+    pub attempts: usize,
+}
+
+convert_reflexive!(DeadLetter);
+
+/// How a task handles records which fail.
+#[derive(Clone, Default)]
+pub struct DeadLetters {
+    /// How many times a failed call is retried before the record is routed.
+    pub retries: usize,
+    /// Where failed records are routed.
+    pub sink: Option<Pushable<DeadLetter>>,
+    /// The name of the task in its dataflow graph, which is recorded in its dead letters.
+    pub task: Option<std::string::String>,
+}
+
+/// Calls `f` on `input`, retrying and then routing `input` to the dead letters of the task if
+/// the call panics. Returns `None` if the record was routed.
+pub fn protect<I, O>(f: fn(I, Context) -> O, input: I, ctx: Context) -> Option<O>
+where
+    I: Clone + Debug,
+{
+    let policy = ctx.state().dead_letters();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let record = input.clone();
+        let panic = match std::panic::catch_unwind(AssertUnwindSafe(|| f(record, ctx))) {
+            Ok(output) => return Some(output),
+            Err(panic) => panic,
+        };
+        if attempts <= policy.retries {
+            continue;
+        }
+        let sink = match &policy.sink {
+            Some(sink) => sink,
+            None => std::panic::resume_unwind(panic),
+        };
+        let task = match &policy.task {
+            Some(task) => task.clone(),
+            None => ctx.component().type_name().to_string(),
+        };
+        let letter = DeadLetter {
+            task,
+            error: TaskError::from_panic(&*panic).message().to_string(),
+            record: format!("{:?}", input),
+            attempts,
+        };
+        sink.try_push(letter, ctx);
+        ctx.supervisor().metrics().add(Metric::DeadLetters, 1);
+        return None;
+    }
+}
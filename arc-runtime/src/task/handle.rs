@@ -0,0 +1,76 @@
+use crate::control::TaskError;
+use crate::task::message::TaskMessage;
+use crate::task::state::TaskState;
+use crate::task::state::TaskStatus;
+
+use kompact::prelude::*;
+
+use std::sync::Arc;
+
+/// A handle to a launched task, through which it can be controlled from the outside.
+#[derive(Clone)]
+pub struct TaskHandle(Target);
+
+#[derive(Clone)]
+enum Target {
+    /// A component which handles its own messages.
+    Actor(ActorRef<TaskMessage>),
+    /// A supervised task, whose state outlives restarts of its component. Messages are applied
+    /// to the state directly, so that they also reach a task which is being restarted.
+    Supervised(Arc<TaskState>),
+}
+
+impl TaskHandle {
+    pub(crate) fn new(actor: ActorRef<TaskMessage>) -> Self {
+        Self(Target::Actor(actor))
+    }
+
+    pub(crate) fn supervised(state: Arc<TaskState>) -> Self {
+        Self(Target::Supervised(state))
+    }
+
+    fn tell(&self, msg: TaskMessage) {
+        match &self.0 {
+            Target::Actor(actor) => actor.tell(msg),
+            Target::Supervised(state) => state.handle(msg),
+        }
+    }
+
+    /// Returns the status of the task, if it is supervised.
+    pub fn status(&self) -> Option<TaskStatus> {
+        match &self.0 {
+            Target::Actor(_) => None,
+            Target::Supervised(state) => Some(state.status()),
+        }
+    }
+
+    /// Returns the error which the task failed with, if it is supervised and failed with an
+    /// error.
+    pub fn error(&self) -> Option<TaskError> {
+        match &self.0 {
+            Target::Actor(_) => None,
+            Target::Supervised(state) => state.error(),
+        }
+    }
+
+    /// Cancels the task and drops its channels.
+    pub fn kill(&self) {
+        self.tell(TaskMessage::Kill);
+    }
+
+    /// Stops the task from making progress until it is resumed.
+    pub fn pause(&self) {
+        self.tell(TaskMessage::Pause);
+    }
+
+    /// Resumes a paused task.
+    pub fn resume(&self) {
+        self.tell(TaskMessage::Resume);
+    }
+
+    /// Lets the task process the data which is already in its input channels, and then
+    /// terminates it.
+    pub fn drain(&self) {
+        self.tell(TaskMessage::Drain);
+    }
+}
@@ -1,4 +1,11 @@
 #[derive(Debug)]
 pub enum TaskMessage {
+    /// Cancels the task and drops its channels.
     Kill,
+    /// Stops the task from making progress.
+    Pause,
+    /// Resumes a paused task.
+    Resume,
+    /// Stops the task once its input channels are empty.
+    Drain,
 }
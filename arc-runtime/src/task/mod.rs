@@ -1 +1,5 @@
+pub mod dead_letter;
+pub mod handle;
 pub mod message;
+pub mod state;
+pub mod supervisor;
@@ -0,0 +1,360 @@
+use crate::control::Outcome;
+use crate::control::TaskError;
+use crate::job::metrics::Metric;
+use crate::task::dead_letter::DeadLetters;
+use crate::task::message::TaskMessage;
+use crate::task::supervisor::Supervisor;
+use crate::task::supervisor::WeakSupervisor;
+
+use futures::task::AtomicWaker;
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::task::Context as PollContext;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long a checkpointing task waits after saving a checkpoint before it saves the next one.
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many transitions a persistent task takes in one poll before it yields. A task whose
+/// futures are always ready would otherwise never return to the executor, which would starve
+/// other components and the control messages of the task itself.
+pub const STEP_BUDGET: usize = 128;
+
+/// The status of a task, as observed by its supervisor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    Running,
+    /// The task failed and is waiting to be restarted.
+    Restarting,
+    /// The body of the task completed.
+    Finished,
+    /// The task was killed through a `TaskMessage::Kill`.
+    Killed,
+    /// The task failed and was not restarted, or its body returned an error.
+    Failed,
+}
+
+impl Default for TaskStatus {
+    fn default() -> Self {
+        TaskStatus::Running
+    }
+}
+
+/// The control state of a task, shared between its component (which receives messages) and
+/// its async body (which acts on them). The state outlives restarts of the task.
+#[derive(Default)]
+pub struct TaskState {
+    killed: AtomicBool,
+    paused: AtomicBool,
+    draining: AtomicBool,
+    waker: AtomicWaker,
+    status: Mutex<TaskStatus>,
+    error: Mutex<Option<TaskError>>,
+    /// The panic which the latest instance of the task failed with, until its supervisor takes it.
+    panic: Mutex<Option<TaskError>>,
+    checkpointing: AtomicBool,
+    /// When the latest checkpoint was saved.
+    checkpointed: Mutex<Option<Instant>>,
+    checkpoint: Mutex<Option<Box<dyn Any + Send>>>,
+    /// The channels which a failed task handed back, by name, for when it is restarted.
+    channels: Mutex<HashMap<&'static str, Box<dyn Any + Send>>>,
+    dead_letters: Mutex<DeadLetters>,
+    supervisor: WeakSupervisor,
+}
+
+impl std::fmt::Debug for TaskState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskState")
+            .field("killed", &self.is_killed())
+            .field("paused", &self.is_paused())
+            .field("draining", &self.is_draining())
+            .field("status", &self.status())
+            .field("error", &self.error())
+            .finish()
+    }
+}
+
+impl TaskState {
+    /// Creates the state of a supervised task, which handles failed records according to
+    /// `dead_letters` and notifies `supervisor` whenever its status changes.
+    pub(crate) fn supervised(dead_letters: DeadLetters, supervisor: WeakSupervisor) -> Self {
+        Self {
+            dead_letters: Mutex::new(dead_letters),
+            supervisor,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the supervisor of the task, if it is supervised and its supervisor is alive.
+    pub fn supervisor(&self) -> Option<Supervisor> {
+        self.supervisor.upgrade()
+    }
+
+    /// Updates the state according to a message, and wakes up the task so that it can act on it.
+    pub fn handle(&self, msg: TaskMessage) {
+        match msg {
+            TaskMessage::Kill => self.killed.store(true, Ordering::SeqCst),
+            TaskMessage::Pause => self.paused.store(true, Ordering::SeqCst),
+            TaskMessage::Resume => self.paused.store(false, Ordering::SeqCst),
+            TaskMessage::Drain => {
+                // A paused task would never finish draining.
+                self.paused.store(false, Ordering::SeqCst);
+                self.draining.store(true, Ordering::SeqCst);
+            }
+        }
+        self.waker.wake();
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    pub fn status(&self) -> TaskStatus {
+        *self.status.lock().unwrap()
+    }
+
+    pub fn set_status(&self, status: TaskStatus) {
+        *self.status.lock().unwrap() = status;
+        self.notify();
+    }
+
+    /// Returns the error which the task failed with, if it failed with an error.
+    pub fn error(&self) -> Option<TaskError> {
+        self.error.lock().unwrap().clone()
+    }
+
+    /// Marks the task as failed with an error.
+    pub fn fail(&self, error: TaskError) {
+        if let Some(supervisor) = self.supervisor() {
+            supervisor.metrics().add(Metric::TaskFailures, 1);
+        }
+        *self.error.lock().unwrap() = Some(error);
+        self.set_status(TaskStatus::Failed);
+    }
+
+    /// Takes the panic which the latest instance of the task failed with, if it panicked.
+    pub(crate) fn take_panic(&self) -> Option<TaskError> {
+        self.panic.lock().unwrap().take()
+    }
+
+    /// Marks the task as finished, unless it has already failed.
+    fn finish(&self) {
+        let mut status = self.status.lock().unwrap();
+        if *status != TaskStatus::Failed {
+            *status = TaskStatus::Finished;
+        }
+        drop(status);
+        self.notify();
+    }
+
+    fn notify(&self) {
+        if let Some(supervisor) = self.supervisor() {
+            supervisor.notify();
+        }
+    }
+
+    /// Drops the channels which the state holds on behalf of the task, once the task has
+    /// exited, so that they can close.
+    pub(crate) fn release(&self) {
+        self.dead_letters.lock().unwrap().sink = None;
+        self.channels.lock().unwrap().clear();
+    }
+
+    /// Keeps a channel of the task while the task may still be restarted, so that it can be
+    /// handed back to the restarted task.
+    fn hand_back(&self, name: &'static str, channel: Box<dyn Any + Send>) {
+        if matches!(self.status(), TaskStatus::Running | TaskStatus::Restarting) {
+            self.channels.lock().unwrap().insert(name, channel);
+        }
+    }
+
+    /// Takes the channel `name` which a failed instance of the task handed back, if any.
+    pub fn take_channel<T: Send + 'static>(&self, name: &'static str) -> Option<T> {
+        let channel = self.channels.lock().unwrap().remove(name)?;
+        channel.downcast().ok().map(|channel| *channel)
+    }
+
+    /// Returns true if the task should save checkpoints, so that it can be restored when
+    /// restarted after a failure.
+    pub fn is_checkpointing(&self) -> bool {
+        self.checkpointing.load(Ordering::SeqCst)
+    }
+
+    pub fn set_checkpointing(&self, checkpointing: bool) {
+        self.checkpointing.store(checkpointing, Ordering::SeqCst);
+    }
+
+    /// Returns true if the task should save a checkpoint now, which is the case when it is
+    /// checkpointing and has not saved one within the last `CHECKPOINT_INTERVAL`.
+    pub fn is_checkpoint_due(&self) -> bool {
+        let checkpointed = *self.checkpointed.lock().unwrap();
+        self.is_checkpointing()
+            && checkpointed.map_or(true, |at| at.elapsed() >= CHECKPOINT_INTERVAL)
+    }
+
+    /// Replaces the latest checkpoint of the task.
+    pub fn save_checkpoint<T: Send + 'static>(&self, checkpoint: T) {
+        *self.checkpoint.lock().unwrap() = Some(Box::new(checkpoint));
+        *self.checkpointed.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Takes the latest checkpoint of the task, if it has one of type `T`.
+    pub fn take_checkpoint<T: Send + 'static>(&self) -> Option<T> {
+        let checkpoint = self.checkpoint.lock().unwrap().take()?;
+        checkpoint.downcast().ok().map(|checkpoint| *checkpoint)
+    }
+
+    pub fn dead_letters(&self) -> DeadLetters {
+        self.dead_letters.lock().unwrap().clone()
+    }
+
+    /// Wraps the body of a task so that it is controlled by this state.
+    pub fn control<F>(self: &Arc<Self>, future: F) -> Controlled<F>
+    where
+        F: Future,
+        F::Output: Outcome,
+    {
+        Controlled {
+            state: self.clone(),
+            future: Some(Box::pin(future)),
+        }
+    }
+}
+
+/// A channel of a task, which is handed back to the task when it is restarted after a failure.
+/// A restarted task would otherwise subscribe a new receiver to its input, and lose the values
+/// which were buffered in the receiver of the failed task.
+pub struct HandBack<T: Send + 'static> {
+    name: &'static str,
+    channel: Option<T>,
+    state: Arc<TaskState>,
+}
+
+impl<T: Send + 'static> HandBack<T> {
+    /// Wraps the channel `name` of a task. This is the channel which a failed instance of the
+    /// task handed back if there is one, and otherwise `channel`.
+    pub fn new(name: &'static str, channel: T, state: &Arc<TaskState>) -> Self {
+        let channel = state.take_channel(name).unwrap_or(channel);
+        Self {
+            name,
+            channel: Some(channel),
+            state: state.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Deref for HandBack<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.channel.as_ref().unwrap()
+    }
+}
+
+impl<T: Send + 'static> DerefMut for HandBack<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.channel.as_mut().unwrap()
+    }
+}
+
+impl<T: Send + 'static> Drop for HandBack<T> {
+    fn drop(&mut self) {
+        if let Some(channel) = self.channel.take() {
+            self.state.hand_back(self.name, Box::new(channel));
+        }
+    }
+}
+
+/// The body of a task, which is not polled while the task is paused, and which is dropped as
+/// soon as the task is killed. Resolves to the final status of the task.
+pub struct Controlled<F: Future> {
+    state: Arc<TaskState>,
+    future: Option<Pin<Box<F>>>,
+}
+
+impl<F> Future for Controlled<F>
+where
+    F: Future,
+    F::Output: Outcome,
+{
+    type Output = TaskStatus;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut PollContext) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.state.waker.register(cx.waker());
+        if this.state.is_killed() {
+            // Dropping the body drops its channels.
+            this.future = None;
+            this.state.release();
+            this.state.set_status(TaskStatus::Killed);
+            return Poll::Ready(TaskStatus::Killed);
+        }
+        if this.state.is_paused() {
+            return Poll::Pending;
+        }
+        if let Some(future) = this.future.as_mut() {
+            // The panic is recorded for the supervisor, which only learns that the component
+            // faulted, and is then resumed so that the component faults.
+            let poll = std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx)))
+                .unwrap_or_else(|panic| {
+                    *this.state.panic.lock().unwrap() = Some(TaskError::from_panic(&*panic));
+                    std::panic::resume_unwind(panic)
+                });
+            if let Poll::Ready(output) = poll {
+                this.future = None;
+                this.state.release();
+                match output.into_error() {
+                    Some(error) => this.state.fail(error),
+                    None => this.state.finish(),
+                }
+            } else {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(this.state.status())
+    }
+}
+
+/// Raised whenever the status of one of a group of tasks changes, so that a thread can wait
+/// for the group to reach some condition.
+#[derive(Default)]
+pub(crate) struct StatusSignal {
+    changes: Mutex<usize>,
+    changed: Condvar,
+}
+
+impl StatusSignal {
+    pub(crate) fn notify(&self) {
+        *self.changes.lock().unwrap() += 1;
+        self.changed.notify_all();
+    }
+
+    /// Blocks until `done` returns true. The condition is checked after every status change.
+    pub(crate) fn wait_until(&self, mut done: impl FnMut() -> bool) {
+        let mut changes = self.changes.lock().unwrap();
+        while !done() {
+            changes = self.changed.wait(changes).unwrap();
+        }
+    }
+}
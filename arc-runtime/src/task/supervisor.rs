@@ -0,0 +1,264 @@
+use crate::control::TaskError;
+use crate::job::metrics::JobMetrics;
+use crate::job::metrics::Metric;
+use crate::job::JobId;
+use crate::task::dead_letter::DeadLetters;
+use crate::task::handle::TaskHandle;
+use crate::task::message::TaskMessage;
+use crate::task::state::StatusSignal;
+use crate::task::state::TaskState;
+use crate::task::state::TaskStatus;
+
+use kompact::prelude::*;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::time::Duration;
+
+/// What a supervisor does when one of its tasks fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// The task stays failed.
+    Never,
+    /// The task is restarted up to `max_restarts` times. The delay before a restart starts at
+    /// `backoff` and doubles with every restart. Once the task has no restarts left, the
+    /// failure is escalated.
+    OnFailure {
+        max_restarts: usize,
+        backoff: Duration,
+    },
+    /// The failure of the task fails the job, which kills all tasks of the supervisor.
+    Escalate,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy::Never
+    }
+}
+
+/// Keeps track of the tasks launched through a context, and restarts them when they fail.
+///
+/// The supervisor of a job is shared by all of its tasks, including the ones which are
+/// launched by other tasks, so that the job can observe and cancel all of them. Tasks which are
+/// launched without supervision are tracked as well, so that they are cancelled with the job,
+/// but since they have no shared state, their status cannot be observed.
+#[derive(Clone, Default)]
+pub struct Supervisor(Arc<Core>);
+
+/// A reference to a supervisor which is held by its tasks.
+#[derive(Clone, Default)]
+pub(crate) struct WeakSupervisor(Weak<Core>);
+
+#[derive(Default)]
+struct Core {
+    job: Option<JobId>,
+    tasks: Mutex<Vec<Arc<TaskState>>>,
+    unsupervised: Mutex<Vec<TaskHandle>>,
+    escalated: AtomicBool,
+    signal: StatusSignal,
+    metrics: JobMetrics,
+}
+
+impl WeakSupervisor {
+    pub(crate) fn upgrade(&self) -> Option<Supervisor> {
+        self.0.upgrade().map(Supervisor)
+    }
+}
+
+impl Supervisor {
+    /// Creates the supervisor of a job.
+    pub(crate) fn for_job(job: JobId) -> Self {
+        Self(Arc::new(Core {
+            job: Some(job),
+            ..Core::default()
+        }))
+    }
+
+    /// Returns the job which the supervised tasks belong to, if any.
+    pub fn job(&self) -> Option<JobId> {
+        self.0.job
+    }
+
+    pub fn metrics(&self) -> &JobMetrics {
+        &self.0.metrics
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakSupervisor {
+        WeakSupervisor(Arc::downgrade(&self.0))
+    }
+
+    /// Wakes up the threads which wait for the status of a supervised task to change.
+    pub(crate) fn notify(&self) {
+        self.0.signal.notify();
+    }
+
+    /// Returns the statuses of the supervised tasks, in the order in which they were launched.
+    pub fn statuses(&self) -> Vec<TaskStatus> {
+        let tasks = self.0.tasks.lock().unwrap();
+        tasks.iter().map(|task| task.status()).collect()
+    }
+
+    /// Returns the states of the supervised tasks, in the order in which they were launched.
+    pub(crate) fn tasks(&self) -> Vec<Arc<TaskState>> {
+        self.0.tasks.lock().unwrap().clone()
+    }
+
+    /// Blocks until `done` returns true. The condition is checked every time the status of a
+    /// supervised task changes.
+    pub(crate) fn wait_until(&self, done: impl FnMut() -> bool) {
+        self.0.signal.wait_until(done);
+    }
+
+    /// Returns true if a task failed with the policy `RestartPolicy::Escalate`, or ran out of
+    /// restarts.
+    pub fn is_escalated(&self) -> bool {
+        self.0.escalated.load(Ordering::SeqCst)
+    }
+
+    /// Kills all tracked tasks.
+    pub fn kill_all(&self) {
+        let tasks = self.0.tasks.lock().unwrap();
+        tasks.iter().for_each(|task| task.handle(TaskMessage::Kill));
+        let unsupervised = self.0.unsupervised.lock().unwrap();
+        unsupervised.iter().for_each(TaskHandle::kill);
+    }
+
+    /// Tracks a task which was launched without supervision.
+    pub(crate) fn track(&self, task: TaskHandle) {
+        self.0.unsupervised.lock().unwrap().push(task);
+        self.metrics().add(Metric::TasksLaunched, 1);
+    }
+
+    /// Creates and starts a task. Every time the task is restarted, `factory` creates a new
+    /// component for it from the same `TaskState`, and therefore the same channels.
+    pub(crate) fn launch<C, F>(
+        &self,
+        system: &KompactSystem,
+        policy: RestartPolicy,
+        dead_letters: DeadLetters,
+        factory: F,
+    ) -> TaskHandle
+    where
+        F: Fn(Arc<TaskState>) -> C + Send + Sync + 'static,
+        C: ComponentDefinition + 'static,
+    {
+        let state = Arc::new(TaskState::supervised(dead_letters, self.downgrade()));
+        state.set_checkpointing(matches!(policy, RestartPolicy::OnFailure { .. }));
+        self.0.tasks.lock().unwrap().push(state.clone());
+        self.metrics().add(Metric::TasksLaunched, 1);
+        let task = Supervised {
+            supervisor: self.clone(),
+            state: state.clone(),
+            policy,
+            factory: Arc::new(factory),
+            restarts: 0,
+        };
+        task.start(system);
+        TaskHandle::supervised(state)
+    }
+
+    fn escalate(&self) {
+        self.0.escalated.store(true, Ordering::SeqCst);
+        self.kill_all();
+    }
+}
+
+/// A launched instance of a supervised task.
+struct Supervised<F> {
+    supervisor: Supervisor,
+    state: Arc<TaskState>,
+    policy: RestartPolicy,
+    factory: Arc<F>,
+    restarts: usize,
+}
+
+impl<C, F> Supervised<F>
+where
+    F: Fn(Arc<TaskState>) -> C + Send + Sync + 'static,
+    C: ComponentDefinition + 'static,
+{
+    fn start(self, system: &KompactSystem) {
+        let c = system.create(|| (self.factory)(self.state.clone()));
+        self.state.set_status(TaskStatus::Running);
+        c.set_recovery_function(move |fault| {
+            fault.recover_with(move |_, system, logger| self.recover(&system, &logger))
+        });
+        system.start(&c);
+    }
+
+    fn recover(mut self, system: &KompactSystem, logger: &KompactLogger) {
+        let error = self
+            .state
+            .take_panic()
+            .unwrap_or_else(|| TaskError::new("Task panicked"));
+        match self.policy {
+            RestartPolicy::OnFailure {
+                max_restarts,
+                backoff,
+            } if self.restarts < max_restarts => {
+                let delay = backoff * 2u32.saturating_pow(self.restarts as u32);
+                self.restarts += 1;
+                warn!(
+                    logger,
+                    "Task failed with {}, restarting in {:?} ({}/{})",
+                    error,
+                    delay,
+                    self.restarts,
+                    max_restarts
+                );
+                self.state.set_status(TaskStatus::Restarting);
+                self.supervisor.metrics().add(Metric::TaskRestarts, 1);
+                let backoff = system.create(|| Backoff::new(delay, self));
+                system.start(&backoff);
+            }
+            RestartPolicy::Never => {
+                warn!(logger, "Task failed with {}", error);
+                self.state.fail(error);
+                self.state.release();
+            }
+            _ => {
+                warn!(logger, "Task failed with {}, escalating", error);
+                self.state.fail(error);
+                self.state.release();
+                self.supervisor.escalate();
+            }
+        }
+    }
+}
+
+/// A short-lived component which restarts a failed task once its backoff has elapsed.
+#[derive(ComponentDefinition, Actor)]
+struct Backoff {
+    ctx: ComponentContext<Self>,
+    delay: Duration,
+    restart: Option<Box<dyn FnOnce(&KompactSystem) + Send>>,
+}
+
+impl Backoff {
+    fn new<C, F>(delay: Duration, task: Supervised<F>) -> Self
+    where
+        F: Fn(Arc<TaskState>) -> C + Send + Sync + 'static,
+        C: ComponentDefinition + 'static,
+    {
+        Self {
+            ctx: ComponentContext::uninitialised(),
+            delay,
+            restart: Some(Box::new(move |system| task.start(system))),
+        }
+    }
+}
+
+impl ComponentLifecycle for Backoff {
+    fn on_start(&mut self) -> Handled {
+        self.schedule_once(self.delay, |backoff, _| {
+            let restart = backoff.restart.take().unwrap();
+            restart(&backoff.ctx().system());
+            Handled::DieNow
+        });
+        Handled::Ok
+    }
+}
@@ -1,4 +1,6 @@
 #![allow(unused)]
+mod common;
+
 #[allow(non_camel_case_types)]
 mod test_closure {
 
@@ -19,7 +21,7 @@ mod test_closure {
 
     #[rewrite]
     fn f(a: i32, env: Erased) -> i32 {
-        let env: Env = unerase!(env, Env);
+        let env: Env = env.unerase::<Env>(ctx).unwrap();
         let b: i32 = env.b;
         a + b
     }
@@ -59,3 +61,211 @@ mod test_toplevel {
         let z: i32 = call!(f(1,));
     }
 }
+
+#[allow(non_camel_case_types)]
+#[cfg(test)]
+mod test_registry {
+
+    use arc_runtime::prelude::*;
+
+    declare_functions!(f, g);
+
+    #[rewrite]
+    fn f(a: i32) -> i32 {
+        a + 1
+    }
+
+    #[rewrite]
+    fn g(a: i32) -> bool {
+        a > 0
+    }
+
+    #[test]
+    fn roundtrip() {
+        let x: function!((i32) -> i32) = function!(f);
+        let bytes = bincode::serialize(&x).unwrap();
+        let y: function!((i32) -> i32) = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(y.ptr as usize, x.ptr as usize);
+        assert_eq!(y.tag.resolve().unwrap() as usize, f as usize);
+    }
+
+    #[test]
+    fn mismatch() {
+        let key = FunctionKey::new::<(i32,), bool>("f");
+        let tag: FunctionTag<(i32,), bool> = FunctionTag(key, std::marker::PhantomData);
+        match tag.resolve() {
+            Err(FunctionError::Mismatch { name, .. }) => assert_eq!(name, "f"),
+            result => panic!("expected a mismatch, got {:?}", result.map(|_| ())),
+        }
+        let x: function!((i32) -> bool) = function!(g);
+        let bytes = bincode::serialize(&x).unwrap();
+        assert!(bincode::deserialize::<function!((i32) -> i32)>(&bytes).is_err());
+    }
+
+    #[test]
+    fn unknown() {
+        let key = FunctionKey::new::<(i32,), i32>("h");
+        let tag: FunctionTag<(i32,), i32> = FunctionTag(key, std::marker::PhantomData);
+        assert_eq!(tag.resolve().err(), Some(FunctionError::Unknown(key)));
+        let bytes = bincode::serialize(&key).unwrap();
+        assert!(bincode::deserialize::<function!((i32) -> i32)>(&bytes).is_err());
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[cfg(test)]
+mod test_captured {
+
+    use crate::common::mod_collect::collect;
+    use crate::common::mod_source::source;
+    use crate::common::Collector;
+    use arc_runtime::data::channels::local::multicast::Pullable;
+    use arc_runtime::data::channels::local::multicast::Pushable;
+    use arc_runtime::prelude::*;
+
+    declare_functions!(scale);
+
+    #[rewrite]
+    fn scale(x: i32, factor: i32) -> i32 {
+        x * factor
+    }
+
+    #[rewrite(nonpersistent)]
+    async fn map(
+        mut i: Pullable<i32>,
+        mut f: closure!(|i32| (i32) -> i32),
+        #[output] mut o: Pushable<i32>,
+    ) {
+        loop {
+            let x = pull!(i);
+            let y = call_indirect!(f(x));
+            push!(o, y);
+        }
+    }
+
+    #[test]
+    fn shipped() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let f: closure!(|i32| (i32) -> i32) = closure!(scale, 3);
+            assert_eq!(call_indirect!(f(2)), 6);
+            let bytes = bincode::serialize(&f.into_sendable(ctx)).unwrap();
+            let tag: <closure!(|i32| (i32) -> i32) as DynSharable>::T =
+                bincode::deserialize(&bytes).unwrap();
+            let g = tag.into_sharable(ctx);
+            assert_eq!(g.env, 3);
+            assert_eq!(call_indirect!(g(4)), 12);
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+
+    #[test]
+    fn parameterised() {
+        let runtime = Runtime::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        runtime.submit(move |ctx| {
+            let v: Vec<i32> = vector![1, 2, 3];
+            let s = source((v,), ctx);
+            let s = map((s, closure!(scale, 10)), ctx);
+            collect((s, Collector(tx)), ctx);
+        });
+        let timeout = std::time::Duration::from_millis(500);
+        let output = (0..3).map(|_| rx.recv_timeout(timeout).unwrap());
+        assert_eq!(output.collect::<std::vec::Vec<_>>(), vec![10, 20, 30]);
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[cfg(test)]
+mod test_erased {
+
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub struct Point {
+        pub x: i32,
+    }
+
+    #[rewrite]
+    pub struct Other {
+        pub y: i32,
+    }
+
+    fn as_point(e: Erased, ctx: Context) -> Control<Point> {
+        Control::Continue(unerase!(e, Point))
+    }
+
+    fn as_other(e: Erased, ctx: Context) -> Control<Other> {
+        Control::Continue(unerase!(e, Other))
+    }
+
+    #[test]
+    fn checked() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let p: Point = new!(Point { x: 1 });
+            let e: Erased = erase!(p, Point);
+            assert!(e.type_name().ends_with("Point"));
+            assert_eq!(e.clone().try_unerase::<Point>(ctx).map(|p| p.x), Some(1));
+            let error = e.clone().unerase::<Other>(ctx).err().unwrap();
+            assert!(error.to_string().contains("Other"));
+
+            let s = e.into_sendable(ctx);
+            assert!(s.clone().unerase::<<Other as DynSharable>::T>(ctx).is_err());
+            let e = s.into_sharable(ctx);
+            assert!(matches!(as_point(e, ctx), Control::Continue(p) if p.x == 1));
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+
+    #[test]
+    fn mismatch_fails() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let p: Point = new!(Point { x: 1 });
+            let e: Erased = erase!(p, Point);
+            match as_other(e, ctx) {
+                Control::Failed(error) => assert!(error.message().starts_with("cannot unerase")),
+                _ => panic!("expected unerasing to fail"),
+            }
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+
+    #[test]
+    fn compared() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let a: Erased = erase!(1, i32);
+            let b: Erased = erase!(1, i32);
+            let c: Erased = erase!(2, i32);
+            assert_eq!(format!("{:?}", a), "1");
+            assert_eq!(a, b);
+            assert_ne!(a, c);
+            let mut keys = std::collections::HashSet::new();
+            keys.insert(a.clone().into_key().unwrap());
+            assert!(keys.contains(&b.clone().into_key().unwrap()));
+            assert!(!keys.contains(&c.clone().into_key().unwrap()));
+            assert_eq!(format!("{:?}", a.into_sendable(ctx)), "1");
+
+            let p: Point = new!(Point { x: 1 });
+            let p: Erased = erase!(p, Point);
+            assert_ne!(p, c);
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+
+    #[test]
+    fn incomparable() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let p: Point = new!(Point { x: 1 });
+            let p: Erased = erase!(p, Point);
+            assert_eq!(p.try_eq(&p), None);
+            assert_ne!(p, p.clone());
+            let error = p.into_key().err().unwrap();
+            assert!(error.to_string().contains("cannot be used as a key"));
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}
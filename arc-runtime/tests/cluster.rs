@@ -0,0 +1,142 @@
+use arc_runtime::cluster::Graphs;
+use arc_runtime::cluster::Master;
+use arc_runtime::cluster::Worker;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::dataflow::DataflowError;
+use arc_runtime::prelude::*;
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const MASTER: &str = "ARC_CLUSTER_MASTER";
+const WORKER: &str = "ARC_CLUSTER_WORKER";
+const OUTPUT: &str = "ARC_CLUSTER_OUTPUT";
+
+#[rewrite(nonpersistent)]
+async fn source(mut i: Vec<i32>, #[output] mut o: Pushable<i32>) {
+    for x in i.into_iter().cloned() {
+        push!(o, x);
+    }
+}
+
+/// Appends `x` to the file named by the output variable.
+fn append(x: i32) {
+    let path = std::env::var(OUTPUT).unwrap();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    writeln!(file, "{}", x).unwrap();
+}
+
+#[rewrite(nonpersistent)]
+async fn output(mut i: Pullable<i32>) {
+    loop {
+        append(pull!(i));
+    }
+}
+
+fn double(x: i32, _: Context) -> i32 {
+    x * 2
+}
+
+/// `source -> double -> output`, where every node is placed on a different worker than the
+/// node before it when there are two workers.
+fn sum() -> DataflowBuilder {
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("source", |ctx| {
+        let mut v: Vec<i32> = Vec::new(ctx);
+        (1..=10).for_each(|x| v.push(x, ctx));
+        source((v,), ctx)
+    });
+    builder.set_sendable(s);
+    let s = builder.add_map("double", s, double);
+    builder.set_sendable(s);
+    builder.add_sink("output", s, |s, ctx| output((s,), ctx));
+    builder
+}
+
+/// `sum`, without allowing its streams to be sent between workers.
+fn local() -> DataflowBuilder {
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("source", |ctx| source((Vec::new(ctx),), ctx));
+    builder.add_sink("output", s, |s, ctx| output((s,), ctx));
+    builder
+}
+
+fn graphs() -> Graphs {
+    Graphs::new().with("sum", sum).with("local", local)
+}
+
+/// Runs a worker when the test binary is started by `master`.
+#[test]
+#[ignore]
+fn worker_process() {
+    let (master, addr) = match (std::env::var(MASTER), std::env::var(WORKER)) {
+        (Ok(master), Ok(addr)) => (master.parse().unwrap(), addr.parse().unwrap()),
+        _ => return,
+    };
+    let runtime = Runtime::with_network(addr);
+    let worker = Worker::start(&runtime, master, graphs());
+    worker.await_completed(1);
+}
+
+/// The address of a runtime which listens on a free port.
+fn any_port() -> SocketAddr {
+    "127.0.0.1:0".parse().unwrap()
+}
+
+fn spawn_worker(master: SocketAddr, output: &std::path::Path) -> std::process::Child {
+    std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["worker_process", "--exact", "--ignored", "--nocapture"])
+        .env(MASTER, master.to_string())
+        .env(WORKER, any_port().to_string())
+        .env(OUTPUT, output)
+        .spawn()
+        .unwrap()
+}
+
+#[test]
+fn master() {
+    let output = std::env::temp_dir().join(format!("arc-cluster-{}", std::process::id()));
+    let _ = std::fs::remove_file(&output);
+    let runtime = Runtime::with_network(any_port());
+    let master = Master::start(&runtime, graphs());
+    let mut workers = vec![
+        spawn_worker(runtime.address(), &output),
+        spawn_worker(runtime.address(), &output),
+    ];
+    assert!(master.await_workers(2, Duration::from_secs(10)));
+    assert_eq!(master.workers(), 2);
+
+    let job = master.submit("sum").unwrap();
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+    for worker in &mut workers {
+        assert!(worker.wait().unwrap().success());
+    }
+    let output = std::fs::read_to_string(&output).unwrap();
+    let output = output
+        .lines()
+        .map(|line| line.parse::<i32>().unwrap())
+        .collect::<std::vec::Vec<_>>();
+    assert_eq!(output.len(), 10);
+    assert_eq!(output.iter().sum::<i32>(), 110);
+    runtime.shutdown();
+}
+
+#[test]
+fn rejected() {
+    let runtime = Runtime::with_network(any_port());
+    let master = Master::start(&runtime, graphs());
+    assert_eq!(master.workers(), 0);
+    let error = master.submit("product").err().unwrap();
+    assert_eq!(error, DataflowError::UnknownGraph("product".to_string()));
+    let error = master.submit("local").err().unwrap();
+    assert_eq!(error, DataflowError::Unsendable("source".to_string()));
+    let error = master.submit("sum").err().unwrap();
+    assert_eq!(error, DataflowError::NoWorkers);
+    runtime.shutdown();
+}
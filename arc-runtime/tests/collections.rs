@@ -0,0 +1,158 @@
+mod common;
+
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::prelude::*;
+use common::mod_collect::collect;
+use common::mod_source::source;
+use common::Collector;
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+#[test]
+fn maps() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let s = |x: &str| String::from_str(x, ctx);
+
+        let mut m: HashMap<String, i32> = HashMap::new(ctx);
+        assert_eq!(m.insert(s("a"), 1, ctx), None);
+        assert_eq!(m.insert(s("b"), 2, ctx), None);
+        assert_eq!(m.insert(s("a"), 3, ctx), Some(1));
+        assert_eq!(m.get(s("a"), ctx), Some(3));
+        assert_eq!(m.get(s("c"), ctx), None);
+        assert!(m.contains_key(s("b"), ctx));
+        assert_eq!(m.len(ctx), 2);
+
+        let mut values = m.values(ctx).as_slice(ctx).to_vec();
+        values.sort();
+        assert_eq!(values, vec![2, 3]);
+
+        let bytes = bincode::serialize(&m.into_sendable(ctx)).unwrap();
+        let sent: <HashMap<String, i32> as DynSharable>::T = bincode::deserialize(&bytes).unwrap();
+        let n = sent.into_sharable(ctx);
+        assert_eq!(n.get(s("b"), ctx), Some(2));
+
+        assert_eq!(m.remove(s("a"), ctx), Some(3));
+        m.clear(ctx);
+        assert!(m.is_empty(ctx));
+        assert_eq!(n.len(ctx), 2);
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
+
+#[test]
+fn sets() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let mut set: HashSet<Vec<i32>> = HashSet::new(ctx);
+        assert!(set.insert(vector![1, 2], ctx));
+        assert!(!set.insert(vector![1, 2], ctx));
+        assert!(set.insert(vector![3], ctx));
+        assert!(set.contains(vector![3], ctx));
+        assert_eq!(set.len(ctx), 2);
+
+        let bytes = bincode::serialize(&set.into_sendable(ctx)).unwrap();
+        let sent: <HashSet<Vec<i32>> as DynSharable>::T = bincode::deserialize(&bytes).unwrap();
+        let copy = sent.into_sharable(ctx);
+        assert!(copy.contains(vector![1, 2], ctx));
+
+        assert!(set.remove(vector![1, 2], ctx));
+        assert!(!set.remove(vector![1, 2], ctx));
+        assert_eq!(copy.len(ctx), 2);
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
+
+#[test]
+fn externs() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let s = |x: &str| String::from_str(x, ctx);
+
+        let m: HashMap<String, i32> = new_hash_map(ctx);
+        insert_hash_map(m.clone(), s("a"), 1, ctx);
+        insert_hash_map(m.clone(), s("b"), 2, ctx);
+        assert_eq!(get_hash_map(m.clone(), s("a"), ctx), Some(1));
+        assert_eq!(get_hash_map(m.clone(), s("c"), ctx), None);
+        assert!(contains_hash_map(m.clone(), s("b"), ctx));
+        assert_eq!(len_hash_map(m.clone(), ctx), 2);
+        assert_eq!(keys_hash_map(m.clone(), ctx).len(ctx), 2);
+        assert_eq!(values_hash_map(m.clone(), ctx).len(ctx), 2);
+        remove_hash_map(m.clone(), s("a"), ctx);
+        assert!(!contains_hash_map(m.clone(), s("a"), ctx));
+        assert_eq!(len_hash_map(m, ctx), 1);
+
+        let set: HashSet<i32> = new_hash_set(ctx);
+        assert!(insert_hash_set(set.clone(), 1, ctx));
+        assert!(!insert_hash_set(set.clone(), 1, ctx));
+        assert!(contains_hash_set(set.clone(), 1, ctx));
+        assert_eq!(values_hash_set(set.clone(), ctx).as_slice(ctx), &[1]);
+        assert!(remove_hash_set(set.clone(), 1, ctx));
+        assert_eq!(len_hash_set(set, ctx), 0);
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
+
+/// A value which counts how many times it has been dropped.
+#[derive(Clone, PartialEq, Eq, Hash, Collectable, Finalize, NoTrace, NoSerde, NoDebug)]
+struct Tracked;
+
+convert_reflexive!(Tracked);
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn finalize() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let mut m: HashMap<i32, Tracked> = HashMap::new(ctx);
+        let mut set: HashSet<Tracked> = HashSet::new(ctx);
+        for k in 0..3 {
+            m.insert(k, Tracked, ctx);
+        }
+        set.insert(Tracked, ctx);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+        // Neither collection is rooted, so collecting the heap finalizes both, which drops
+        // their tables along with the values in them.
+        ctx.mutator().collect(&mut []);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 4);
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
+
+/// Forwards the values which have not been seen before, keeping the seen values as task state.
+#[rewrite(nonpersistent)]
+async fn distinct(mut i: Pullable<i32>, mut seen: HashSet<i32>, #[output] mut o: Pushable<i32>) {
+    loop {
+        let x = pull!(i);
+        if seen.insert(x, ctx) {
+            push!(o, x);
+        }
+    }
+}
+
+#[test]
+fn task_state() {
+    let runtime = Runtime::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    runtime.submit(move |ctx| {
+        let v: Vec<i32> = vector![1, 2, 1, 3, 2, 4];
+        let mut seen: HashSet<i32> = HashSet::new(ctx);
+        seen.insert(4, ctx);
+        let s = source((v,), ctx);
+        let s = distinct((s, seen), ctx);
+        collect((s, Collector(tx)), ctx);
+    });
+    let timeout = std::time::Duration::from_millis(500);
+    let output = (0..3).map(|_| rx.recv_timeout(timeout).unwrap());
+    assert_eq!(output.collect::<std::vec::Vec<_>>(), vec![1, 2, 3]);
+    assert!(rx.recv_timeout(timeout).is_err());
+}
@@ -0,0 +1,66 @@
+//! Tasks which are shared by the integration tests.
+#![allow(dead_code, unused_imports)]
+
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::prelude::*;
+
+/// A component which lends its context to the test thread.
+#[derive(ComponentDefinition, Actor)]
+pub struct Host {
+    ctx: ComponentContext<Self>,
+}
+
+impl ComponentLifecycle for Host {}
+
+/// Creates a context through which the test thread can push into the channels of a job, whose
+/// own context is destroyed once it has launched its tasks.
+pub fn context(runtime: &Runtime) -> Context {
+    let host = runtime.system.create(|| Host {
+        ctx: ComponentContext::uninitialised(),
+    });
+    Context::new(host, instantiate_immix(ImmixOptions::default()))
+}
+
+/// The sending end of a channel to the test thread.
+#[derive(Clone, Collectable, Finalize, NoTrace, NoSerde, NoDebug)]
+pub struct Collector(pub std::sync::mpsc::Sender<i32>);
+
+convert_reflexive!(Collector);
+
+/// Pushes the elements of a vector into a stream.
+#[rewrite(nonpersistent)]
+pub async fn source(mut i: Vec<i32>, #[output] mut o: Pushable<i32>) {
+    for x in i.into_iter().cloned() {
+        push!(o, x);
+    }
+}
+
+/// Forwards the values of a stream to the test thread. The task finishes once the test thread
+/// stops listening.
+#[rewrite(nonpersistent)]
+pub async fn collect(mut i: Pullable<i32>, c: Collector) {
+    loop {
+        let x: i32 = pull!(i);
+        if c.0.send(x).is_err() {
+            return Control::Finished;
+        }
+    }
+}
+
+/// The sending end of a channel of dead letters to the test thread.
+#[derive(Clone, Collectable, Finalize, NoTrace, NoSerde, NoDebug)]
+pub struct DeadLetterCollector(pub std::sync::mpsc::Sender<DeadLetter>);
+
+convert_reflexive!(DeadLetterCollector);
+
+/// Forwards dead letters to the test thread, like `collect`.
+#[rewrite(nonpersistent)]
+pub async fn collect_dead_letters(mut i: Pullable<DeadLetter>, c: DeadLetterCollector) {
+    loop {
+        let x: DeadLetter = pull!(i);
+        if c.0.send(x).is_err() {
+            return Control::Finished;
+        }
+    }
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use arc_runtime::data::channels::local::multicast::channel;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::prelude::*;
+
+use std::time::Duration;
+
+fn checked(x: i32) -> Result<i32, std::string::String> {
+    if x < 0 {
+        Err(format!("negative input {}", x))
+    } else {
+        Ok(x)
+    }
+}
+
+/// The line on which `double` applies `?` to a `Result`.
+const DOUBLE_TRY_LINE: u32 = line!() + 2;
+fn double(x: i32) -> Control<i32> {
+    let x = checked(x)?;
+    Control::Continue(x * 2)
+}
+
+fn double_twice(x: i32) -> Control<i32> {
+    let x = double(x)?;
+    double(x)
+}
+
+#[test]
+fn continue_and_finish() {
+    assert!(matches!(double_twice(1), Control::Continue(4)));
+    let finished = || -> Control<i32> {
+        Control::<()>::Finished?;
+        Control::Continue(0)
+    };
+    assert!(matches!(finished(), Control::Finished));
+}
+
+#[test]
+fn fail_from_result() {
+    let error = match double_twice(-1) {
+        Control::Failed(error) => error,
+        _ => panic!("expected the task to fail"),
+    };
+    assert_eq!(error.message(), "negative input -1");
+    // The error is located where `?` was applied to the `Result`, inside `double`.
+    assert_eq!(error.location().file(), file!());
+    assert_eq!(error.location().line(), DOUBLE_TRY_LINE);
+    assert!(error.to_string().starts_with("negative input -1 at "));
+}
+
+#[test]
+fn convert_result() {
+    assert!(matches!(Control::from(checked(1)), Control::Continue(1)));
+    let line = line!() + 1;
+    let control = Control::from(checked(-1));
+    match control {
+        Control::Failed(error) => assert_eq!(error.location().line(), line),
+        _ => panic!("expected a failure"),
+    }
+}
+
+#[rewrite(nonpersistent)]
+async fn validate(mut i: Pullable<i32>, #[output] mut o: Pushable<i32>) {
+    loop {
+        let x = pull!(i);
+        let y = checked(x)?;
+        push!(o, y);
+    }
+}
+
+#[test]
+fn failed_task_status() {
+    let runtime = Runtime::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("input", move |ctx| {
+        let (o, i): (Pushable<i32>, Pullable<i32>) = channel(ctx);
+        tx.send(o).unwrap();
+        i
+    });
+    let s = builder.add_operator("validate", s, |s, ctx| validate((s,), ctx));
+    builder.add_sink("drop", s, |s, _| std::mem::forget(s));
+    let tasks = builder.deploy(&runtime).unwrap();
+    let o = rx.recv().unwrap();
+    let ctx = common::context(&runtime);
+
+    o.try_push(-1, ctx);
+    let failed = (0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        tasks[0].status() == Some(TaskStatus::Failed)
+    });
+    assert!(failed);
+    assert_eq!(tasks[0].error().unwrap().message(), "negative input -1");
+}
@@ -0,0 +1,277 @@
+mod common;
+
+use arc_runtime::data::channels::local::multicast::channel;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::dataflow::DataflowError;
+use arc_runtime::dataflow::Stream;
+use arc_runtime::prelude::*;
+use common::mod_collect::collect;
+use common::mod_source::source;
+use common::Collector;
+
+declare_functions!(inc);
+
+#[rewrite]
+fn inc(x: i32) -> i32 {
+    x + 1
+}
+
+#[rewrite(nonpersistent)]
+async fn map(mut i: Pullable<i32>, mut f: function!((i32) -> i32), #[output] mut o: Pushable<i32>) {
+    loop {
+        let x = pull!(i);
+        let y = call_indirect!(f(x));
+        push!(o, y);
+    }
+}
+
+#[rewrite(nonpersistent)]
+async fn log(mut i: Pullable<i32>) {
+    loop {
+        println!("Logging {}", pull!(i));
+    }
+}
+
+fn numbers(ctx: Context) -> Pullable<i32> {
+    let v: Vec<i32> = vector![1, 2, 3];
+    source((v,), ctx)
+}
+
+fn increment(s: Pullable<i32>, ctx: Context) -> Pullable<i32> {
+    map((s, function!(inc)), ctx)
+}
+
+fn print(s: Pullable<i32>, ctx: Context) {
+    log((s,), ctx)
+}
+
+#[test]
+fn validate() {
+    let mut builder = DataflowBuilder::new();
+    assert_eq!(builder.validate(), Err(DataflowError::NoSource));
+    let s = builder.add_source("numbers", numbers);
+    assert_eq!(builder.validate(), Err(DataflowError::NoSink));
+    let s = builder.add_operator("increment", s, increment);
+    builder.add_sink("print", s, print);
+    assert_eq!(builder.validate(), Ok(()));
+    builder.add_source("numbers", numbers);
+    assert_eq!(
+        builder.validate(),
+        Err(DataflowError::DuplicateName("numbers".to_string()))
+    );
+}
+
+#[test]
+fn validate_unconsumed() {
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("numbers", numbers);
+    builder.add_operator("increment", s, increment);
+    builder.add_sink("print", s, print);
+    assert_eq!(
+        builder.validate(),
+        Err(DataflowError::Unconsumed("increment".to_string()))
+    );
+}
+
+#[test]
+fn validate_foreign() {
+    let mut other = DataflowBuilder::new();
+    let s = other.add_source("numbers", numbers);
+    let mut builder = DataflowBuilder::new();
+    builder.add_source("other", numbers);
+    builder.add_sink("print", s, print);
+    assert_eq!(
+        builder.validate(),
+        Err(DataflowError::ForeignStream("print".to_string()))
+    );
+}
+
+/// Adds a source whose values are pushed by the test thread. The values can only be pushed
+/// once the graph is deployed, so every consumer receives all of them.
+fn input(builder: &mut DataflowBuilder) -> (Stream<i32>, std::sync::mpsc::Receiver<Pushable<i32>>) {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let s = builder.add_source("input", move |ctx| {
+        let (o, i) = channel(ctx);
+        tx.send(o).unwrap();
+        i
+    });
+    (s, rx)
+}
+
+/// Adds a sink which forwards the values of `s` to the returned receiver.
+fn output(
+    builder: &mut DataflowBuilder,
+    name: &str,
+    s: Stream<i32>,
+) -> std::sync::mpsc::Receiver<i32> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    builder.add_sink(name, s, move |s, ctx| collect((s, Collector(tx)), ctx));
+    rx
+}
+
+/// Pushes `values` into the input of a deployed graph, and then closes it.
+fn push_all(runtime: &Runtime, input: std::sync::mpsc::Receiver<Pushable<i32>>, values: &[i32]) {
+    let o = input.recv().unwrap();
+    let ctx = common::context(runtime);
+    for x in values {
+        o.try_push(*x, ctx);
+    }
+    ctx.destroy();
+}
+
+/// Receives the next `n` values of a sink.
+fn received(rx: &std::sync::mpsc::Receiver<i32>, n: usize) -> std::vec::Vec<i32> {
+    let timeout = std::time::Duration::from_secs(5);
+    (0..n).map(|_| rx.recv_timeout(timeout).unwrap()).collect()
+}
+
+#[test]
+fn deploy() {
+    let runtime = Runtime::new();
+    let mut builder = DataflowBuilder::new();
+    let (s, i) = input(&mut builder);
+    let a = builder.add_operator("a", s, increment);
+    let b = builder.add_operator("b", s, increment);
+    let a = output(&mut builder, "collect_a", a);
+    let b = output(&mut builder, "collect_b", b);
+    builder.deploy(&runtime).unwrap();
+    push_all(&runtime, i, &[1, 2, 3]);
+    assert_eq!(received(&a, 3), [2, 3, 4]);
+    assert_eq!(received(&b, 3), [2, 3, 4]);
+}
+
+fn add_one(x: i32, _: Context) -> i32 {
+    x + 1
+}
+
+fn is_even(x: i32, _: Context) -> bool {
+    x % 2 == 0
+}
+
+#[test]
+fn deploy_fused() {
+    let runtime = Runtime::new();
+    let mut builder = DataflowBuilder::new();
+    let (s, i) = input(&mut builder);
+    let s = builder.add_map("a", s, add_one);
+    let s = builder.add_filter("b", s, is_even);
+    let s = builder.add_map("c", s, add_one);
+    let t = builder.add_map("d", s, add_one);
+    let u = builder.add_map("e", s, add_one);
+    let t = output(&mut builder, "collect_t", t);
+    let u = output(&mut builder, "collect_u", u);
+    let tasks = builder.deploy(&runtime).unwrap();
+    // `a -> b -> c` runs as one task, while `d` and `e` run on their own since `c` has two
+    // consumers.
+    assert_eq!(tasks.len(), 5);
+    push_all(&runtime, i, &[1, 2, 3]);
+    assert_eq!(received(&t, 2), [4, 6]);
+    assert_eq!(received(&u, 2), [4, 6]);
+}
+
+/// Deploys `input -> a -> b -> c -> collect`, and returns the number of tasks and the output.
+fn deploy_linear(fuse_b: bool) -> (usize, std::vec::Vec<i32>) {
+    let runtime = Runtime::new();
+    let mut builder = DataflowBuilder::new();
+    let (s, i) = input(&mut builder);
+    let s = builder.add_map("a", s, add_one);
+    let s = builder.add_filter("b", s, is_even);
+    if !fuse_b {
+        builder.disable_fusion(s.node());
+    }
+    let s = builder.add_map("c", s, add_one);
+    let s = output(&mut builder, "collect", s);
+    let tasks = builder.deploy(&runtime).unwrap();
+    push_all(&runtime, i, &[1, 2, 3]);
+    (tasks.len(), received(&s, 2))
+}
+
+#[test]
+fn deploy_disabled_fusion() {
+    // `a -> b -> c` runs as one task.
+    assert_eq!(deploy_linear(true), (3, vec![3, 5]));
+    // With fusion disabled for `b`, neither `a` nor `c` has a fusible neighbour.
+    assert_eq!(deploy_linear(false), (5, vec![3, 5]));
+}
+
+#[test]
+fn deploy_unfused() {
+    let runtime = Runtime::new();
+    let mut builder = DataflowBuilder::new();
+    builder.set_fusion(false);
+    let (s, i) = input(&mut builder);
+    let s = builder.add_map("a", s, add_one);
+    let s = builder.add_filter("b", s, is_even);
+    let s = output(&mut builder, "collect", s);
+    let tasks = builder.deploy(&runtime).unwrap();
+    assert_eq!(tasks.len(), 3);
+    push_all(&runtime, i, &[1, 2, 3]);
+    assert_eq!(received(&s, 2), [2, 4]);
+}
+
+/// Deploys `input -> increment -> collect`, where the input is pushed by the test thread.
+fn controlled() -> (
+    Runtime,
+    std::vec::Vec<TaskHandle>,
+    Pushable<i32>,
+    Context,
+    std::sync::mpsc::Receiver<i32>,
+) {
+    let runtime = Runtime::new();
+    let mut builder = DataflowBuilder::new();
+    let (s, input_rx) = input(&mut builder);
+    let s = builder.add_operator("increment", s, increment);
+    let output_rx = output(&mut builder, "collect", s);
+    let tasks = builder.deploy(&runtime).unwrap();
+    let o = input_rx.recv().unwrap();
+    let ctx = common::context(&runtime);
+    (runtime, tasks, o, ctx, output_rx)
+}
+
+/// Returns true if the input channel is closed within a few seconds, which happens once the
+/// task which pulls from it has terminated.
+fn closes(o: &Pushable<i32>, ctx: Context) -> bool {
+    (0..50).any(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        matches!(o.try_push(0, ctx), Control::Finished)
+    })
+}
+
+#[test]
+fn pause_resume_kill() {
+    let (_runtime, tasks, o, ctx, rx) = controlled();
+    let timeout = std::time::Duration::from_millis(500);
+
+    o.try_push(1, ctx);
+    assert_eq!(rx.recv_timeout(timeout), Ok(2));
+
+    tasks.iter().for_each(TaskHandle::pause);
+    o.try_push(2, ctx);
+    assert!(rx.recv_timeout(timeout).is_err());
+
+    tasks.iter().for_each(TaskHandle::resume);
+    assert_eq!(rx.recv_timeout(timeout), Ok(3));
+
+    tasks.iter().for_each(TaskHandle::kill);
+    assert!(closes(&o, ctx));
+}
+
+#[test]
+fn drain() {
+    let (_runtime, tasks, o, ctx, rx) = controlled();
+    let timeout = std::time::Duration::from_millis(500);
+
+    // Only the operator is drained. The collector finishes once the output of the operator
+    // closes, so it cannot finish before the drained values have reached it.
+    let increment = &tasks[0];
+    increment.pause();
+    for x in [1, 2, 3] {
+        o.try_push(x, ctx);
+    }
+    increment.drain();
+    for expected in [2, 3, 4] {
+        assert_eq!(rx.recv_timeout(timeout), Ok(expected));
+    }
+    assert!(closes(&o, ctx));
+}
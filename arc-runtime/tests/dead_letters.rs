@@ -0,0 +1,165 @@
+mod common;
+
+use arc_runtime::data::channels::local::multicast::channel;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::dataflow::Stream;
+use arc_runtime::prelude::*;
+use common::mod_collect::collect;
+use common::mod_collect_dead_letters::collect_dead_letters;
+use common::Collector;
+use common::DeadLetterCollector;
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+declare_functions!(halve);
+
+/// Panics on odd values.
+#[rewrite]
+fn halve(x: i32) -> i32 {
+    if x % 2 != 0 {
+        panic!("odd");
+    }
+    x / 2
+}
+
+/// Forwards the results of `f`, skipping the records for which it fails.
+#[rewrite(nonpersistent)]
+async fn protected(
+    mut i: Pullable<i32>,
+    f: function!((i32) -> i32),
+    #[output] mut o: Pushable<i32>,
+) {
+    loop {
+        let x: i32 = pull!(i);
+        if let Some(y) = call_protected!(f(x)) {
+            push!(o, y);
+        }
+    }
+}
+
+/// Panics on every zero it sees.
+fn faulty(x: i32, _: Context) -> i32 {
+    if x == 0 {
+        panic!("faulty");
+    }
+    x + 1
+}
+
+/// Panics on every other call.
+fn unreliable(x: i32, _: Context) -> i32 {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    if CALLS.fetch_add(1, Ordering::SeqCst) % 2 == 0 {
+        panic!("unreliable");
+    }
+    x + 1
+}
+
+/// Deploys `input -> operator -> output` together with the dead letters of the graph, where
+/// the input is pushed by the test thread.
+fn deploy(
+    operator: impl FnOnce(&mut DataflowBuilder, Stream<i32>) -> Stream<i32>,
+) -> (
+    Runtime,
+    Pushable<i32>,
+    Context,
+    Receiver<i32>,
+    Receiver<DeadLetter>,
+) {
+    let runtime = Runtime::new();
+    let (input_tx, input_rx) = std::sync::mpsc::channel();
+    let (output_tx, output_rx) = std::sync::mpsc::channel();
+    let (dead_tx, dead_rx) = std::sync::mpsc::channel();
+    let mut builder = DataflowBuilder::new();
+    let dead_letters = builder.add_dead_letters("dead letters");
+    let s = builder.add_source("input", move |ctx| {
+        let (o, i) = channel(ctx);
+        input_tx.send(o).unwrap();
+        i
+    });
+    let s = operator(&mut builder, s);
+    builder.add_sink("output", s, move |s, ctx| {
+        collect((s, Collector(output_tx)), ctx)
+    });
+    builder.add_sink("dead", dead_letters, move |s, ctx| {
+        collect_dead_letters((s, DeadLetterCollector(dead_tx)), ctx)
+    });
+    builder.deploy(&runtime).unwrap();
+    let o = input_rx.recv().unwrap();
+    let ctx = common::context(&runtime);
+    (runtime, o, ctx, output_rx, dead_rx)
+}
+
+/// Deploys the map `f`, which retries a failed record `retries` times.
+fn deploy_map(
+    f: fn(i32, Context) -> i32,
+    retries: usize,
+) -> (
+    Runtime,
+    Pushable<i32>,
+    Context,
+    Receiver<i32>,
+    Receiver<DeadLetter>,
+) {
+    deploy(|builder, s| {
+        let s = builder.add_map("f", s, f);
+        builder.set_retries(s.node(), retries);
+        s
+    })
+}
+
+#[test]
+fn route_failed_record() {
+    let (_runtime, o, ctx, output, dead) = deploy_map(faulty, 2);
+    let timeout = Duration::from_millis(500);
+
+    o.try_push(1, ctx);
+    o.try_push(0, ctx);
+    o.try_push(2, ctx);
+    assert_eq!(output.recv_timeout(timeout), Ok(2));
+    // The task survives the failed record.
+    assert_eq!(output.recv_timeout(timeout), Ok(3));
+
+    let letter = dead.recv_timeout(timeout).unwrap();
+    assert_eq!(letter.error, "faulty");
+    assert_eq!(letter.record, "0");
+    assert_eq!(letter.attempts, 3);
+    assert_eq!(letter.task, "f");
+}
+
+#[test]
+fn retry_failed_record() {
+    let (_runtime, o, ctx, output, dead) = deploy_map(unreliable, 1);
+    let timeout = Duration::from_millis(500);
+
+    o.try_push(1, ctx);
+    o.try_push(2, ctx);
+    assert_eq!(output.recv_timeout(timeout), Ok(2));
+    assert_eq!(output.recv_timeout(timeout), Ok(3));
+    assert!(dead.try_recv().is_err());
+}
+
+#[test]
+fn route_failed_call() {
+    let (_runtime, o, ctx, output, dead) = deploy(|builder, s| {
+        let s = builder.add_operator("halve", s, |s, ctx| protected((s, function!(halve)), ctx));
+        builder.set_retries(s.node(), 1);
+        s
+    });
+    let timeout = Duration::from_millis(500);
+
+    o.try_push(4, ctx);
+    o.try_push(3, ctx);
+    o.try_push(2, ctx);
+    assert_eq!(output.recv_timeout(timeout), Ok(2));
+    assert_eq!(output.recv_timeout(timeout), Ok(1));
+
+    let letter = dead.recv_timeout(timeout).unwrap();
+    assert_eq!(letter.task, "halve");
+    assert_eq!(letter.error, "odd");
+    assert_eq!(letter.record, "(3,)");
+    assert_eq!(letter.attempts, 2);
+}
@@ -0,0 +1,59 @@
+//! Measures the CPU time of the whole process, so it runs as its own test binary where no
+//! other tests run alongside it.
+
+use arc_runtime::data::channels::local::multicast::channel;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::prelude::*;
+
+#[rewrite(persistent)]
+async fn forward(mut a: Pullable<i32>, #[output] mut b: Pushable<i32>) {
+    loop {
+        let x: i32 = pull!(a);
+        push!(b, x);
+    }
+}
+
+/// Returns the CPU time consumed by this process, in clock ticks.
+#[cfg(target_os = "linux")]
+fn cpu_ticks() -> u64 {
+    let stat = std::fs::read_to_string("/proc/self/stat").unwrap();
+    // Skip the executable name, which may contain whitespace.
+    let fields = stat.rsplit(')').next().unwrap();
+    let fields = fields.split_whitespace().collect::<std::vec::Vec<_>>();
+    // The fields `utime` and `stime` of proc(5), counted from after the executable name.
+    let utime: u64 = fields[11].parse().unwrap();
+    let stime: u64 = fields[12].parse().unwrap();
+    utime + stime
+}
+
+fn idle(ctx: Context) -> Pullable<i32> {
+    let (o, i): (Pushable<i32>, Pullable<i32>) = channel(ctx);
+    // Keep the channel open without ever pushing into it.
+    std::mem::forget(o);
+    i
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn idle_task_does_not_spin() {
+    let runtime = Runtime::new();
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("idle", idle);
+    let s = builder.add_operator("forward", s, |s, ctx| forward((s,), ctx));
+    builder.add_sink("drop", s, |s, _| std::mem::forget(s));
+    builder.deploy(&runtime).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let before = cpu_ticks();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let after = cpu_ticks();
+
+    // A task which polls itself continuously keeps a core busy for the whole second, which is
+    // ~100 ticks. An idle pipeline should stay close to zero.
+    assert!(
+        after - before < 10,
+        "idle pipeline used {} ticks",
+        after - before
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use arc_runtime::data::channels::local::multicast::channel;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::job::metrics::Metric;
+use arc_runtime::prelude::*;
+use common::mod_collect::collect;
+use common::mod_source::source;
+use common::Collector;
+
+fn checked(x: i32) -> Result<i32, std::string::String> {
+    if x < 0 {
+        Err(format!("negative input {}", x))
+    } else {
+        Ok(x)
+    }
+}
+
+#[rewrite(nonpersistent)]
+async fn validate(mut i: Pullable<i32>, #[output] mut o: Pushable<i32>) {
+    loop {
+        let x = pull!(i);
+        let y = checked(x)?;
+        push!(o, y);
+    }
+}
+
+/// Submits `source -> validate -> collect`, and returns the job together with the output of
+/// `validate`.
+fn submit(
+    runtime: &Runtime,
+    numbers: std::vec::Vec<i32>,
+) -> (JobHandle, std::sync::mpsc::Receiver<i32>) {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let job = runtime.submit(move |ctx| {
+        let mut v: Vec<i32> = Vec::new(ctx);
+        numbers.into_iter().for_each(|x| v.push(x, ctx));
+        let s = source((v,), ctx);
+        let s = validate((s,), ctx);
+        collect((s, Collector(tx)), ctx);
+    });
+    (job, rx)
+}
+
+#[test]
+fn finished() {
+    let runtime = Runtime::new();
+    let (job, rx) = submit(&runtime, vec![1, 2, 3]);
+    assert_eq!(job.tasks().len(), 3);
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+    let timeout = std::time::Duration::from_millis(500);
+    let output = (0..3).map(|_| rx.recv_timeout(timeout).unwrap());
+    assert_eq!(output.collect::<std::vec::Vec<_>>(), vec![1, 2, 3]);
+    runtime.shutdown();
+}
+
+#[test]
+fn failed() {
+    let runtime = Runtime::new();
+    let (job, _) = submit(&runtime, vec![1, -2, 3]);
+    match job.await_completion() {
+        JobStatus::Failed(error) => assert_eq!(error.message(), "negative input -2"),
+        status => panic!("expected the job to fail, got {:?}", status),
+    }
+}
+
+#[test]
+fn failed_body() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|_| panic!("no tasks"));
+    match job.await_completion() {
+        JobStatus::Failed(error) => assert_eq!(error.message(), "no tasks"),
+        status => panic!("expected the job to fail, got {:?}", status),
+    }
+}
+
+#[test]
+fn cancelled() {
+    let runtime = Runtime::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let job = runtime.submit(move |ctx| {
+        let (o, i): (Pushable<i32>, Pullable<i32>) = channel(ctx);
+        validate((i,), ctx);
+        // The input stays open, so the job only completes when it is cancelled.
+        tx.send(o).unwrap();
+    });
+    let _input = rx.recv().unwrap();
+    assert_eq!(job.status(), JobStatus::Running);
+    job.cancel();
+    assert_eq!(job.await_completion(), JobStatus::Cancelled);
+}
+
+#[test]
+fn isolated() {
+    let runtime = Runtime::new();
+    let (failing, _) = submit(&runtime, vec![-1]);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let running = runtime.submit(move |ctx| {
+        let (o, i): (Pushable<i32>, Pullable<i32>) = channel(ctx);
+        validate((i,), ctx);
+        tx.send(o).unwrap();
+    });
+    let _input = rx.recv().unwrap();
+    assert_ne!(failing.id(), running.id());
+
+    assert!(matches!(failing.await_completion(), JobStatus::Failed(_)));
+    assert_eq!(running.status(), JobStatus::Running);
+    let jobs = runtime.jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].id(), running.id());
+
+    assert_eq!(failing.metric(Metric::TasksLaunched), 3);
+    assert_eq!(failing.metric(Metric::TaskFailures), 1);
+    assert!(failing.metric(Metric::HeapAllocations) > 0);
+    assert!(failing.metric(Metric::HeapBytes) > 0);
+    assert_eq!(running.metric(Metric::TaskFailures), 0);
+    let namespace = format!("{}.", running.id());
+    assert!(running
+        .metrics()
+        .iter()
+        .all(|(name, _)| name.starts_with(&namespace)));
+
+    running.cancel();
+    assert_eq!(running.await_completion(), JobStatus::Cancelled);
+    assert!(runtime.job(running.id()).is_none());
+}
@@ -1,4 +1,9 @@
+mod common;
+
 use arc_runtime::prelude::*;
+use common::mod_collect::collect;
+use common::mod_source::source;
+use common::Collector;
 
 declare_functions!(x);
 
@@ -112,3 +117,72 @@ fn x(x: i32) -> i32 {
 //     let s = direct_call!(map(*s, function!(x)));
 //     let s = direct_call!(log(*s));
 // }
+
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+
+#[rewrite(persistent)]
+async fn filter_map(
+    mut a: Pullable<i32>,
+    f: function!((i32) -> i32),
+    #[output] mut b: Pushable<i32>,
+) {
+    loop {
+        let x: i32 = pull!(a);
+        if x % 2 == 0 {
+            continue;
+        }
+        let y: i32 = call_indirect!(f(x));
+        push!(b, y);
+    }
+}
+
+/// Pushes ones forever. Pushing into a multicast channel never has to wait, so the task is
+/// always ready to run.
+#[rewrite(persistent)]
+async fn ones(#[output] mut o: Pushable<i32>) {
+    loop {
+        push!(o, 1);
+    }
+}
+
+#[test]
+fn busy_task_yields() {
+    let runtime = Runtime::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("ones", |ctx| ones((), ctx));
+    builder.add_sink("collect", s, move |s, ctx| collect((s, Collector(tx)), ctx));
+    let tasks = builder.deploy(&runtime).unwrap();
+
+    let timeout = std::time::Duration::from_secs(5);
+    assert_eq!(rx.recv_timeout(timeout), Ok(1));
+    // The task only handles the kill if it yields to the executor between its steps.
+    tasks[0].kill();
+    let killed = (0..50).any(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        tasks[0].status() == Some(TaskStatus::Killed)
+    });
+    assert!(killed);
+}
+
+#[test]
+fn generated_state_machine() {
+    let runtime = Runtime::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("numbers", |ctx| {
+        let v: Vec<i32> = vector![1, 2, 3, 4, 5];
+        source((v,), ctx)
+    });
+    let s = builder.add_operator("filter_map", s, |s, ctx| {
+        filter_map((s, function!(x)), ctx)
+    });
+    builder.add_sink("collect", s, move |s, ctx| collect((s, Collector(tx)), ctx));
+    builder.deploy(&runtime).unwrap();
+
+    let timeout = std::time::Duration::from_secs(5);
+    for expected in [2, 4, 6] {
+        assert_eq!(rx.recv_timeout(timeout), Ok(expected));
+    }
+}
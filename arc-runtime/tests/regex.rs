@@ -0,0 +1,68 @@
+use arc_runtime::prelude::*;
+
+#[test]
+fn matching() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let s = |x: &str| String::from_str(x, ctx);
+
+        let r = Regex::new(s(r"(\w+)@(\w+)\.com"), ctx);
+        assert!(r.clone().is_match(s("mail alice@example.com"), ctx));
+        assert!(!r.clone().is_match(s("no mail"), ctx));
+
+        let groups = r
+            .clone()
+            .captures(s("to bob@arc.com and eve@lang.com"), ctx);
+        let groups = groups.as_slice(ctx);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].as_str(), "bob@arc.com");
+        assert_eq!(groups[1].as_str(), "bob");
+        assert_eq!(groups[2].as_str(), "arc");
+        assert_eq!(r.clone().captures(s("nobody"), ctx).len(ctx), 0);
+
+        let matches = r
+            .clone()
+            .find_all(s("to bob@arc.com and eve@lang.com"), ctx);
+        let matches = matches.as_slice(ctx);
+        assert_eq!(matches[0].as_str(), "bob@arc.com");
+        assert_eq!(matches[1].as_str(), "eve@lang.com");
+
+        let replaced = r.replace_all(s("bob@arc.com, eve@lang.com"), s("$2:$1"), ctx);
+        assert_eq!(replaced.as_str(), "arc:bob, lang:eve");
+
+        assert!(Regex::try_new(s("("), ctx).is_err());
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
+
+#[test]
+fn externs() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let s = |x: &str| String::from_str(x, ctx);
+
+        let r = regex(s(r"(\d+)-(\d+)"), ctx);
+        assert!(is_match(r.clone(), s("1-2"), ctx));
+        assert!(!is_match(r.clone(), s("1+2"), ctx));
+        let groups = captures(r.clone(), s("10-20"), ctx);
+        assert_eq!(groups.as_slice(ctx)[2].as_str(), "20");
+        assert_eq!(find_all(r.clone(), s("1-2 3-4"), ctx).len(ctx), 2);
+        let replaced = replace_all(r, s("1-2 3-4"), s("$2-$1"), ctx);
+        assert_eq!(replaced.as_str(), "2-1 4-3");
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
+
+#[test]
+fn shipped() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let r = Regex::new(String::from_str("a+b", ctx), ctx);
+        let bytes = bincode::serialize(&r.into_sendable(ctx)).unwrap();
+        let pattern: <Regex as DynSharable>::T = bincode::deserialize(&bytes).unwrap();
+        let r = pattern.into_sharable(ctx);
+        assert_eq!(r.clone().pattern(ctx).as_str(), "a+b");
+        assert!(r.is_match(String::from_str("xaab", ctx), ctx));
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
@@ -0,0 +1,96 @@
+#![allow(unused)]
+
+use arc_runtime::data::schema::fingerprint;
+use arc_runtime::prelude::*;
+
+mod v1 {
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[rewrite]
+    pub enum Shape {
+        ShapeDot(Point),
+        ShapeLine(Point, Point),
+    }
+}
+
+/// The same types as `v1`, declared in another module.
+mod moved {
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[rewrite]
+    pub enum Shape {
+        ShapeDot(Point),
+        ShapeLine(Point, Point),
+    }
+}
+
+/// `v1`, where a field of `Point` has been retyped.
+mod retyped {
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub struct Point {
+        pub x: i32,
+        pub y: i64,
+    }
+
+    #[rewrite]
+    pub enum Shape {
+        ShapeDot(Point),
+        ShapeLine(Point, Point),
+    }
+}
+
+/// `v1`, where a field of `Point` has been renamed.
+mod renamed {
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub struct Point {
+        pub x: i32,
+        pub z: i32,
+    }
+}
+
+#[rewrite]
+pub enum List {
+    ListCons(i32, List),
+    ListNil(unit),
+}
+
+#[test]
+fn structural() {
+    assert_eq!(fingerprint::<v1::Point>(), fingerprint::<moved::Point>());
+    assert_eq!(fingerprint::<v1::Shape>(), fingerprint::<moved::Shape>());
+    assert_ne!(fingerprint::<v1::Point>(), fingerprint::<retyped::Point>());
+    assert_ne!(fingerprint::<v1::Point>(), fingerprint::<renamed::Point>());
+    // Changes propagate to the types which contain the changed type.
+    assert_ne!(fingerprint::<v1::Shape>(), fingerprint::<retyped::Shape>());
+}
+
+#[test]
+fn generic() {
+    assert_ne!(fingerprint::<Vec<i32>>(), fingerprint::<Vec<i64>>());
+    assert_ne!(fingerprint::<(i32, i64)>(), fingerprint::<(i64, i32)>());
+    assert_ne!(
+        fingerprint::<((i32,), i32)>(),
+        fingerprint::<(i32, (i32,))>()
+    );
+}
+
+#[test]
+fn recursive() {
+    assert_ne!(fingerprint::<List>(), fingerprint::<Vec<i32>>());
+}
@@ -0,0 +1,44 @@
+use arc_runtime::prelude::*;
+
+#[test]
+fn text() {
+    let runtime = Runtime::new();
+    let job = runtime.submit(|ctx| {
+        let s = |x: &str| String::from_str(x, ctx);
+
+        let x = s("  Hello, wörld!  ").trim(ctx);
+        assert_eq!(x.as_str(), "Hello, wörld!");
+        assert_eq!(x.clone().char_len(ctx), 13);
+        assert_eq!(x.clone().slice(7..12, ctx).unwrap().as_str(), "wörld");
+        assert_eq!(x.clone().slice(13..13, ctx).unwrap().as_str(), "");
+        assert_eq!(x.clone().slice(0..13, ctx).unwrap(), x.clone());
+        assert_eq!(x.clone().slice(12..14, ctx), None);
+        assert_eq!(x.clone().slice(14..14, ctx), None);
+        assert_eq!(x.clone().slice(5..2, ctx), None);
+        assert_eq!(x.clone().char_at(8, ctx), Some('ö'));
+        assert_eq!(x.clone().find(s("l"), ctx), Some(2));
+        assert_eq!(x.clone().find(s("d"), ctx), Some(11));
+        assert_eq!(x.clone().find(s("z"), ctx), None);
+        assert!(x.clone().contains(s("wö"), ctx));
+        assert!(x.clone().starts_with(s("Hello"), ctx));
+        assert!(x.clone().ends_with(s("!"), ctx));
+        assert_eq!(x.clone().to_uppercase(ctx).as_str(), "HELLO, WÖRLD!");
+        assert_eq!(x.clone().to_lowercase(ctx).as_str(), "hello, wörld!");
+        assert_eq!(
+            x.clone().replace(s("l"), s("L"), ctx).as_str(),
+            "HeLLo, wörLd!"
+        );
+        assert_eq!(s("a").concat(s("b"), ctx).as_str(), "ab");
+
+        let parts = s("1,22,333").split(s(","), ctx);
+        assert_eq!(parts.clone().len(ctx), 3);
+        let parts = parts.as_slice(ctx);
+        assert_eq!(parts[2].as_str(), "333");
+        assert_eq!(parts[1].clone().parse::<i32>(ctx), Ok(22));
+        assert!(s("x").parse::<f64>(ctx).is_err());
+
+        assert!(s("a") < s("b"));
+        assert_eq!(s("a"), s("a"));
+    });
+    assert_eq!(job.await_completion(), JobStatus::Finished);
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use arc_runtime::data::channels::local::multicast::channel;
+use arc_runtime::data::channels::local::multicast::Pullable;
+use arc_runtime::data::channels::local::multicast::Pushable;
+use arc_runtime::dataflow::Stream;
+use arc_runtime::prelude::*;
+use common::mod_collect::collect;
+use common::Collector;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+/// Panics on the first zero it sees.
+fn flaky(x: i32, _: Context) -> i32 {
+    static FAILED: AtomicBool = AtomicBool::new(false);
+    if x == 0 && !FAILED.swap(true, Ordering::SeqCst) {
+        panic!("flaky");
+    }
+    x + 1
+}
+
+/// Panics on every zero it sees.
+fn faulty(x: i32, _: Context) -> i32 {
+    if x == 0 {
+        panic!("faulty");
+    }
+    x + 1
+}
+
+/// Panics on the first ten it sees.
+fn explode_once(x: i32) -> i32 {
+    static FAILED: AtomicBool = AtomicBool::new(false);
+    if x == 10 && !FAILED.swap(true, Ordering::SeqCst) {
+        panic!("explode_once");
+    }
+    x
+}
+
+/// Pushes the running sum of a stream.
+#[rewrite(persistent)]
+async fn running_sum(mut a: Pullable<i32>, #[output] mut b: Pushable<i32>) {
+    let mut sum: i32 = 0;
+    loop {
+        let x: i32 = pull!(a);
+        sum = sum + explode_once(x);
+        push!(b, sum);
+    }
+}
+
+/// Adds `f` as a map.
+fn map(
+    f: fn(i32, Context) -> i32,
+) -> impl FnOnce(&mut DataflowBuilder, Stream<i32>) -> Stream<i32> {
+    move |builder, s| builder.add_map("f", s, f)
+}
+
+/// Deploys `input -> f -> collect`, where the input is pushed by the test thread.
+fn deploy(
+    add: impl FnOnce(&mut DataflowBuilder, Stream<i32>) -> Stream<i32>,
+    policy: RestartPolicy,
+) -> (Runtime, JobHandle, Pushable<i32>, Context, Receiver<i32>) {
+    let runtime = Runtime::new();
+    let (input_tx, input_rx) = std::sync::mpsc::channel();
+    let (output_tx, output_rx) = std::sync::mpsc::channel();
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("input", move |ctx| {
+        let (o, i) = channel(ctx);
+        input_tx.send(o).unwrap();
+        i
+    });
+    let s = add(&mut builder, s);
+    builder.set_restart_policy(s.node(), policy);
+    builder.add_sink("collect", s, move |s, ctx| {
+        collect((s, Collector(output_tx)), ctx)
+    });
+    let job = builder.submit(&runtime).unwrap();
+    let o = input_rx.recv().unwrap();
+    let ctx = common::context(&runtime);
+    (runtime, job, o, ctx, output_rx)
+}
+
+/// Waits for a task to reach a status.
+fn reaches(task: &TaskHandle, status: TaskStatus) -> bool {
+    (0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        task.status() == Some(status)
+    })
+}
+
+#[test]
+fn restart_on_failure() {
+    let policy = RestartPolicy::OnFailure {
+        max_restarts: 3,
+        backoff: Duration::from_millis(100),
+    };
+    let (_runtime, job, o, ctx, rx) = deploy(map(flaky), policy);
+    let task = &job.tasks()[0];
+    let timeout = Duration::from_millis(500);
+
+    o.try_push(1, ctx);
+    assert_eq!(rx.recv_timeout(timeout), Ok(2));
+
+    // The value which is pushed while the task is failing is buffered in its receiver, which
+    // is handed back to the restarted task.
+    o.try_push(0, ctx);
+    o.try_push(2, ctx);
+    assert!(reaches(task, TaskStatus::Restarting));
+    assert!(reaches(task, TaskStatus::Running));
+    assert_eq!(rx.recv_timeout(timeout), Ok(3));
+
+    // The restarted task is attached to the same channels.
+    o.try_push(3, ctx);
+    assert_eq!(rx.recv_timeout(timeout), Ok(4));
+}
+
+#[test]
+fn never_restart() {
+    let (_runtime, job, o, ctx, rx) = deploy(map(faulty), RestartPolicy::Never);
+    let task = &job.tasks()[0];
+    o.try_push(0, ctx);
+    assert!(reaches(task, TaskStatus::Failed));
+    assert_eq!(task.error().unwrap().message(), "faulty");
+    o.try_push(1, ctx);
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+}
+
+#[test]
+fn escalate() {
+    let (_runtime, job, o, ctx, _rx) = deploy(map(faulty), RestartPolicy::Escalate);
+    o.try_push(0, ctx);
+    match job.await_completion() {
+        JobStatus::Failed(error) => assert_eq!(error.message(), "faulty"),
+        status => panic!("Expected the job to fail, but it is {:?}", status),
+    }
+    // The failure kills the other tasks of the job.
+    assert_eq!(job.tasks()[1].status(), Some(TaskStatus::Killed));
+}
+
+#[test]
+fn restore_from_checkpoint() {
+    let policy = RestartPolicy::OnFailure {
+        max_restarts: 1,
+        backoff: Duration::from_millis(100),
+    };
+    let add = |builder: &mut DataflowBuilder, s: Stream<i32>| {
+        builder.add_operator("f", s, |s, ctx| running_sum((s,), ctx))
+    };
+    let (_runtime, job, o, ctx, rx) = deploy(add, policy);
+    let task = &job.tasks()[0];
+    let timeout = Duration::from_millis(500);
+
+    for x in [1, 2] {
+        o.try_push(x, ctx);
+    }
+    assert_eq!(rx.recv_timeout(timeout), Ok(1));
+    assert_eq!(rx.recv_timeout(timeout), Ok(3));
+
+    // The task fails after it took the ten out of its receiver. The restarted task continues
+    // from the checkpoint which was saved when the ten was pulled, so the ten is neither lost
+    // nor added twice.
+    o.try_push(10, ctx);
+    o.try_push(3, ctx);
+    assert!(reaches(task, TaskStatus::Restarting));
+    assert!(reaches(task, TaskStatus::Running));
+    assert_eq!(rx.recv_timeout(timeout), Ok(13));
+    assert_eq!(rx.recv_timeout(timeout), Ok(16));
+}
@@ -103,3 +103,298 @@ mod unit {
         let _x: Foo = enwrap!(FooBar, unit);
     }
 }
+
+mod comparison {
+    use arc_runtime::prelude::*;
+
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    #[rewrite(hash, ord)]
+    pub struct Key {
+        pub id: i32,
+        pub name: String,
+    }
+
+    #[rewrite(hash)]
+    pub enum Shape {
+        ShapeKey(Key),
+        ShapeNone(unit),
+    }
+
+    fn hash<T: Hash>(x: &T) -> u64 {
+        let mut state = DefaultHasher::new();
+        x.hash(&mut state);
+        state.finish()
+    }
+
+    #[test]
+    fn test() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let a: Key = new!(Key {
+                id: 1,
+                name: String::from_str("a", ctx)
+            });
+            let b: Key = new!(Key {
+                id: 1,
+                name: String::from_str("a", ctx)
+            });
+            let c: Key = new!(Key {
+                id: 1,
+                name: String::from_str("b", ctx)
+            });
+            assert_eq!(a, b);
+            assert_ne!(a, c);
+            assert!(a < c);
+            assert_eq!(hash(&a), hash(&b));
+            assert_eq!(hash(&a), hash(&a.into_sendable(ctx)));
+            assert_eq!(a.into_sendable(ctx), b.into_sendable(ctx));
+
+            let s: Shape = enwrap!(ShapeKey, a);
+            let t: Shape = enwrap!(ShapeKey, b);
+            let u: Shape = enwrap!(ShapeNone, unit);
+            assert_eq!(s, t);
+            assert_ne!(s, u);
+            assert_eq!(hash(&s), hash(&s.into_sendable(ctx)));
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}
+
+mod generics {
+    use arc_runtime::prelude::*;
+
+    #[rewrite(eq)]
+    pub struct Pair<A, B> {
+        pub a: A,
+        pub b: B,
+    }
+
+    #[rewrite]
+    pub enum Maybe<T> {
+        MaybeSome(T),
+        MaybeNone(unit),
+    }
+
+    #[test]
+    fn test() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let p: Pair<i32, String> = new!(Pair {
+                a: 1,
+                b: String::from_str("a", ctx)
+            });
+            let bytes = bincode::serialize(&p.into_sendable(ctx)).unwrap();
+            let q: <Pair<i32, String> as DynSharable>::T = bincode::deserialize(&bytes).unwrap();
+            let q: Pair<i32, String> = q.into_sharable(ctx);
+            assert_eq!(p, q);
+
+            let m: Maybe<Pair<i32, String>> = enwrap!(MaybeSome, p);
+            let m: Maybe<Pair<i32, String>> = m.into_sendable(ctx).into_sharable(ctx);
+            assert!(is!(MaybeSome, m));
+            assert_eq!(unwrap!(MaybeSome, m).a, 1);
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}
+
+mod variants {
+    use arc_runtime::prelude::*;
+
+    #[rewrite(eq)]
+    pub enum Shape {
+        ShapePoint,
+        ShapeLine(i32, i32),
+        ShapeRect { width: i32, height: i32 },
+    }
+
+    #[test]
+    fn test() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let p: Shape = enwrap!(ShapePoint);
+            let l: Shape = enwrap!(ShapeLine, 1, 2);
+            let r: Shape = enwrap!(ShapeRect {
+                width: 3,
+                height: 4
+            });
+            assert!(is!(ShapePoint, p));
+            assert!(is!(ShapeLine, l));
+            assert!(!is!(ShapeLine, r));
+            assert_eq!(unwrap!(ShapeLine(_, _), l), (1, 2));
+            assert_eq!(unwrap!(ShapeRect { height }, r), 4);
+            assert_eq!(unwrap!(ShapeRect { width, height }, r), (3, 4));
+            for s in [p, l, r] {
+                assert_eq!(s.into_sendable(ctx).into_sharable(ctx), s);
+            }
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}
+
+mod matching {
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub enum Expr {
+        ExprNum(i32),
+        ExprAdd(Expr, Expr),
+        ExprNeg { inner: Expr },
+        ExprZero,
+    }
+
+    fn eval(e: &Expr) -> i32 {
+        match_enum!(e, {
+            ExprNum(n) => *n,
+            ExprAdd(a, ExprZero) => eval(a),
+            ExprAdd(a, b) => eval(a) + eval(b),
+            ExprNeg { inner: ExprNeg { inner } } => eval(inner),
+            ExprNeg { inner } => -eval(inner),
+            ExprZero => 0,
+        })
+    }
+
+    fn describe(e: &Expr) -> &'static str {
+        match_enum!(e, {
+            ExprNum(n) if *n < 0 => "negative",
+            ExprNum(_) => "number",
+            ExprAdd(ExprNum(a), ExprNum(b)) if a == b => "double",
+            _ => "other",
+        })
+    }
+
+    /// Nested patterns are tested by guards, so the compiler does not see that the nested arms
+    /// cover an `ExprNeg`. The arm for any `ExprNeg` is required, and catches the values which
+    /// the nested arms do not match.
+    fn negation(e: &Expr) -> &'static str {
+        match_enum!(e, {
+            ExprNeg { inner: ExprNum(_) } => "negated number",
+            ExprNeg { inner: ExprZero } => "negated zero",
+            ExprNeg { .. } => "negation",
+            _ => "other",
+        })
+    }
+
+    #[test]
+    fn test() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let one: Expr = enwrap!(ExprNum, 1);
+            let two: Expr = enwrap!(ExprAdd, one.clone(), one.clone());
+            let zero: Expr = enwrap!(ExprZero);
+            let sum: Expr = enwrap!(ExprAdd, two.clone(), zero);
+            let neg: Expr = enwrap!(ExprNeg { inner: sum.clone() });
+            let pos: Expr = enwrap!(ExprNeg { inner: neg.clone() });
+            assert_eq!(eval(&sum), 2);
+            assert_eq!(eval(&neg), -2);
+            assert_eq!(eval(&pos), 2);
+            assert_eq!(describe(&one), "number");
+            assert_eq!(describe(&enwrap!(ExprNum, -1)), "negative");
+            assert_eq!(describe(&two), "double");
+            assert_eq!(describe(&sum), "other");
+            let neg_one: Expr = enwrap!(ExprNeg { inner: one.clone() });
+            let zero: Expr = enwrap!(ExprZero);
+            let neg_zero: Expr = enwrap!(ExprNeg { inner: zero });
+            assert_eq!(negation(&neg_one), "negated number");
+            assert_eq!(negation(&neg_zero), "negated zero");
+            assert_eq!(negation(&neg), "negation");
+            assert_eq!(negation(&one), "other");
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}
+
+mod mutation {
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+        pub name: String,
+    }
+
+    #[test]
+    fn test() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let a: Point = new!(Point {
+                x: 1,
+                y: 2,
+                name: String::from_str("a", ctx)
+            });
+            let b = a.clone();
+            update!(a, x, 3);
+            update!(a, name, String::from_str("b", ctx));
+            assert_eq!(b.x, 3);
+            assert_eq!(b.name.as_str(), "b");
+
+            let c: Point = with!(a, y = 4, name = String::from_str("c", ctx));
+            assert_eq!((c.x, c.y, c.name.as_str()), (3, 4, "c"));
+            assert_eq!((a.y, a.name.as_str()), (2, "b"));
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}
+
+mod formatting {
+    use arc_runtime::prelude::*;
+
+    #[rewrite]
+    pub struct Person {
+        pub name: String,
+        pub tags: Vec<String>,
+        pub shape: Shape,
+    }
+
+    #[rewrite]
+    pub enum Shape {
+        ShapePoint,
+        ShapeLine(i32, String),
+        ShapeRect { width: f64, height: f64 },
+    }
+
+    #[rewrite]
+    pub struct Pair<A, B> {
+        pub a: A,
+        pub b: B,
+    }
+
+    #[test]
+    fn test() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            assert_eq!(to_string(5, ctx).as_str(), "5");
+            assert_eq!(to_string(unit, ctx).as_str(), "unit");
+            assert_eq!(to_string(String::from_str("a", ctx), ctx).as_str(), "a");
+
+            let tags: Vec<String> = vector![String::from_str("x", ctx)];
+            let p: Person = new!(Person {
+                name: String::from_str("Ada", ctx),
+                tags: tags,
+                shape: enwrap!(ShapeLine, 1, String::from_str("c", ctx))
+            });
+            assert_eq!(
+                to_string(p.clone(), ctx).as_str(),
+                r#"Person { name: "Ada", tags: ["x"], shape: ShapeLine(1, "c") }"#
+            );
+            let s: Shape = enwrap!(ShapeRect {
+                width: 1.5,
+                height: 2.0
+            });
+            assert_eq!(
+                format!("{}", Formatted(&s)),
+                "ShapeRect { width: 1.5, height: 2 }"
+            );
+            let s: Shape = enwrap!(ShapePoint);
+            assert_eq!(to_string(s, ctx).as_str(), "ShapePoint");
+
+            let q: Pair<i32, bool> = new!(Pair { a: 1, b: true });
+            assert_eq!(to_string(q, ctx).as_str(), "Pair { a: 1, b: true }");
+            let e: Erased = erase!(p, Person);
+            assert!(to_string(e, ctx).as_str().starts_with("Person { name"));
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}
@@ -0,0 +1,139 @@
+#![allow(unused)]
+mod common;
+
+use arc_runtime::prelude::*;
+use common::mod_collect::collect;
+use common::mod_source::source;
+use common::Collector;
+
+#[test]
+fn literals() {
+    let runtime = Runtime::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let (output_tx, output_rx) = std::sync::mpsc::channel();
+    let mut builder = DataflowBuilder::new();
+    let s = builder.add_source("literals", move |ctx| {
+        let v: Vec<i32> = vector![1, 2, 3];
+        let w: Vec<i32> = vector![4];
+        let x: Vec<i32> = vector![5, 6,];
+        let slices = [&v, &w, &x].map(|v| v.as_slice(ctx).to_vec());
+        tx.send(slices).unwrap();
+        source((v,), ctx)
+    });
+    builder.add_sink("collect", s, move |s, ctx| {
+        collect((s, Collector(output_tx)), ctx)
+    });
+    builder.deploy(&runtime).unwrap();
+
+    let timeout = std::time::Duration::from_secs(5);
+    let [v, w, x] = rx.recv_timeout(timeout).unwrap();
+    assert_eq!(v, vec![1, 2, 3]);
+    assert_eq!(w, vec![4]);
+    assert_eq!(x, vec![5, 6]);
+    let output = (0..3).map(|_| output_rx.recv_timeout(timeout).unwrap());
+    assert_eq!(output.collect::<std::vec::Vec<_>>(), vec![1, 2, 3]);
+}
+
+#[allow(non_camel_case_types)]
+mod test_vectors {
+
+    use arc_runtime::prelude::*;
+
+    declare_functions!(double, is_odd, add, descending);
+
+    #[rewrite]
+    fn double(x: i32) -> i32 {
+        x * 2
+    }
+
+    #[rewrite]
+    fn is_odd(x: i32) -> bool {
+        x % 2 == 1
+    }
+
+    #[rewrite]
+    fn add(acc: i32, x: i32) -> i32 {
+        acc + x
+    }
+
+    fn descending((a, b): (i32, i32), _: Context) -> std::cmp::Ordering {
+        b.cmp(&a)
+    }
+
+    #[test]
+    fn stdlib() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let mut v: Vec<i32> = Vec::new(ctx);
+            v.push(1, ctx);
+            v.push(2, ctx);
+            v.extend(v.clone(), ctx);
+            assert_eq!(v.as_slice(ctx), &[1, 2, 1, 2]);
+            assert_eq!(v.len(ctx), 4);
+            assert_eq!(v.at(1, ctx), 2);
+            assert!(v.contains(2, ctx));
+            assert!(!v.contains(3, ctx));
+            assert_eq!(v.pop(ctx), Some(2));
+            assert_eq!(v.slice(1..3, ctx).unwrap().as_slice(ctx), &[2, 1]);
+            assert!(v.slice(3..3, ctx).unwrap().is_empty(ctx));
+            assert!(v.slice(2..4, ctx).is_none());
+            assert!(v.slice(2..1, ctx).is_none());
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+
+    #[test]
+    fn externs() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let v: Vec<i32> = array(ctx);
+            push(v.clone(), 1, ctx);
+            push(v.clone(), 2, ctx);
+            extend(v.clone(), v.clone(), ctx);
+            assert_eq!(len(v.clone(), ctx), 4);
+            assert_eq!(select(v.clone(), 1, ctx), Some(2));
+            assert_eq!(select(v.clone(), 4, ctx), None);
+            assert_eq!(select(v.clone(), -1, ctx), None);
+            assert!(contains(2, v.clone(), ctx));
+            assert!(!contains(3, v.clone(), ctx));
+            pop(v.clone(), ctx);
+            assert_eq!(v.as_slice(ctx), &[1, 2, 1]);
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+
+    #[test]
+    fn higher_order() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let mut v: Vec<i32> = vector![3, 1, 2];
+            assert_eq!(v.map(function!(double), ctx).as_slice(ctx), &[6, 2, 4]);
+            assert_eq!(v.filter(function!(is_odd), ctx).as_slice(ctx), &[3, 1]);
+            assert_eq!(v.fold(10, function!(add), ctx), 16);
+
+            v.sort(ctx);
+            assert_eq!(v.as_slice(ctx), &[1, 2, 3]);
+            assert_eq!(v.binary_search(2, ctx), Ok(1));
+            assert_eq!(v.binary_search(4, ctx), Err(3));
+            v.sort_by(function!(descending), ctx);
+            assert_eq!(v.as_slice(ctx), &[3, 2, 1]);
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+
+    #[test]
+    fn iteration() {
+        let runtime = Runtime::new();
+        let job = runtime.submit(|ctx| {
+            let mut v: Vec<i32> = vector![1, 2];
+            let mut iter = v.iter(ctx);
+            assert_eq!(iter.next(), Some(1));
+            // The iterator reads through its handle, so it sees elements pushed meanwhile.
+            for x in 3..100 {
+                v.push(x, ctx);
+            }
+            assert_eq!(iter.sum::<i32>(), (2..100).sum());
+        });
+        assert_eq!(job.await_completion(), JobStatus::Finished);
+    }
+}